@@ -0,0 +1,37 @@
+use super::{mixer::Mixer, output::AudioOutput, sample_bank::SampleBank};
+
+/// Drives the mixer in step with the runtime's clock, sending the result to an audio output.
+pub struct AudioEngine {
+    pub mixer: Mixer,
+    pub sample_bank: SampleBank,
+    output: Box<dyn AudioOutput>,
+    frames_rendered: u64,
+    mix_buffer: Vec<f32>,
+}
+
+impl AudioEngine {
+    pub fn new(output: Box<dyn AudioOutput>) -> Self {
+        AudioEngine {
+            mixer: Mixer::new(output.sample_rate()),
+            sample_bank: SampleBank::default(),
+            output,
+            frames_rendered: 0,
+            mix_buffer: Vec::new(),
+        }
+    }
+
+    /// Mix and output all audio up to the given time on the runtime's clock. Tracking the total number of frames
+    /// rendered (instead of rendering a fixed number per step) keeps rounding errors from accumulating.
+    pub fn render_until(&mut self, msecs: f64) {
+        let target_frames = ((msecs / 1000.0) * self.mixer.sample_rate() as f64).floor() as u64;
+        if target_frames <= self.frames_rendered {
+            return;
+        }
+
+        let num_frames = (target_frames - self.frames_rendered) as usize;
+        self.mix_buffer.resize(num_frames * 2, 0.0);
+        self.mixer.mix(&mut self.mix_buffer);
+        self.output.write(&self.mix_buffer);
+        self.frames_rendered = target_frames;
+    }
+}
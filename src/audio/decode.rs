@@ -0,0 +1,297 @@
+use std::{convert::TryInto, fmt, io::Cursor};
+
+use symphonia::core::{
+    audio::SampleBuffer, codecs::DecoderOptions, errors::Error as SymphoniaError,
+    formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint,
+};
+
+/// Decoded audio, downmixed to mono.
+pub struct SoundBuffer {
+    pub samples: Box<[f32]>,
+    pub sample_rate: u32,
+}
+
+impl SoundBuffer {
+    pub fn empty() -> Self {
+        SoundBuffer {
+            samples: Box::new([]),
+            sample_rate: 22050,
+        }
+    }
+
+    /// Length of the sound, in seconds.
+    pub fn duration(&self) -> f64 {
+        self.samples.len() as f64 / self.sample_rate as f64
+    }
+}
+
+impl fmt::Debug for SoundBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SoundBuffer")
+            .field("len", &self.samples.len())
+            .field("sample_rate", &self.sample_rate)
+            .finish()
+    }
+}
+
+const WAVE_FORMAT_PCM: u16 = 0x0001;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+const WAVE_FORMAT_IMA_ADPCM: u16 = 0x0011;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+struct WavFormat {
+    format_tag: u16,
+    channels: usize,
+    sample_rate: u32,
+    block_align: usize,
+    bits_per_sample: u16,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, &'static str> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or("WAV file is truncated")
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, &'static str> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or("WAV file is truncated")
+}
+
+/// Average interleaved channels down to one.
+fn downmix(interleaved: &[f32], channels: usize) -> Box<[f32]> {
+    if channels <= 1 {
+        return interleaved.into();
+    }
+    interleaved
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// Decode a WAV file containing PCM (8, 16, 24, or 32-bit), IEEE float, or IMA ADPCM audio.
+pub fn decode_wav(data: &[u8]) -> Result<SoundBuffer, &'static str> {
+    if data.get(0..4) != Some(b"RIFF") || data.get(8..12) != Some(b"WAVE") {
+        return Err("not a WAV file");
+    }
+
+    let mut format: Option<WavFormat> = None;
+    let mut sample_data: Option<&[u8]> = None;
+
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let chunk_id = &data[offset..offset + 4];
+        let chunk_len = read_u32(data, offset + 4)? as usize;
+        let chunk_start = offset + 8;
+        // Some encoders write a bogus length for the last chunk, so clamp it to the end of the file.
+        let chunk_end = chunk_start.saturating_add(chunk_len).min(data.len());
+        let chunk = &data[chunk_start..chunk_end];
+
+        match chunk_id {
+            b"fmt " => {
+                let mut format_tag = read_u16(chunk, 0)?;
+                if format_tag == WAVE_FORMAT_EXTENSIBLE {
+                    // The real format tag is the first two bytes of the subformat GUID.
+                    format_tag = read_u16(chunk, 24)?;
+                }
+                format = Some(WavFormat {
+                    format_tag,
+                    channels: read_u16(chunk, 2)? as usize,
+                    sample_rate: read_u32(chunk, 4)?,
+                    block_align: read_u16(chunk, 12)? as usize,
+                    bits_per_sample: read_u16(chunk, 14)?,
+                });
+            }
+            b"data" => sample_data = Some(chunk),
+            _ => {}
+        }
+
+        // Chunks are padded to an even length.
+        offset = chunk_start + chunk_len + (chunk_len & 1);
+    }
+
+    let format = format.ok_or("WAV file has no fmt chunk")?;
+    let sample_data = sample_data.ok_or("WAV file has no data chunk")?;
+    if format.channels == 0 || format.sample_rate == 0 {
+        return Err("WAV file has an invalid format");
+    }
+
+    let interleaved: Vec<f32> = match (format.format_tag, format.bits_per_sample) {
+        (WAVE_FORMAT_PCM, 8) => sample_data
+            .iter()
+            .map(|sample| (*sample as f32 - 128.0) / 128.0)
+            .collect(),
+        (WAVE_FORMAT_PCM, 16) => sample_data
+            .chunks_exact(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0)
+            .collect(),
+        (WAVE_FORMAT_PCM, 24) => sample_data
+            .chunks_exact(3)
+            .map(|bytes| {
+                i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) as f32 / 2147483648.0
+            })
+            .collect(),
+        (WAVE_FORMAT_PCM, 32) => sample_data
+            .chunks_exact(4)
+            .map(|bytes| i32::from_le_bytes(bytes.try_into().unwrap()) as f32 / 2147483648.0)
+            .collect(),
+        (WAVE_FORMAT_IEEE_FLOAT, 32) => sample_data
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect(),
+        (WAVE_FORMAT_IMA_ADPCM, 4) => {
+            decode_ima_adpcm(sample_data, format.channels, format.block_align)?
+        }
+        _ => return Err("unsupported WAV sample format"),
+    };
+
+    Ok(SoundBuffer {
+        samples: downmix(&interleaved, format.channels),
+        sample_rate: format.sample_rate,
+    })
+}
+
+const IMA_STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+const IMA_INDEX_TABLE: [i32; 16] = [-1, -1, -1, -1, 2, 4, 6, 8, -1, -1, -1, -1, 2, 4, 6, 8];
+
+/// Decoder state for one channel of IMA ADPCM audio.
+struct ImaChannel {
+    predictor: i32,
+    step_index: i32,
+}
+
+impl ImaChannel {
+    fn decode_nibble(&mut self, nibble: u8) -> f32 {
+        let step = IMA_STEP_TABLE[self.step_index as usize];
+        let mut delta = step >> 3;
+        if nibble & 1 != 0 {
+            delta += step >> 2;
+        }
+        if nibble & 2 != 0 {
+            delta += step >> 1;
+        }
+        if nibble & 4 != 0 {
+            delta += step;
+        }
+        if nibble & 8 != 0 {
+            delta = -delta;
+        }
+
+        self.predictor = (self.predictor + delta).clamp(-32768, 32767);
+        self.step_index = (self.step_index + IMA_INDEX_TABLE[nibble as usize]).clamp(0, 88);
+        self.predictor as f32 / 32768.0
+    }
+}
+
+/// Decode IMA ADPCM audio into interleaved samples. Each block starts with a 4-byte header per channel, followed by
+/// groups of 4 bytes (8 samples) per channel.
+fn decode_ima_adpcm(
+    data: &[u8],
+    channels: usize,
+    block_align: usize,
+) -> Result<Vec<f32>, &'static str> {
+    if block_align < channels * 4 {
+        return Err("invalid IMA ADPCM block size");
+    }
+
+    let mut samples = Vec::with_capacity(data.len() * 2);
+
+    for block in data.chunks(block_align) {
+        if block.len() < channels * 4 {
+            break;
+        }
+
+        let mut decoders: Vec<ImaChannel> = block
+            .chunks_exact(4)
+            .take(channels)
+            .map(|header| ImaChannel {
+                predictor: i16::from_le_bytes([header[0], header[1]]) as i32,
+                step_index: (header[2] as i32).clamp(0, 88),
+            })
+            .collect();
+
+        // The header's predictor is the block's first sample.
+        for decoder in &decoders {
+            samples.push(decoder.predictor as f32 / 32768.0);
+        }
+
+        let mut frames = vec![0f32; 8 * channels];
+        for group in block[channels * 4..].chunks_exact(4 * channels) {
+            for (channel, decoder) in decoders.iter_mut().enumerate() {
+                let bytes = &group[channel * 4..(channel + 1) * 4];
+                for (i, byte) in bytes.iter().enumerate() {
+                    frames[(i * 2 * channels) + channel] = decoder.decode_nibble(byte & 0x0f);
+                    frames[(((i * 2) + 1) * channels) + channel] = decoder.decode_nibble(byte >> 4);
+                }
+            }
+            samples.extend_from_slice(&frames);
+        }
+    }
+
+    Ok(samples)
+}
+
+/// Decode an MP3 file.
+pub fn decode_mp3(data: &[u8]) -> Result<SoundBuffer, &'static str> {
+    let source = MediaSourceStream::new(Box::new(Cursor::new(data.to_vec())), Default::default());
+    let mut hint = Hint::new();
+    hint.with_extension("mp3");
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|_| "could not read MP3 file")?;
+    let mut format = probed.format;
+    let track = format.default_track().ok_or("MP3 file has no tracks")?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|_| "could not create MP3 decoder")?;
+
+    let mut samples = Vec::new();
+    let mut sample_rate = track.codec_params.sample_rate.unwrap_or(0);
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(_)) => break,
+            Err(_) => return Err("could not read MP3 packet"),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // Skip over corrupt frames, like browsers do.
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(_) => return Err("could not decode MP3 packet"),
+        };
+        let spec = *decoded.spec();
+        sample_rate = spec.rate;
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend_from_slice(&downmix(buffer.samples(), spec.channels.count()));
+    }
+
+    if sample_rate == 0 {
+        return Err("MP3 file has no sample rate");
+    }
+
+    Ok(SoundBuffer {
+        samples: samples.into_boxed_slice(),
+        sample_rate,
+    })
+}
@@ -0,0 +1,212 @@
+use std::io::{self, Write};
+
+use super::sample_bank::{DRUMS, INSTRUMENTS};
+
+/// What a music extension block played.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MusicEventKind {
+    Note { note: f64, instrument: usize },
+    Drum { drum: usize },
+}
+
+/// A note or drum played by the music extension, timed by the runtime clock.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MusicEvent {
+    /// When the note started, in milliseconds on the runtime clock.
+    pub time: f64,
+    /// How long the note was held for, in milliseconds.
+    pub duration: f64,
+    /// Tempo when the note was played, in beats per minute.
+    pub tempo: f64,
+    /// Volume of the sprite that played the note, from 0 to 100.
+    pub volume: f64,
+    pub kind: MusicEventKind,
+}
+
+/// General MIDI program (0-indexed) for each of Scratch's instruments.
+const INSTRUMENT_PROGRAMS: [u8; INSTRUMENTS.len()] = [
+    0,   // Piano: Acoustic Grand Piano
+    4,   // Electric Piano: Electric Piano 1
+    19,  // Organ: Church Organ
+    24,  // Guitar: Acoustic Guitar (nylon)
+    26,  // Electric Guitar: Electric Guitar (jazz)
+    32,  // Bass: Acoustic Bass
+    45,  // Pizzicato: Pizzicato Strings
+    42,  // Cello
+    57,  // Trombone
+    71,  // Clarinet
+    65,  // Saxophone: Alto Sax
+    73,  // Flute
+    75,  // Wooden Flute: Pan Flute
+    70,  // Bassoon
+    52,  // Choir: Choir Aahs
+    11,  // Vibraphone
+    10,  // Music Box
+    114, // Steel Drum: Steel Drums
+    12,  // Marimba
+    80,  // Synth Lead: Lead 1 (square)
+    89,  // Synth Pad: Pad 2 (warm)
+];
+
+/// General MIDI percussion key for each of Scratch's drums.
+const DRUM_KEYS: [u8; DRUMS.len()] = [
+    38, // Snare Drum: Acoustic Snare
+    36, // Bass Drum: Bass Drum 1
+    37, // Side Stick
+    49, // Crash Cymbal: Crash Cymbal 1
+    46, // Open Hi-Hat
+    42, // Closed Hi-Hat
+    54, // Tambourine
+    39, // Hand Clap
+    75, // Claves
+    76, // Wood Block: Hi Wood Block
+    56, // Cowbell
+    81, // Triangle: Open Triangle
+    60, // Bongo: Hi Bongo
+    63, // Conga: Open Hi Conga
+    69, // Cabasa
+    73, // Guiro: Short Guiro
+    58, // Vibraslap
+    79, // Cuica: Open Cuica
+];
+
+const TICKS_PER_BEAT: u16 = 480;
+const PERCUSSION_CHANNEL: u8 = 9;
+/// Channels available for instruments: all 16 except the percussion channel.
+const MELODIC_CHANNELS: usize = 15;
+
+/// The MIDI channel for the nth instrument played, skipping the percussion channel.
+fn melodic_channel(idx: usize) -> u8 {
+    let channel = (idx % MELODIC_CHANNELS) as u8;
+    if channel >= PERCUSSION_CHANNEL {
+        channel + 1
+    } else {
+        channel
+    }
+}
+
+/// Converts runtime clock times to MIDI ticks, following the tempo changes seen in the event log.
+struct TempoMap {
+    /// (time in milliseconds, tempo) for each tempo change, in order.
+    changes: Vec<(f64, f64)>,
+}
+
+impl TempoMap {
+    fn new(events: &[MusicEvent]) -> Self {
+        let mut changes: Vec<(f64, f64)> = Vec::new();
+        for event in events {
+            match changes.last() {
+                Some((_, tempo)) if *tempo == event.tempo => {}
+                // The first tempo applies from the start of the file.
+                None => changes.push((0.0, event.tempo)),
+                Some(_) => changes.push((event.time, event.tempo)),
+            }
+        }
+        TempoMap { changes }
+    }
+
+    fn ticks_at(&self, time: f64) -> u64 {
+        let mut beats = 0.0;
+        for (i, (start, tempo)) in self.changes.iter().enumerate() {
+            if time <= *start {
+                break;
+            }
+            let end = self
+                .changes
+                .get(i + 1)
+                .map_or(time, |(next_start, _)| next_start.min(time));
+            beats += ((end - start) / 60_000.0) * tempo;
+        }
+        (beats * TICKS_PER_BEAT as f64).round() as u64
+    }
+}
+
+/// Append a MIDI variable-length quantity.
+fn write_var_len(out: &mut Vec<u8>, mut value: u64) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value > 0 {
+        bytes.push(((value & 0x7f) as u8) | 0x80);
+        value >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+/// Write a music event log as a format 0 Standard MIDI File. Each instrument gets its own channel (sharing channels
+/// once they run out), and drums go on the General MIDI percussion channel.
+pub fn write_midi<W: Write>(writer: &mut W, events: &[MusicEvent]) -> io::Result<()> {
+    let tempo_map = TempoMap::new(events);
+
+    // (tick, order, message). Note-offs sort before note-ons at the same tick so repeated notes aren't cut short.
+    let mut messages: Vec<(u64, u8, Vec<u8>)> = Vec::new();
+
+    for (time, tempo) in &tempo_map.changes {
+        let micros_per_beat = (60_000_000.0 / tempo).round() as u32;
+        let mut message = vec![0xff, 0x51, 0x03];
+        message.extend_from_slice(&micros_per_beat.to_be_bytes()[1..]);
+        messages.push((tempo_map.ticks_at(*time), 0, message));
+    }
+
+    let mut channel_instruments: Vec<usize> = Vec::new();
+    let mut channel_programs: [Option<u8>; 16] = [None; 16];
+    for event in events {
+        let velocity = ((event.volume / 100.0) * 127.0).round().clamp(1.0, 127.0) as u8;
+        let (channel, key) = match event.kind {
+            MusicEventKind::Note { note, instrument } => {
+                let channel_idx = match channel_instruments.iter().position(|i| *i == instrument) {
+                    Some(idx) => idx,
+                    None => {
+                        channel_instruments.push(instrument);
+                        channel_instruments.len() - 1
+                    }
+                };
+                let channel = melodic_channel(channel_idx);
+                // Channels are shared once they run out, so switch programs whenever a different instrument plays.
+                let program = INSTRUMENT_PROGRAMS[instrument];
+                if channel_programs[channel as usize] != Some(program) {
+                    channel_programs[channel as usize] = Some(program);
+                    messages.push((
+                        tempo_map.ticks_at(event.time),
+                        1,
+                        vec![0xc0 | channel, program],
+                    ));
+                }
+                (channel, note.round().clamp(0.0, 127.0) as u8)
+            }
+            MusicEventKind::Drum { drum } => (PERCUSSION_CHANNEL, DRUM_KEYS[drum]),
+        };
+
+        let start = tempo_map.ticks_at(event.time);
+        // Give zero-length drum hits a tick so the note-off doesn't land on top of the note-on.
+        let end = tempo_map
+            .ticks_at(event.time + event.duration)
+            .max(start + 1);
+        messages.push((start, 3, vec![0x90 | channel, key, velocity]));
+        messages.push((end, 2, vec![0x80 | channel, key, 0]));
+    }
+
+    // A stable sort keeps events at the same tick in the order they were played.
+    messages.sort_by_key(|(tick, order, _)| (*tick, *order));
+
+    let mut track = Vec::new();
+    let mut last_tick = 0;
+    for (tick, _, message) in &messages {
+        write_var_len(&mut track, tick - last_tick);
+        track.extend_from_slice(message);
+        last_tick = *tick;
+    }
+    // End of track
+    track.extend_from_slice(&[0x00, 0xff, 0x2f, 0x00]);
+
+    writer.write_all(b"MThd")?;
+    writer.write_all(&6u32.to_be_bytes())?;
+    writer.write_all(&0u16.to_be_bytes())?;
+    writer.write_all(&1u16.to_be_bytes())?;
+    writer.write_all(&TICKS_PER_BEAT.to_be_bytes())?;
+
+    writer.write_all(b"MTrk")?;
+    writer.write_all(&(track.len() as u32).to_be_bytes())?;
+    writer.write_all(&track)?;
+
+    Ok(())
+}
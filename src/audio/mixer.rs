@@ -0,0 +1,217 @@
+use std::{collections::HashMap, rc::Rc};
+
+use super::decode::SoundBuffer;
+
+pub type VoiceID = u64;
+
+/// How an owner's sounds are played back. Applies to sounds that are already playing, not just new ones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoiceParams {
+    pub gain: f32,
+    /// Speed multiplier. Like scratch-audio, changing the pitch changes the speed too.
+    pub playback_rate: f64,
+    pub left_gain: f32,
+    pub right_gain: f32,
+}
+
+impl Default for VoiceParams {
+    fn default() -> Self {
+        VoiceParams {
+            gain: 1.0,
+            playback_rate: 1.0,
+            left_gain: 1.0,
+            right_gain: 1.0,
+        }
+    }
+}
+
+/// A sprite sound being played: the execution context that started it, and the index of the sound in its target.
+#[derive(PartialEq)]
+struct SpriteSound {
+    owner: usize,
+    sound_index: usize,
+}
+
+/// Playback settings for a music extension note or drum. Unlike sprite sounds, these don't follow the sprite's volume
+/// or effects after they start.
+#[derive(Debug, Clone, Copy)]
+pub struct NoteParams {
+    pub gain: f32,
+    pub playback_rate: f64,
+    /// How long to play the note before releasing it, in seconds. `None` plays the whole sample.
+    pub duration: Option<f64>,
+    /// How long the note takes to fade out once released, in seconds.
+    pub release: f64,
+}
+
+/// One playing instance of a sound.
+struct Voice {
+    id: VoiceID,
+    buffer: Rc<SoundBuffer>,
+    /// Playback position, in (fractional) samples of the source buffer.
+    position: f64,
+    /// The sprite sound this voice is playing, or `None` for music extension notes and drums.
+    sprite_sound: Option<SpriteSound>,
+    note: NoteParams,
+    /// Output frames mixed so far, for timing note releases.
+    frames_played: u64,
+}
+
+impl Voice {
+    fn is_finished(&self, output_rate: f64) -> bool {
+        if self.position as usize >= self.buffer.samples.len() {
+            return true;
+        }
+        match self.note.duration {
+            Some(duration) => {
+                self.frames_played as f64 >= (duration + self.note.release) * output_rate
+            }
+            None => false,
+        }
+    }
+
+    /// Gain from the note's release envelope at the current position.
+    fn envelope(&self, output_rate: f64) -> f32 {
+        let duration = match self.note.duration {
+            Some(duration) => duration,
+            None => return 1.0,
+        };
+        let released_for = (self.frames_played as f64 / output_rate) - duration;
+        if released_for <= 0.0 {
+            1.0
+        } else {
+            (1.0 - (released_for / self.note.release)).max(0.0) as f32
+        }
+    }
+}
+
+/// Mixes every playing sound into a single stereo stream, resampling each one to the output sample rate.
+pub struct Mixer {
+    sample_rate: u32,
+    voices: Vec<Voice>,
+    next_voice_id: VoiceID,
+    owner_params: HashMap<usize, VoiceParams>,
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32) -> Self {
+        Mixer {
+            sample_rate,
+            voices: Vec::new(),
+            next_voice_id: 1,
+            owner_params: HashMap::new(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Start playing a sound. Like scratch-audio, each sprite can only play one copy of each sound at a time, so this
+    /// stops the sound if it's already playing.
+    pub fn play(&mut self, buffer: Rc<SoundBuffer>, owner: usize, sound_index: usize) -> VoiceID {
+        let sprite_sound = SpriteSound { owner, sound_index };
+        self.voices
+            .retain(|voice| voice.sprite_sound.as_ref() != Some(&sprite_sound));
+
+        self.add_voice(
+            buffer,
+            Some(sprite_sound),
+            NoteParams {
+                gain: 1.0,
+                playback_rate: 1.0,
+                duration: None,
+                release: 0.0,
+            },
+        )
+    }
+
+    /// Start playing a music extension note or drum.
+    pub fn play_note(&mut self, buffer: Rc<SoundBuffer>, note: NoteParams) -> VoiceID {
+        self.add_voice(buffer, None, note)
+    }
+
+    fn add_voice(
+        &mut self,
+        buffer: Rc<SoundBuffer>,
+        sprite_sound: Option<SpriteSound>,
+        note: NoteParams,
+    ) -> VoiceID {
+        let id = self.next_voice_id;
+        self.next_voice_id += 1;
+        self.voices.push(Voice {
+            id,
+            buffer,
+            position: 0.0,
+            sprite_sound,
+            note,
+            frames_played: 0,
+        });
+        id
+    }
+
+    /// The number of music extension notes and drums currently playing.
+    pub fn num_notes_playing(&self) -> usize {
+        self.voices
+            .iter()
+            .filter(|voice| voice.sprite_sound.is_none())
+            .count()
+    }
+
+    /// Set the volume and effects for every sound played by the given owner.
+    pub fn set_params(&mut self, owner: usize, params: VoiceParams) {
+        self.owner_params.insert(owner, params);
+    }
+
+    pub fn is_playing(&self, id: VoiceID) -> bool {
+        self.voices.iter().any(|voice| voice.id == id)
+    }
+
+    pub fn stop_all(&mut self) {
+        self.voices.clear();
+    }
+
+    /// Mix the next `output.len() / 2` frames of audio into `output`, as interleaved stereo.
+    pub fn mix(&mut self, output: &mut [f32]) {
+        output.iter_mut().for_each(|sample| *sample = 0.0);
+
+        let output_rate = self.sample_rate as f64;
+        for voice in &mut self.voices {
+            let params = match &voice.sprite_sound {
+                Some(sprite_sound) => self
+                    .owner_params
+                    .get(&sprite_sound.owner)
+                    .copied()
+                    .unwrap_or_default(),
+                None => VoiceParams::default(),
+            };
+            let buffer = Rc::clone(&voice.buffer);
+            let samples = &buffer.samples;
+            let step = (buffer.sample_rate as f64 / output_rate)
+                * params.playback_rate
+                * voice.note.playback_rate;
+            let left_gain = params.gain * params.left_gain * voice.note.gain;
+            let right_gain = params.gain * params.right_gain * voice.note.gain;
+
+            for frame in output.chunks_exact_mut(2) {
+                if voice.is_finished(output_rate) {
+                    break;
+                }
+                let index = voice.position as usize;
+
+                // Linearly interpolate between source samples.
+                let fraction = (voice.position - index as f64) as f32;
+                let next = samples.get(index + 1).copied().unwrap_or(0.0);
+                let sample = (samples[index] + ((next - samples[index]) * fraction))
+                    * voice.envelope(output_rate);
+
+                frame[0] += sample * left_gain;
+                frame[1] += sample * right_gain;
+                voice.position += step;
+                voice.frames_played += 1;
+            }
+        }
+
+        self.voices.retain(|voice| !voice.is_finished(output_rate));
+    }
+}
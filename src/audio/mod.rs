@@ -0,0 +1,7 @@
+pub mod audio_engine;
+pub mod decode;
+pub mod midi;
+pub mod mixer;
+pub mod output;
+pub mod sample_bank;
+pub mod wav;
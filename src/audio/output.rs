@@ -0,0 +1,63 @@
+use std::{cell::RefCell, rc::Rc};
+
+/// Somewhere to send mixed audio. Samples are interleaved stereo (left, right), nominally in the range -1 to 1.
+pub trait AudioOutput {
+    fn sample_rate(&self) -> u32;
+    fn write(&mut self, samples: &[f32]);
+}
+
+/// Discards all audio. Sounds still "play" (and take time to finish), but nothing is heard.
+pub struct NullOutput {
+    sample_rate: u32,
+}
+
+impl NullOutput {
+    pub fn new(sample_rate: u32) -> Self {
+        NullOutput { sample_rate }
+    }
+}
+
+impl AudioOutput for NullOutput {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write(&mut self, _samples: &[f32]) {}
+}
+
+/// Collects all audio into a buffer in memory. Clones share the same buffer, so keep a clone around to read the
+/// samples back after handing this to the runtime.
+#[derive(Clone)]
+pub struct BufferOutput {
+    sample_rate: u32,
+    samples: Rc<RefCell<Vec<f32>>>,
+}
+
+impl BufferOutput {
+    pub fn new(sample_rate: u32) -> Self {
+        BufferOutput {
+            sample_rate,
+            samples: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Take all the samples written so far, leaving the buffer empty.
+    pub fn take_samples(&self) -> Vec<f32> {
+        std::mem::take(&mut self.samples.borrow_mut())
+    }
+
+    /// The number of stereo frames written so far.
+    pub fn len_frames(&self) -> usize {
+        self.samples.borrow().len() / 2
+    }
+}
+
+impl AudioOutput for BufferOutput {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write(&mut self, samples: &[f32]) {
+        self.samples.borrow_mut().extend_from_slice(samples);
+    }
+}
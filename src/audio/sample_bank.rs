@@ -0,0 +1,331 @@
+use std::{f64::consts::TAU, rc::Rc};
+
+use super::decode::SoundBuffer;
+
+/// Sample rate of the synthesized samples. The mixer resamples them to the output rate.
+const SAMPLE_RATE: u32 = 22050;
+
+/// Peak level every synthesized sample is normalized to.
+const PEAK: f32 = 0.8;
+
+/// One of the music extension's instruments, in Scratch's order.
+pub struct InstrumentInfo {
+    pub name: &'static str,
+    /// MIDI note numbers of this instrument's samples. Notes are played by pitch-shifting the closest one.
+    pub sample_notes: &'static [u8],
+    /// How long a note takes to fade out after its duration is up, in seconds.
+    pub release_time: f64,
+    synth: InstrumentSynth,
+}
+
+/// One of the music extension's drums, in Scratch's order.
+pub struct DrumInfo {
+    pub name: &'static str,
+    synth: DrumSynth,
+}
+
+/// Parameters for synthesizing an instrument's samples additively.
+struct InstrumentSynth {
+    /// Amplitude of each harmonic, starting with the fundamental.
+    harmonics: &'static [f32],
+    /// Attack time, in seconds.
+    attack: f64,
+    /// Exponential decay rate, per second. 0 for instruments that sustain.
+    decay: f64,
+    vibrato: bool,
+    /// Amount of breath noise mixed in.
+    breath: f32,
+}
+
+/// Parameters for synthesizing a drum hit from a pitch-swept tone and noise.
+struct DrumSynth {
+    tone_start: f64,
+    tone_end: f64,
+    tone_amp: f32,
+    noise_amp: f32,
+    /// Whether to high-pass the noise, for cymbals and shakers.
+    bright: bool,
+    /// Exponential decay rate, per second.
+    decay: f64,
+    /// Length of the sample, in seconds.
+    length: f64,
+    /// Frequency of amplitude modulation, for rattling sounds. 0 for none.
+    rattle: f64,
+}
+
+#[allow(clippy::too_many_arguments)]
+const fn instrument(
+    name: &'static str,
+    sample_notes: &'static [u8],
+    release_time: f64,
+    harmonics: &'static [f32],
+    attack: f64,
+    decay: f64,
+    vibrato: bool,
+    breath: f32,
+) -> InstrumentInfo {
+    InstrumentInfo {
+        name,
+        sample_notes,
+        release_time,
+        synth: InstrumentSynth {
+            harmonics,
+            attack,
+            decay,
+            vibrato,
+            breath,
+        },
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+const fn drum(
+    name: &'static str,
+    tone_start: f64,
+    tone_end: f64,
+    tone_amp: f32,
+    noise_amp: f32,
+    bright: bool,
+    decay: f64,
+    length: f64,
+    rattle: f64,
+) -> DrumInfo {
+    DrumInfo {
+        name,
+        synth: DrumSynth {
+            tone_start,
+            tone_end,
+            tone_amp,
+            noise_amp,
+            bright,
+            decay,
+            length,
+            rattle,
+        },
+    }
+}
+
+/// Scratch's instruments. The sample notes and release times are the same as scratch-vm's; the samples themselves are
+/// synthesized stand-ins for Scratch's recordings.
+#[rustfmt::skip]
+pub const INSTRUMENTS: [InstrumentInfo; 21] = [
+    instrument("Piano", &[24, 36, 48, 60, 72, 84, 96, 108], 0.5, &[1.0, 0.5, 0.3, 0.15, 0.1, 0.05], 0.005, 1.5, false, 0.0),
+    instrument("Electric Piano", &[60], 0.5, &[1.0, 0.2, 0.05, 0.1], 0.005, 1.2, false, 0.0),
+    instrument("Organ", &[60], 0.5, &[1.0, 0.8, 0.6, 0.0, 0.4, 0.0, 0.3, 0.2], 0.02, 0.0, false, 0.0),
+    instrument("Guitar", &[60], 0.5, &[1.0, 0.6, 0.4, 0.3, 0.2, 0.1], 0.003, 2.0, false, 0.0),
+    instrument("Electric Guitar", &[60], 0.5, &[1.0, 0.8, 0.6, 0.5, 0.4, 0.3, 0.2], 0.003, 0.8, false, 0.0),
+    instrument("Bass", &[36, 48], 0.25, &[1.0, 0.4, 0.2, 0.1], 0.005, 1.5, false, 0.0),
+    instrument("Pizzicato", &[60], 0.25, &[1.0, 0.5, 0.3, 0.2], 0.002, 6.0, false, 0.0),
+    instrument("Cello", &[36, 48, 60], 0.1, &[1.0, 0.7, 0.5, 0.4, 0.3, 0.2, 0.15, 0.1], 0.08, 0.0, true, 0.0),
+    instrument("Trombone", &[36, 48, 60], 0.01, &[1.0, 0.9, 0.7, 0.5, 0.4, 0.3], 0.05, 0.0, false, 0.0),
+    instrument("Clarinet", &[48, 60], 0.01, &[1.0, 0.0, 0.6, 0.0, 0.4, 0.0, 0.2], 0.04, 0.0, false, 0.02),
+    instrument("Saxophone", &[36, 60, 84], 0.01, &[1.0, 0.8, 0.6, 0.4, 0.3, 0.2, 0.1], 0.04, 0.0, true, 0.03),
+    instrument("Flute", &[60, 72], 0.01, &[1.0, 0.2, 0.05], 0.06, 0.0, true, 0.08),
+    instrument("Wooden Flute", &[60, 72], 0.01, &[1.0, 0.3, 0.1], 0.04, 0.0, false, 0.1),
+    instrument("Bassoon", &[36, 48, 60], 0.01, &[1.0, 0.9, 0.7, 0.6, 0.4], 0.05, 0.0, false, 0.0),
+    instrument("Choir", &[48, 60, 72], 0.25, &[1.0, 0.5, 0.3, 0.2], 0.15, 0.0, true, 0.02),
+    instrument("Vibraphone", &[60, 72], 0.2, &[1.0, 0.0, 0.0, 0.3], 0.002, 1.2, true, 0.0),
+    instrument("Music Box", &[60], 0.25, &[1.0, 0.0, 0.0, 0.0, 0.4], 0.001, 3.0, false, 0.0),
+    instrument("Steel Drum", &[60], 0.5, &[1.0, 0.5, 0.0, 0.3, 0.2], 0.002, 2.0, false, 0.0),
+    instrument("Marimba", &[60], 0.01, &[1.0, 0.0, 0.0, 0.2], 0.002, 5.0, false, 0.0),
+    instrument("Synth Lead", &[60], 0.01, &[1.0, 0.0, 0.33, 0.0, 0.2, 0.0, 0.14, 0.0, 0.11], 0.01, 0.0, false, 0.0),
+    instrument("Synth Pad", &[60], 0.25, &[1.0, 0.5, 0.33, 0.25, 0.2, 0.16], 0.3, 0.0, false, 0.0),
+];
+
+/// Scratch's drums, synthesized.
+#[rustfmt::skip]
+pub const DRUMS: [DrumInfo; 18] = [
+    drum("Snare Drum", 200.0, 180.0, 0.5, 0.8, true, 18.0, 0.4, 0.0),
+    drum("Bass Drum", 120.0, 45.0, 1.0, 0.05, false, 10.0, 0.5, 0.0),
+    drum("Side Stick", 800.0, 600.0, 0.6, 0.3, true, 60.0, 0.15, 0.0),
+    drum("Crash Cymbal", 0.0, 0.0, 0.0, 0.7, true, 2.5, 2.0, 0.0),
+    drum("Open Hi-Hat", 0.0, 0.0, 0.0, 0.6, true, 6.0, 0.6, 0.0),
+    drum("Closed Hi-Hat", 0.0, 0.0, 0.0, 0.6, true, 40.0, 0.12, 0.0),
+    drum("Tambourine", 6000.0, 6000.0, 0.1, 0.5, true, 10.0, 0.4, 20.0),
+    drum("Hand Clap", 0.0, 0.0, 0.0, 0.8, false, 25.0, 0.25, 0.0),
+    drum("Claves", 2500.0, 2500.0, 0.8, 0.0, false, 40.0, 0.15, 0.0),
+    drum("Wood Block", 1200.0, 1100.0, 0.8, 0.05, false, 35.0, 0.15, 0.0),
+    drum("Cowbell", 800.0, 800.0, 0.7, 0.0, false, 12.0, 0.4, 0.0),
+    drum("Triangle", 4000.0, 4000.0, 0.4, 0.0, false, 2.0, 1.5, 0.0),
+    drum("Bongo", 400.0, 350.0, 0.8, 0.05, false, 20.0, 0.25, 0.0),
+    drum("Conga", 250.0, 220.0, 0.8, 0.05, false, 14.0, 0.35, 0.0),
+    drum("Cabasa", 0.0, 0.0, 0.0, 0.5, true, 25.0, 0.2, 0.0),
+    drum("Guiro", 0.0, 0.0, 0.0, 0.5, false, 4.0, 0.5, 30.0),
+    drum("Vibraslap", 900.0, 900.0, 0.4, 0.2, true, 4.0, 1.0, 25.0),
+    drum("Cuica", 500.0, 900.0, 0.7, 0.0, false, 8.0, 0.4, 0.0),
+];
+
+/// Frequency of a MIDI note, in Hz.
+pub fn note_frequency(note: f64) -> f64 {
+    440.0 * 2f64.powf((note - 69.0) / 12.0)
+}
+
+/// A deterministic noise source, so that renders are identical from run to run.
+struct Noise(u32);
+
+impl Noise {
+    fn next(&mut self) -> f32 {
+        self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        ((self.0 >> 8) as f32 / (1u32 << 23) as f32) - 1.0
+    }
+}
+
+fn normalize(mut samples: Vec<f32>) -> SoundBuffer {
+    let peak = samples.iter().fold(0f32, |peak, s| peak.max(s.abs()));
+    if peak > 0.0 {
+        let scale = PEAK / peak;
+        samples.iter_mut().for_each(|s| *s *= scale);
+    }
+
+    SoundBuffer {
+        samples: samples.into_boxed_slice(),
+        sample_rate: SAMPLE_RATE,
+    }
+}
+
+fn synthesize_instrument(synth: &InstrumentSynth, note: u8, seed: u32) -> SoundBuffer {
+    let rate = SAMPLE_RATE as f64;
+    let frequency = note_frequency(note as f64);
+    // Decaying instruments stop once they're inaudible (-60dB); sustained ones last long enough for most notes.
+    let length = if synth.decay > 0.0 {
+        (1000f64.ln() / synth.decay).min(3.0)
+    } else {
+        2.0
+    };
+    let num_samples = (length * rate) as usize;
+    let mut noise = Noise(seed);
+    let mut phase = 0.0;
+
+    let samples = (0..num_samples)
+        .map(|i| {
+            let t = i as f64 / rate;
+            let vibrato = if synth.vibrato {
+                1.0 + (0.004 * (TAU * 5.0 * t).sin())
+            } else {
+                1.0
+            };
+            phase += frequency * vibrato / rate;
+
+            let tone: f64 = synth
+                .harmonics
+                .iter()
+                .enumerate()
+                .map(|(h, amp)| (h as f64 + 1.0, *amp as f64))
+                // Skip harmonics above the Nyquist frequency so they don't alias.
+                .filter(|(h, amp)| *amp != 0.0 && frequency * h < rate * 0.5)
+                .map(|(h, amp)| amp * (TAU * phase * h).sin())
+                .sum();
+
+            let attack = (t / synth.attack).min(1.0);
+            let decay = (-synth.decay * t).exp();
+            // Fade out the last 10ms so the sample doesn't end with a click.
+            let fade = ((length - t) / 0.01).min(1.0);
+            let envelope = (attack * decay * fade) as f32;
+
+            ((tone as f32) + (noise.next() * synth.breath)) * envelope
+        })
+        .collect();
+
+    normalize(samples)
+}
+
+fn synthesize_drum(synth: &DrumSynth, seed: u32) -> SoundBuffer {
+    let rate = SAMPLE_RATE as f64;
+    let num_samples = (synth.length * rate) as usize;
+    let mut noise = Noise(seed);
+    let mut last_noise = 0.0;
+    let mut phase = 0.0;
+
+    let samples = (0..num_samples)
+        .map(|i| {
+            let t = i as f64 / rate;
+            let progress = t / synth.length;
+            phase += (synth.tone_start + ((synth.tone_end - synth.tone_start) * progress)) / rate;
+            let tone = (TAU * phase).sin() as f32 * synth.tone_amp;
+
+            let white = noise.next();
+            let noise_sample = if synth.bright {
+                // A first difference is a cheap high-pass filter.
+                let filtered = (white - last_noise) * 0.5;
+                last_noise = white;
+                filtered
+            } else {
+                white
+            };
+
+            let rattle = if synth.rattle > 0.0 {
+                0.5 + (0.5 * (TAU * synth.rattle * t).sin())
+            } else {
+                1.0
+            };
+            let attack = (t / 0.001).min(1.0);
+            let envelope = (attack * (-synth.decay * t).exp() * rattle) as f32;
+
+            (tone + (noise_sample * synth.noise_amp)) * envelope
+        })
+        .collect();
+
+    normalize(samples)
+}
+
+/// The music extension's instrument and drum samples. Each sample is synthesized the first time it's used.
+pub struct SampleBank {
+    instruments: Vec<Vec<Option<Rc<SoundBuffer>>>>,
+    drums: Vec<Option<Rc<SoundBuffer>>>,
+}
+
+impl Default for SampleBank {
+    fn default() -> Self {
+        SampleBank {
+            instruments: INSTRUMENTS
+                .iter()
+                .map(|info| vec![None; info.sample_notes.len()])
+                .collect(),
+            drums: vec![None; DRUMS.len()],
+        }
+    }
+}
+
+impl SampleBank {
+    /// Get the sample to play a note with, and the playback rate needed to bring it to the right pitch.
+    pub fn instrument_sample(&mut self, instrument: usize, note: f64) -> (Rc<SoundBuffer>, f64) {
+        let info = &INSTRUMENTS[instrument];
+        // Use the sample whose note is closest, preferring lower ones on ties, like scratch-vm.
+        let (sample_idx, sample_note) = info
+            .sample_notes
+            .iter()
+            .enumerate()
+            .fold(
+                None,
+                |closest: Option<(usize, u8)>, (idx, sample_note)| match closest {
+                    Some((_, closest_note))
+                        if (closest_note as f64 - note).abs()
+                            <= (*sample_note as f64 - note).abs() =>
+                    {
+                        closest
+                    }
+                    _ => Some((idx, *sample_note)),
+                },
+            )
+            .unwrap();
+
+        let buffer = self.instruments[instrument][sample_idx]
+            .get_or_insert_with(|| {
+                Rc::new(synthesize_instrument(
+                    &info.synth,
+                    sample_note,
+                    instrument as u32,
+                ))
+            })
+            .clone();
+        let playback_rate = 2f64.powf((note - sample_note as f64) / 12.0);
+
+        (buffer, playback_rate)
+    }
+
+    pub fn drum_sample(&mut self, drum: usize) -> Rc<SoundBuffer> {
+        self.drums[drum]
+            .get_or_insert_with(|| Rc::new(synthesize_drum(&DRUMS[drum].synth, drum as u32 + 1)))
+            .clone()
+    }
+}
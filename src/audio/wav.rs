@@ -0,0 +1,56 @@
+use std::io::{self, Write};
+
+const BITS_PER_SAMPLE: u16 = 16;
+
+fn write_header<W: Write>(
+    writer: &mut W,
+    channels: u16,
+    sample_rate: u32,
+    num_samples: usize,
+) -> io::Result<()> {
+    let block_align = channels * (BITS_PER_SAMPLE / 8);
+    let data_len = (num_samples * 2) as u32;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_len).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&channels.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())
+}
+
+/// Write interleaved stereo samples as a 16-bit PCM WAV file.
+pub fn write_wav<W: Write>(writer: &mut W, samples: &[f32], sample_rate: u32) -> io::Result<()> {
+    write_header(writer, 2, sample_rate, samples.len())?;
+    for sample in samples {
+        // Clip anything the mixer pushed out of range.
+        let sample = (sample.clamp(-1.0, 1.0) * 32767.0).round() as i16;
+        writer.write_all(&sample.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Write 16-bit samples with the given number of interleaved channels as a PCM WAV file.
+pub fn write_pcm16_wav<W: Write>(
+    writer: &mut W,
+    samples: &[i16],
+    channels: u16,
+    sample_rate: u32,
+) -> io::Result<()> {
+    write_header(writer, channels, sample_rate, samples.len())?;
+    for sample in samples {
+        writer.write_all(&sample.to_le_bytes())?;
+    }
+
+    Ok(())
+}
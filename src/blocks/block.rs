@@ -4,6 +4,8 @@ use crate::scalar_value::ScalarValue;
 pub enum BlockFieldType {
     Field,
     Input,
+    /// The mouth of a C-block, like the inside of a "repeat" loop.
+    Substack,
 }
 
 #[derive(Debug)]
@@ -18,7 +20,14 @@ pub enum BlockShape {
 pub enum BlockInput<'spec> {
     Literal(ScalarValue),
     Reporter(Block<'spec>),
-    Substack(usize),
+    /// A reporter dropped onto an input that had a shadow (such as a number field) in it. The shadow is what the input
+    /// goes back to if the reporter is removed; it's never evaluated.
+    Obscured {
+        reporter: Box<Block<'spec>>,
+        shadow: Box<BlockInput<'spec>>,
+    },
+    /// The first block of a substack, or None if it's empty.
+    Substack(Option<usize>),
 }
 
 #[derive(Debug)]
@@ -17,6 +17,16 @@ pub fn make_block_specs() -> BlockSpecMap {
         },
     );
 
+    specs.insert(
+        "text".to_string(),
+        BlockSpec {
+            name: "text",
+            field_names: Box::new(["TEXT".to_string()]),
+            field_types: Box::new([BlockFieldType::Field]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
     specs.insert(
         "motion_movesteps".to_string(),
         BlockSpec {
@@ -32,7 +42,7 @@ pub fn make_block_specs() -> BlockSpecMap {
         BlockSpec {
             name: "control_repeat",
             field_names: Box::new(["TIMES".to_string(), "SUBSTACK".to_string()]),
-            field_types: Box::new([BlockFieldType::Input, BlockFieldType::Input]),
+            field_types: Box::new([BlockFieldType::Input, BlockFieldType::Substack]),
             shape: BlockShape::Command,
         },
     );
@@ -47,5 +57,388 @@ pub fn make_block_specs() -> BlockSpecMap {
         },
     );
 
+    specs.insert(
+        "sensing_distanceto".to_string(),
+        BlockSpec {
+            name: "sensing_distanceto",
+            field_names: Box::new(["DISTANCETOMENU".to_string()]),
+            field_types: Box::new([BlockFieldType::Input]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    specs.insert(
+        "sensing_distancetomenu".to_string(),
+        BlockSpec {
+            name: "sensing_distancetomenu",
+            field_names: Box::new(["DISTANCETOMENU".to_string()]),
+            field_types: Box::new([BlockFieldType::Field]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    specs.insert(
+        "sensing_of".to_string(),
+        BlockSpec {
+            name: "sensing_of",
+            field_names: Box::new(["PROPERTY".to_string(), "OBJECT".to_string()]),
+            field_types: Box::new([BlockFieldType::Field, BlockFieldType::Input]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    specs.insert(
+        "sensing_of_object_menu".to_string(),
+        BlockSpec {
+            name: "sensing_of_object_menu",
+            field_names: Box::new(["OBJECT".to_string()]),
+            field_types: Box::new([BlockFieldType::Field]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    specs.insert(
+        "sensing_current".to_string(),
+        BlockSpec {
+            name: "sensing_current",
+            field_names: Box::new(["CURRENTMENU".to_string()]),
+            field_types: Box::new([BlockFieldType::Field]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    specs.insert(
+        "sensing_dayssince2000".to_string(),
+        BlockSpec {
+            name: "sensing_dayssince2000",
+            field_names: Box::new([]),
+            field_types: Box::new([]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    specs.insert(
+        "sensing_username".to_string(),
+        BlockSpec {
+            name: "sensing_username",
+            field_names: Box::new([]),
+            field_types: Box::new([]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    specs.insert(
+        "event_whengreaterthan".to_string(),
+        BlockSpec {
+            name: "event_whengreaterthan",
+            field_names: Box::new(["WHENGREATERTHANMENU".to_string(), "VALUE".to_string()]),
+            field_types: Box::new([BlockFieldType::Field, BlockFieldType::Input]),
+            shape: BlockShape::Hat,
+        },
+    );
+
+    specs.insert(
+        "sensing_timer".to_string(),
+        BlockSpec {
+            name: "sensing_timer",
+            field_names: Box::new([]),
+            field_types: Box::new([]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    specs.insert(
+        "sensing_resettimer".to_string(),
+        BlockSpec {
+            name: "sensing_resettimer",
+            field_names: Box::new([]),
+            field_types: Box::new([]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "sensing_askandwait".to_string(),
+        BlockSpec {
+            name: "sensing_askandwait",
+            field_names: Box::new(["QUESTION".to_string()]),
+            field_types: Box::new([BlockFieldType::Input]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "sensing_answer".to_string(),
+        BlockSpec {
+            name: "sensing_answer",
+            field_names: Box::new([]),
+            field_types: Box::new([]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    specs.insert(
+        "event_whenthisspriteclicked".to_string(),
+        BlockSpec {
+            name: "event_whenthisspriteclicked",
+            field_names: Box::new([]),
+            field_types: Box::new([]),
+            shape: BlockShape::Hat,
+        },
+    );
+
+    specs.insert(
+        "event_whenstageclicked".to_string(),
+        BlockSpec {
+            name: "event_whenstageclicked",
+            field_names: Box::new([]),
+            field_types: Box::new([]),
+            shape: BlockShape::Hat,
+        },
+    );
+
+    specs.insert(
+        "sensing_setdragmode".to_string(),
+        BlockSpec {
+            name: "sensing_setdragmode",
+            field_names: Box::new(["DRAG_MODE".to_string()]),
+            field_types: Box::new([BlockFieldType::Field]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "sound_play".to_string(),
+        BlockSpec {
+            name: "sound_play",
+            field_names: Box::new(["SOUND_MENU".to_string()]),
+            field_types: Box::new([BlockFieldType::Input]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "sound_playuntildone".to_string(),
+        BlockSpec {
+            name: "sound_playuntildone",
+            field_names: Box::new(["SOUND_MENU".to_string()]),
+            field_types: Box::new([BlockFieldType::Input]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "sound_sounds_menu".to_string(),
+        BlockSpec {
+            name: "sound_sounds_menu",
+            field_names: Box::new(["SOUND_MENU".to_string()]),
+            field_types: Box::new([BlockFieldType::Field]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    specs.insert(
+        "sound_stopallsounds".to_string(),
+        BlockSpec {
+            name: "sound_stopallsounds",
+            field_names: Box::new([]),
+            field_types: Box::new([]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "sound_setvolumeto".to_string(),
+        BlockSpec {
+            name: "sound_setvolumeto",
+            field_names: Box::new(["VOLUME".to_string()]),
+            field_types: Box::new([BlockFieldType::Input]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "sound_changevolumeby".to_string(),
+        BlockSpec {
+            name: "sound_changevolumeby",
+            field_names: Box::new(["VOLUME".to_string()]),
+            field_types: Box::new([BlockFieldType::Input]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "sound_volume".to_string(),
+        BlockSpec {
+            name: "sound_volume",
+            field_names: Box::new([]),
+            field_types: Box::new([]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    specs.insert(
+        "sound_seteffectto".to_string(),
+        BlockSpec {
+            name: "sound_seteffectto",
+            field_names: Box::new(["EFFECT".to_string(), "VALUE".to_string()]),
+            field_types: Box::new([BlockFieldType::Field, BlockFieldType::Input]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "sound_changeeffectby".to_string(),
+        BlockSpec {
+            name: "sound_changeeffectby",
+            field_names: Box::new(["EFFECT".to_string(), "VALUE".to_string()]),
+            field_types: Box::new([BlockFieldType::Field, BlockFieldType::Input]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "sound_cleareffects".to_string(),
+        BlockSpec {
+            name: "sound_cleareffects",
+            field_names: Box::new([]),
+            field_types: Box::new([]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "music_playDrumForBeats".to_string(),
+        BlockSpec {
+            name: "music_playDrumForBeats",
+            field_names: Box::new(["DRUM".to_string(), "BEATS".to_string()]),
+            field_types: Box::new([BlockFieldType::Input, BlockFieldType::Input]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "music_menu_DRUM".to_string(),
+        BlockSpec {
+            name: "music_menu_DRUM",
+            field_names: Box::new(["DRUM".to_string()]),
+            field_types: Box::new([BlockFieldType::Field]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    specs.insert(
+        "music_restForBeats".to_string(),
+        BlockSpec {
+            name: "music_restForBeats",
+            field_names: Box::new(["BEATS".to_string()]),
+            field_types: Box::new([BlockFieldType::Input]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "music_playNoteForBeats".to_string(),
+        BlockSpec {
+            name: "music_playNoteForBeats",
+            field_names: Box::new(["NOTE".to_string(), "BEATS".to_string()]),
+            field_types: Box::new([BlockFieldType::Input, BlockFieldType::Input]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "note".to_string(),
+        BlockSpec {
+            name: "note",
+            field_names: Box::new(["NOTE".to_string()]),
+            field_types: Box::new([BlockFieldType::Field]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    specs.insert(
+        "music_setInstrument".to_string(),
+        BlockSpec {
+            name: "music_setInstrument",
+            field_names: Box::new(["INSTRUMENT".to_string()]),
+            field_types: Box::new([BlockFieldType::Input]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "music_menu_INSTRUMENT".to_string(),
+        BlockSpec {
+            name: "music_menu_INSTRUMENT",
+            field_names: Box::new(["INSTRUMENT".to_string()]),
+            field_types: Box::new([BlockFieldType::Field]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    specs.insert(
+        "music_setTempo".to_string(),
+        BlockSpec {
+            name: "music_setTempo",
+            field_names: Box::new(["TEMPO".to_string()]),
+            field_types: Box::new([BlockFieldType::Input]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "music_changeTempo".to_string(),
+        BlockSpec {
+            name: "music_changeTempo",
+            field_names: Box::new(["TEMPO".to_string()]),
+            field_types: Box::new([BlockFieldType::Input]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "music_getTempo".to_string(),
+        BlockSpec {
+            name: "music_getTempo",
+            field_names: Box::new([]),
+            field_types: Box::new([]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    // Stand-ins for blocks that aren't supported, so that projects using them still load. Deserialization picks one
+    // based on how the unsupported block is used. Commands do nothing, reporters report an empty string, and hats
+    // never trigger.
+    specs.insert(
+        "unsupported_command".to_string(),
+        BlockSpec {
+            name: "unsupported_command",
+            field_names: Box::new([]),
+            field_types: Box::new([]),
+            shape: BlockShape::Command,
+        },
+    );
+
+    specs.insert(
+        "unsupported_reporter".to_string(),
+        BlockSpec {
+            name: "unsupported_reporter",
+            field_names: Box::new([]),
+            field_types: Box::new([]),
+            shape: BlockShape::Reporter,
+        },
+    );
+
+    specs.insert(
+        "unsupported_hat".to_string(),
+        BlockSpec {
+            name: "unsupported_hat",
+            field_names: Box::new([]),
+            field_types: Box::new([]),
+            shape: BlockShape::Hat,
+        },
+    );
+
     specs
 }
@@ -0,0 +1,107 @@
+//! Finding out how much of a project is supported before running it. Blocks with unsupported opcodes still load, as
+//! placeholders that do nothing, so a project using them may run but behave differently than in Scratch. The report
+//! lists the placeholders the loader actually created, including ones for primitives like variable reporters.
+
+use crate::engine::{engine_data::EngineData, project::Project};
+
+use std::{collections::BTreeMap, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedOpcode {
+    pub opcode: String,
+    /// How many blocks in the whole project use this opcode.
+    pub count: usize,
+    /// Names of the targets that use it, in project order.
+    pub targets: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatibilityReport {
+    /// Extensions the project lists that none of the supported blocks belong to.
+    pub unsupported_extensions: Vec<String>,
+    /// Every unsupported opcode in the project, sorted by opcode.
+    pub unsupported: Vec<UnsupportedOpcode>,
+}
+
+/// Whether any of an extension's blocks are implemented. Extension block opcodes all start with the extension's ID.
+pub fn is_extension_supported(extension: &str, eng_data: &EngineData) -> bool {
+    eng_data.block_specs.keys().any(|opcode| {
+        opcode
+            .strip_prefix(extension)
+            .is_some_and(|rest| rest.starts_with('_'))
+    })
+}
+
+impl CompatibilityReport {
+    pub fn new(project: &Project, eng_data: &EngineData) -> Self {
+        let mut unsupported: BTreeMap<&str, UnsupportedOpcode> = BTreeMap::new();
+
+        for target in &project.targets {
+            for opcode in &target.unsupported_opcodes {
+                let entry =
+                    unsupported
+                        .entry(opcode.as_str())
+                        .or_insert_with(|| UnsupportedOpcode {
+                            opcode: opcode.to_string(),
+                            count: 0,
+                            targets: vec![],
+                        });
+                entry.count += 1;
+                if !entry.targets.contains(&target.name) {
+                    entry.targets.push(target.name.clone());
+                }
+            }
+        }
+
+        CompatibilityReport {
+            unsupported_extensions: project
+                .extensions
+                .iter()
+                .filter(|extension| !is_extension_supported(extension, eng_data))
+                .cloned()
+                .collect(),
+            unsupported: unsupported.into_values().collect(),
+        }
+    }
+
+    pub fn is_fully_supported(&self) -> bool {
+        self.unsupported_extensions.is_empty() && self.unsupported.is_empty()
+    }
+}
+
+impl fmt::Display for CompatibilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_fully_supported() {
+            return writeln!(f, "All blocks are supported.");
+        }
+
+        if !self.unsupported_extensions.is_empty() {
+            writeln!(
+                f,
+                "Unsupported extensions: {}",
+                self.unsupported_extensions.join(", ")
+            )?;
+        }
+        if self.unsupported.is_empty() {
+            return Ok(());
+        }
+
+        let total: usize = self.unsupported.iter().map(|opcode| opcode.count).sum();
+        writeln!(
+            f,
+            "{} unsupported opcodes ({} blocks):",
+            self.unsupported.len(),
+            total
+        )?;
+        for opcode in &self.unsupported {
+            writeln!(
+                f,
+                "  {} x{} in {}",
+                opcode.opcode,
+                opcode.count,
+                opcode.targets.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
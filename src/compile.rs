@@ -1,5 +1,5 @@
 use crate::{
-    blocks::block::{Block, BlockInput},
+    blocks::block::{Block, BlockInput, BlockShape},
     engine::instruction::{Instruction, Script},
     engine::trigger::Trigger,
     scalar_value::ScalarValue,
@@ -13,13 +13,22 @@ pub fn compile_block_input(
     match input {
         BlockInput::Literal(v) => instructions.push(Instruction::Push(v.clone())),
         BlockInput::Reporter(block) => compile_block(block, instructions, blocks),
-        BlockInput::Substack(idx) => compile_substack(*idx, instructions, blocks),
+        BlockInput::Obscured { reporter, .. } => compile_block(reporter, instructions, blocks),
+        BlockInput::Substack(Some(idx)) => compile_substack(*idx, instructions, blocks),
+        BlockInput::Substack(None) => {}
     }
 }
 
 pub fn compile_block(block: &Block, instructions: &mut Vec<Instruction>, blocks: &[Block]) {
     match block.spec.name {
-        "math_number" => {
+        "math_number"
+        | "text"
+        | "sensing_distancetomenu"
+        | "sensing_of_object_menu"
+        | "sound_sounds_menu"
+        | "note"
+        | "music_menu_DRUM"
+        | "music_menu_INSTRUMENT" => {
             compile_block_input(&block.field_values[0], instructions, blocks);
         }
         "motion_movesteps" => {
@@ -59,6 +68,99 @@ pub fn compile_block(block: &Block, instructions: &mut Vec<Instruction>, blocks:
             instructions[jump_to_cleanup] = Instruction::JumpIfTrue(label_cleanup_code);
         }
 
+        "sensing_distanceto" => {
+            compile_block_input(&block.field_values[0], instructions, blocks);
+            instructions.push(Instruction::DistanceTo);
+        }
+        "sensing_of" => {
+            compile_block_input(&block.field_values[1 /* OBJECT */], instructions, blocks);
+            compile_block_input(&block.field_values[0 /* PROPERTY */], instructions, blocks);
+            instructions.push(Instruction::AttributeOf);
+        }
+        "sensing_current" => {
+            compile_block_input(&block.field_values[0], instructions, blocks);
+            instructions.push(Instruction::Current);
+        }
+        "sensing_dayssince2000" => instructions.push(Instruction::DaysSince2000),
+        "sensing_username" => instructions.push(Instruction::Username),
+        "sensing_timer" => instructions.push(Instruction::Timer),
+        "sensing_resettimer" => instructions.push(Instruction::ResetTimer),
+        "sensing_askandwait" => {
+            compile_block_input(&block.field_values[0], instructions, blocks);
+            instructions.push(Instruction::Ask);
+            instructions.push(Instruction::WaitForAnswer);
+        }
+        "sensing_answer" => instructions.push(Instruction::Answer),
+        "sensing_setdragmode" => {
+            compile_block_input(&block.field_values[0], instructions, blocks);
+            instructions.push(Instruction::SetDragMode);
+        }
+        "sound_play" => {
+            compile_block_input(&block.field_values[0], instructions, blocks);
+            instructions.push(Instruction::StartSound);
+        }
+        "sound_playuntildone" => {
+            compile_block_input(&block.field_values[0], instructions, blocks);
+            instructions.push(Instruction::PlaySound);
+            instructions.push(Instruction::WaitForSound);
+        }
+        "sound_stopallsounds" => instructions.push(Instruction::StopAllSounds),
+        "sound_setvolumeto" => {
+            compile_block_input(&block.field_values[0], instructions, blocks);
+            instructions.push(Instruction::SetVolumeTo);
+        }
+        "sound_changevolumeby" => {
+            compile_block_input(&block.field_values[0], instructions, blocks);
+            instructions.push(Instruction::ChangeVolumeBy);
+        }
+        "sound_volume" => instructions.push(Instruction::Volume),
+        "sound_seteffectto" => {
+            compile_block_input(&block.field_values[0 /* EFFECT */], instructions, blocks);
+            compile_block_input(&block.field_values[1 /* VALUE */], instructions, blocks);
+            instructions.push(Instruction::SetSoundEffectTo);
+        }
+        "sound_changeeffectby" => {
+            compile_block_input(&block.field_values[0 /* EFFECT */], instructions, blocks);
+            compile_block_input(&block.field_values[1 /* VALUE */], instructions, blocks);
+            instructions.push(Instruction::ChangeSoundEffectBy);
+        }
+        "sound_cleareffects" => instructions.push(Instruction::ClearSoundEffects),
+        "music_playDrumForBeats" => {
+            compile_block_input(&block.field_values[0 /* DRUM */], instructions, blocks);
+            compile_block_input(&block.field_values[1 /* BEATS */], instructions, blocks);
+            instructions.push(Instruction::PlayDrum);
+            instructions.push(Instruction::WaitUntilTime);
+        }
+        "music_playNoteForBeats" => {
+            compile_block_input(&block.field_values[0 /* NOTE */], instructions, blocks);
+            compile_block_input(&block.field_values[1 /* BEATS */], instructions, blocks);
+            instructions.push(Instruction::PlayNote);
+            instructions.push(Instruction::WaitUntilTime);
+        }
+        "music_restForBeats" => {
+            compile_block_input(&block.field_values[0], instructions, blocks);
+            instructions.push(Instruction::Rest);
+            instructions.push(Instruction::WaitUntilTime);
+        }
+        "music_setInstrument" => {
+            compile_block_input(&block.field_values[0], instructions, blocks);
+            instructions.push(Instruction::SetInstrument);
+        }
+        "music_setTempo" => {
+            compile_block_input(&block.field_values[0], instructions, blocks);
+            instructions.push(Instruction::SetTempo);
+        }
+        "music_changeTempo" => {
+            compile_block_input(&block.field_values[0], instructions, blocks);
+            instructions.push(Instruction::ChangeTempo);
+        }
+        "music_getTempo" => instructions.push(Instruction::Tempo),
+
+        "unsupported_command" => {}
+        "unsupported_reporter" => {
+            instructions.push(Instruction::Push(ScalarValue::Text(Box::default())))
+        }
+
         _ => {
             println!("Unknown opcode {}", block.spec.name);
         }
@@ -76,28 +178,48 @@ pub fn compile_substack(substack_id: usize, instructions: &mut Vec<Instruction>,
     }
 }
 
-pub fn compile_hat(block: &Block) -> Trigger {
+/// Compile a script's hat block into its trigger. Edge-activated hats also compile their predicate, which is evaluated
+/// before the rest of the script.
+pub fn compile_hat(
+    block: &Block,
+    instructions: &mut Vec<Instruction>,
+    blocks: &[Block],
+) -> Trigger {
     match block.spec.name {
         "event_whenflagclicked" => Trigger::WhenFlagClicked,
-        _ => panic!("Unknown hat opcode {}", block.spec.name),
+        "event_whenthisspriteclicked" => Trigger::WhenSpriteClicked,
+        "event_whenstageclicked" => Trigger::WhenStageClicked,
+        "event_whengreaterthan" => {
+            compile_block_input(&block.field_values[0], instructions, blocks);
+            compile_block_input(&block.field_values[1], instructions, blocks);
+            instructions.push(Instruction::WhenGreaterThan);
+            instructions.push(Instruction::EdgeActivatedHat);
+            Trigger::WhenGreaterThan
+        }
+        // A hat spec that's missing from this list loads fine, but its script can never be triggered.
+        _ => Trigger::Unsupported,
     }
 }
 
 pub fn compile_blocks(blocks: &[Block]) -> Box<[Script]> {
     let mut scripts = Vec::new();
 
+    // Only scripts that start with a hat ever run. Loose reporters and stacks without a hat are left in the workspace,
+    // where Scratch would only run them if they were clicked.
     blocks
-        .into_iter()
-        .filter(|block| block.parent == None)
+        .iter()
+        .filter(|block| block.parent.is_none() && matches!(block.spec.shape, BlockShape::Hat))
         .for_each(|root_block| {
             let mut instructions: Vec<Instruction> = Vec::new();
 
-            // TODO: this skips the first block but expressions in edge-triggered hats must be compiled too
-            compile_substack(root_block.next.unwrap(), &mut instructions, blocks);
+            let trigger = compile_hat(root_block, &mut instructions, blocks);
+            if let Some(next) = root_block.next {
+                compile_substack(next, &mut instructions, blocks);
+            }
 
             scripts.push(Script {
                 instructions,
-                trigger: compile_hat(root_block),
+                trigger,
             })
         });
 
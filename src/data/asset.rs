@@ -9,6 +9,19 @@ pub enum AssetType {
     MP3,
 }
 
+impl AssetType {
+    /// The `dataFormat` that project.json uses for this type, which is also its file extension.
+    pub fn data_format(&self) -> &'static str {
+        match self {
+            AssetType::PNG => "png",
+            AssetType::SVG => "svg",
+            AssetType::JPEG => "jpg",
+            AssetType::WAV => "wav",
+            AssetType::MP3 => "mp3",
+        }
+    }
+}
+
 pub struct Asset {
     pub data: Box<[u8]>,
     pub asset_type: AssetType,
@@ -0,0 +1,143 @@
+//! Content-addressed storage for a project's assets. Costumes and sounds are often shared between sprites (or repeated
+//! within one), so each distinct file is only kept, rasterized, or decoded once, no matter how many times it's used.
+
+use std::{
+    cell::{LazyCell, RefCell},
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+};
+
+use md5::Digest;
+
+use crate::{
+    audio::decode::SoundBuffer,
+    data::asset::{Asset, AssetType},
+    engine::sound::LazySoundBuffer,
+    renderer::skin::Skin,
+};
+
+/// What to do when an asset's contents don't match the MD5 in its `assetId`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AssetVerification {
+    /// Fail to load the project.
+    Strict,
+    /// Print a warning and use the asset anyway.
+    #[default]
+    Warn,
+}
+
+/// When costumes and sounds are decoded (and, for costumes, uploaded to the GPU).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AssetLoading {
+    /// While the project is loading, so everything is ready before it starts.
+    #[default]
+    Eager,
+    /// When each one is first used, or in the background once the project is running. Large projects start much
+    /// sooner, but costumes are blank until they load.
+    Lazy,
+}
+
+/// A skin depends on the costume's rotation center and resolution as well as its image.
+#[derive(PartialEq, Eq, Hash)]
+struct SkinKey {
+    digest: Digest,
+    rotation_center: (u64, u64),
+    bitmap_resolution: u64,
+}
+
+pub struct AssetStore {
+    verification: AssetVerification,
+    loading: AssetLoading,
+    assets: HashMap<Digest, Rc<Asset>>,
+    skins: HashMap<SkinKey, Rc<RefCell<dyn Skin>>>,
+    sound_buffers: HashMap<Digest, Rc<LazySoundBuffer>>,
+}
+
+impl fmt::Debug for AssetStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AssetStore")
+            .field("verification", &self.verification)
+            .field("loading", &self.loading)
+            .field("assets", &self.assets.len())
+            .finish()
+    }
+}
+
+impl AssetStore {
+    pub fn new(verification: AssetVerification, loading: AssetLoading) -> Self {
+        AssetStore {
+            verification,
+            loading,
+            assets: HashMap::new(),
+            skins: HashMap::new(),
+            sound_buffers: HashMap::new(),
+        }
+    }
+
+    pub fn loading(&self) -> AssetLoading {
+        self.loading
+    }
+
+    /// Verify an asset file against the MD5 it's listed under, and store it. Returns the stored copy, which is shared
+    /// with any earlier asset that had the same contents.
+    pub fn insert(
+        &mut self,
+        file_name: &str,
+        data: Vec<u8>,
+        asset_id: Digest,
+        asset_type: AssetType,
+    ) -> Result<Rc<Asset>, &'static str> {
+        let digest = md5::compute(&data);
+        if digest != asset_id {
+            match self.verification {
+                AssetVerification::Strict => return Err("asset does not match its MD5"),
+                AssetVerification::Warn => println!(
+                    "Asset \"{}\" does not match its MD5 (actual MD5 is {:x})",
+                    file_name, digest
+                ),
+            }
+        }
+
+        // Key by what's actually in the file, so the store stays content-addressed even if the project's IDs are wrong.
+        Ok(Rc::clone(self.assets.entry(digest).or_insert_with(|| {
+            Rc::new(Asset {
+                data: data.into_boxed_slice(),
+                asset_type,
+                md5_digest: digest,
+            })
+        })))
+    }
+
+    /// Get the skin for a costume, creating it the first time it's needed.
+    pub fn skin(
+        &mut self,
+        asset: &Asset,
+        rotation_center: (f64, f64),
+        bitmap_resolution: f64,
+        create: impl FnOnce() -> Rc<RefCell<dyn Skin>>,
+    ) -> Rc<RefCell<dyn Skin>> {
+        let key = SkinKey {
+            digest: asset.md5_digest,
+            rotation_center: (rotation_center.0.to_bits(), rotation_center.1.to_bits()),
+            bitmap_resolution: bitmap_resolution.to_bits(),
+        };
+        Rc::clone(self.skins.entry(key).or_insert_with(create))
+    }
+
+    /// Get the decoded audio for a sound. It's decoded now if loading eagerly, or else when it's first played.
+    pub fn sound_buffer(
+        &mut self,
+        asset: &Asset,
+        decode: impl FnOnce() -> SoundBuffer + 'static,
+    ) -> Rc<LazySoundBuffer> {
+        let buffer = self
+            .sound_buffers
+            .entry(asset.md5_digest)
+            .or_insert_with(|| Rc::new(LazyCell::new(Box::new(move || Rc::new(decode())))));
+        if self.loading == AssetLoading::Eager {
+            LazyCell::force(buffer);
+        }
+        Rc::clone(buffer)
+    }
+}
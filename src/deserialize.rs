@@ -1,28 +1,39 @@
 use crate::{
     blocks::{
-        block::{Block, BlockInput},
+        block::{Block, BlockFieldType, BlockInput, BlockSpec},
         block_specs::BlockSpecMap,
     },
+    compatibility::is_extension_supported,
     compile::compile_blocks,
-    data::asset,
+    data::{
+        asset,
+        asset_store::{AssetLoading, AssetStore, AssetVerification},
+    },
     engine::{
         costume::{Costume, CostumeAsset},
         engine_data::EngineData,
-        project, target,
+        monitor::{Monitor, MonitorMode},
+        project::{self, ProjectMeta},
+        sound::{Sound, SoundAsset},
+        target,
+        variable::{Broadcast, List, Variable},
     },
+    load_error::{LoadContext, LoadError},
     renderer::renderer::Renderer,
+    sb2,
     scalar_value::ScalarValue,
+    source::{AssetSource, ProjectSource},
 };
 
 use std::convert::TryFrom;
-use std::{collections::HashMap, convert::TryInto, fs::File, io::Read};
+use std::{cell::RefCell, collections::HashMap, convert::TryInto, rc::Rc};
 
 use md5::Digest;
 use serde_json::{Map, Value};
 
 use num_enum::TryFromPrimitive;
-use zip::ZipArchive;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(TryFromPrimitive)]
 #[repr(u8)]
 enum InputDescriptorShadowStatus {
@@ -46,152 +57,359 @@ enum InputDescriptorPrimitive {
     DataListcontents,
 }
 
-fn deserialize_input_descriptor<'eng>(
-    serialized_input_descriptor: &Vec<Value>,
+/// How deeply reporters can be nested inside each other. Only malformed projects, where a block ends up inside its own
+/// input, should come anywhere near this.
+const MAX_REPORTER_DEPTH: usize = 256;
+
+/// A target's blocks as they appear in project.json, for looking up the blocks that inputs refer to.
+struct SerializedBlocks<'a, 'eng> {
+    blocks: &'a Map<String, Value>,
     block_specs: &'eng BlockSpecMap,
-    ids_to_indices: &HashMap<&String, usize>,
-    parent: usize,
-) -> Result<BlockInput<'eng>, &'static str> {
-    /*let shadow_status = InputDescriptorShadowStatus::try_from(
-        u8::try_from(
-            serialized_input_descriptor[0].as_u64().ok_or_else(|| "Malformed input descriptor")?
-        ).map_err(|_| "Malformed input descriptor")?
-    ).map_err(|_| "Malformed input descriptor")?;*/
-
-    match &serialized_input_descriptor[1] {
-        Value::Array(arr) => {
-            let input_primitive = InputDescriptorPrimitive::try_from(
-                u8::try_from(
-                    arr[0]
-                        .as_u64()
-                        .ok_or_else(|| "Malformed input descriptor")?,
-                )
-                .map_err(|_| "Malformed input descriptor")?,
+    /// Where each block ends up in the list of deserialized blocks.
+    ids_to_indices: HashMap<&'a str, usize>,
+    /// The opcode of every block that was loaded as a placeholder, once per block.
+    unsupported_opcodes: RefCell<Vec<String>>,
+}
+
+impl SerializedBlocks<'_, '_> {
+    fn index_of(&self, block_id: &str) -> Result<usize, &'static str> {
+        self.ids_to_indices
+            .get(block_id)
+            .copied()
+            .ok_or("Block references invalid block ID")
+    }
+
+    /// Note that a placeholder was loaded for an unsupported opcode. Reporters are deserialized again inside every
+    /// block they're plugged into, so only the top-level pass over the blocks (at depth 0), which sees each block and
+    /// primitive exactly once, counts.
+    fn record_placeholder(&self, opcode: &str, depth: usize) {
+        if depth == 0 {
+            self.unsupported_opcodes
+                .borrow_mut()
+                .push(opcode.to_string());
+        }
+    }
+}
+
+/// Expand a compact `[type, value, ...]` primitive into the block it stands for. Primitives are usually in an input,
+/// but variable and list reporters left loose in the workspace are stored as `[type, name, id, x, y]` too.
+fn deserialize_primitive<'eng>(
+    serialized_primitive: &[Value],
+    blocks: &SerializedBlocks<'_, 'eng>,
+    parent: Option<usize>,
+    depth: usize,
+) -> Result<Block<'eng>, &'static str> {
+    let input_primitive = serialized_primitive
+        .first()
+        .and_then(Value::as_u64)
+        .and_then(|code| u8::try_from(code).ok())
+        .and_then(|code| InputDescriptorPrimitive::try_from(code).ok())
+        .ok_or("Malformed input descriptor")?;
+    let opcode = match input_primitive {
+        InputDescriptorPrimitive::MathNumber
+        | InputDescriptorPrimitive::MathPositiveNumber
+        | InputDescriptorPrimitive::MathWholeNumber
+        | InputDescriptorPrimitive::MathInteger
+        | InputDescriptorPrimitive::MathAngle => "math_number",
+        InputDescriptorPrimitive::ColourPicker => "colour_picker",
+        InputDescriptorPrimitive::Text | InputDescriptorPrimitive::EventBroadcastMenu => "text",
+        InputDescriptorPrimitive::DataVariable => "data_variable",
+        InputDescriptorPrimitive::DataListcontents => "data_listcontents",
+    };
+    let value = serialized_primitive
+        .get(1)
+        .ok_or("Malformed input descriptor")?;
+    let value = ScalarValue::try_from(value)?;
+
+    let (spec, field_values): (_, Box<[BlockInput]>) = match blocks.block_specs.get(opcode) {
+        Some(spec) => (spec, Box::new([BlockInput::Literal(value)])),
+        // Like any other unsupported block, this loads as a placeholder.
+        None => {
+            blocks.record_placeholder(opcode, depth);
+            (
+                blocks
+                    .block_specs
+                    .get("unsupported_reporter")
+                    .ok_or("Missing placeholder block spec")?,
+                Box::default(),
             )
-            .map_err(|_| "Malformed input descriptor")?;
-
-            Ok(BlockInput::Reporter(match input_primitive {
-                InputDescriptorPrimitive::MathNumber
-                | InputDescriptorPrimitive::MathPositiveNumber
-                | InputDescriptorPrimitive::MathWholeNumber
-                | InputDescriptorPrimitive::MathInteger
-                | InputDescriptorPrimitive::MathAngle => Block {
-                    spec: block_specs
-                        .get("math_number")
-                        .ok_or_else(|| "Missing math_number block spec")?,
-                    field_values: Box::new([BlockInput::Literal(ScalarValue::try_from(&arr[1])?)]),
-                    next: None,
-                    parent: Some(parent),
-                },
-                InputDescriptorPrimitive::ColourPicker => Block {
-                    spec: block_specs
-                        .get("colour_picker")
-                        .ok_or_else(|| "Missing colour_picker block spec")?,
-                    field_values: Box::new([BlockInput::Literal(ScalarValue::try_from(&arr[1])?)]),
-                    next: None,
-                    parent: Some(parent),
-                },
-                InputDescriptorPrimitive::Text | InputDescriptorPrimitive::EventBroadcastMenu => {
-                    Block {
-                        spec: block_specs
-                            .get("text")
-                            .ok_or_else(|| "Missing text block spec")?,
-                        field_values: Box::new([BlockInput::Literal(ScalarValue::try_from(
-                            &arr[1],
-                        )?)]),
-                        next: None,
-                        parent: Some(parent),
-                    }
-                }
-                InputDescriptorPrimitive::DataVariable => Block {
-                    spec: block_specs
-                        .get("data_variable")
-                        .ok_or_else(|| "Missing data_variable block spec")?,
-                    field_values: Box::new([BlockInput::Literal(ScalarValue::try_from(&arr[1])?)]),
-                    next: None,
-                    parent: Some(parent),
-                },
-                InputDescriptorPrimitive::DataListcontents => Block {
-                    spec: block_specs
-                        .get("data_listcontents")
-                        .ok_or_else(|| "Missing data_listcontents block spec")?,
-                    field_values: Box::new([BlockInput::Literal(ScalarValue::try_from(&arr[1])?)]),
-                    next: None,
-                    parent: Some(parent),
+        }
+    };
+
+    Ok(Block {
+        spec,
+        field_values,
+        next: None,
+        parent,
+    })
+}
+
+/// If a block is a shadow menu (e.g. `motion_goto_menu` or `looks_costume`), the option picked in it. Menus only hold
+/// that one value, so they're folded into it rather than being run as blocks.
+fn shadow_menu_value(serialized_block: &Map<String, Value>) -> Option<&Value> {
+    if serialized_block.get("shadow").and_then(Value::as_bool) != Some(true) {
+        return None;
+    }
+    let has_inputs = serialized_block
+        .get("inputs")
+        .and_then(Value::as_object)
+        .is_some_and(|inputs| !inputs.is_empty());
+    let fields = serialized_block.get("fields")?.as_object()?;
+    if has_inputs || fields.len() != 1 {
+        return None;
+    }
+    fields.values().next()?.as_array()?.first()
+}
+
+/// Deserialize a block that's plugged into another block's input: a shadow menu's value, or a reporter along with
+/// everything in its own inputs.
+fn deserialize_input_block<'eng>(
+    block_id: &str,
+    blocks: &SerializedBlocks<'_, 'eng>,
+    depth: usize,
+) -> Result<BlockInput<'eng>, LoadError> {
+    let serialized_block = blocks
+        .blocks
+        .get(block_id)
+        .and_then(Value::as_object)
+        .ok_or("Referenced nonexistent block")?;
+    let opcode = serialized_block.get("opcode").and_then(Value::as_str);
+
+    if let Some(value) = shadow_menu_value(serialized_block) {
+        return Ok(BlockInput::Literal(
+            ScalarValue::try_from(value).in_block(block_id, opcode)?,
+        ));
+    }
+    if depth >= MAX_REPORTER_DEPTH {
+        return Err("Reporters are nested too deeply".into());
+    }
+    Ok(BlockInput::Reporter(
+        deserialize_block(serialized_block, blocks, block_id, depth + 1)
+            .in_block(block_id, opcode)?,
+    ))
+}
+
+/// Deserialize one of the values in an input descriptor: a primitive, or the ID of the block in the input.
+fn deserialize_input_value<'eng>(
+    value: &Value,
+    field_type: &BlockFieldType,
+    blocks: &SerializedBlocks<'_, 'eng>,
+    parent: usize,
+    depth: usize,
+) -> Result<BlockInput<'eng>, LoadError> {
+    Ok(match (field_type, value) {
+        (BlockFieldType::Substack, Value::String(block_id)) => {
+            BlockInput::Substack(Some(blocks.index_of(block_id)?))
+        }
+        (BlockFieldType::Substack, Value::Null) => BlockInput::Substack(None),
+        (BlockFieldType::Substack, _) => Err("Malformed substack")?,
+        (_, Value::Array(primitive)) => BlockInput::Reporter(deserialize_primitive(
+            primitive,
+            blocks,
+            Some(parent),
+            depth,
+        )?),
+        (_, Value::String(block_id)) => deserialize_input_block(block_id, blocks, depth)?,
+        // An empty slot, e.g. a boolean input with nothing in it.
+        (_, Value::Null) => BlockInput::Literal(ScalarValue::Text(Box::default())),
+        (_, _) => Err("Malformed input descriptor")?,
+    })
+}
+
+/// Deserialize a `[shadow status, value, obscured shadow]` input descriptor.
+fn deserialize_input_descriptor<'eng>(
+    serialized_input_descriptor: &[Value],
+    field_type: &BlockFieldType,
+    blocks: &SerializedBlocks<'_, 'eng>,
+    parent: usize,
+    depth: usize,
+) -> Result<BlockInput<'eng>, LoadError> {
+    let shadow_status = serialized_input_descriptor
+        .first()
+        .and_then(Value::as_u64)
+        .and_then(|status| u8::try_from(status).ok())
+        .and_then(|status| InputDescriptorShadowStatus::try_from(status).ok())
+        .ok_or("Malformed input descriptor")?;
+    let value = serialized_input_descriptor
+        .get(1)
+        .ok_or("Malformed input descriptor")?;
+    let input = deserialize_input_value(value, field_type, blocks, parent, depth)?;
+
+    match shadow_status {
+        InputDescriptorShadowStatus::UnobscuredShadow | InputDescriptorShadowStatus::NoShadow => {
+            Ok(input)
+        }
+        InputDescriptorShadowStatus::ObscuredShadow => {
+            let shadow = match serialized_input_descriptor.get(2) {
+                Some(Value::Null) | None => return Ok(input),
+                Some(shadow) => deserialize_input_value(shadow, field_type, blocks, parent, depth)?,
+            };
+            Ok(match input {
+                BlockInput::Reporter(reporter) => BlockInput::Obscured {
+                    reporter: Box::new(reporter),
+                    shadow: Box::new(shadow),
                 },
-            }))
+                input => input,
+            })
         }
-        Value::String(substack_id) => Ok(BlockInput::Substack(
-            *ids_to_indices
-                .get(&substack_id)
-                .ok_or_else(|| "Referenced nonexistent substack")?,
-        )),
-        _ => Err("Malformed input descriptor")?,
     }
 }
 
-fn deserialize_block<'eng>(
+fn deserialize_block_input<'eng>(
+    name: &str,
+    field_type: &BlockFieldType,
+    inputs: &Map<String, Value>,
+    fields: &Map<String, Value>,
+    blocks: &SerializedBlocks<'_, 'eng>,
+    block_idx: usize,
+    depth: usize,
+) -> Result<BlockInput<'eng>, LoadError> {
+    let entry = match inputs.get(name).or_else(|| fields.get(name)) {
+        Some(entry) => entry,
+        // Scratch leaves empty C-block mouths out entirely.
+        None if matches!(field_type, BlockFieldType::Substack) => {
+            return Ok(BlockInput::Substack(None))
+        }
+        None => Err("Could not find block input")?,
+    };
+
+    Ok(match (field_type, entry) {
+        // Fields are serialized as [value, id] pairs (the ID is only present for variables, lists, and broadcasts).
+        (BlockFieldType::Field, Value::Array(arr)) => BlockInput::Literal(ScalarValue::try_from(
+            arr.first().ok_or("Malformed block field")?,
+        )?),
+        (_, Value::Bool(_) | Value::Number(_) | Value::String(_)) => {
+            BlockInput::Literal(ScalarValue::try_from(entry)?)
+        }
+        (_, Value::Array(arr)) => {
+            deserialize_input_descriptor(arr, field_type, blocks, block_idx, depth)?
+        }
+        (_, _) => Err("Malformed block input")?,
+    })
+}
+
+/// Pick the placeholder spec for a block with an unsupported opcode, based on how it's used: reporters are plugged into
+/// another block's input, and hats start top-level scripts. C-blocks also hold the first block inside them in an input,
+/// so blocks in those inputs are commands.
+fn placeholder_spec<'eng>(
+    opcode: &str,
     serialized_block: &Map<String, Value>,
+    serialized_blocks: &Map<String, Value>,
     block_specs: &'eng BlockSpecMap,
-    ids_to_indices: &HashMap<&String, usize>,
-    block_id: &String,
-) -> Result<Block<'eng>, &'static str> {
+    block_id: &str,
+) -> Option<&'eng BlockSpec> {
+    let parent = serialized_block
+        .get("parent")
+        .and_then(Value::as_str)
+        .and_then(|parent_id| serialized_blocks.get(parent_id));
+    let placeholder = match parent {
+        Some(parent) => {
+            let input_name = parent
+                .get("inputs")
+                .and_then(Value::as_object)
+                .and_then(|inputs| {
+                    inputs.iter().find(|(_, input)| {
+                        input.as_array().is_some_and(|input| {
+                            input
+                                .iter()
+                                .skip(1)
+                                .any(|entry| entry.as_str() == Some(block_id))
+                        })
+                    })
+                })
+                .map(|(name, _)| name.as_str());
+            let parent_spec = parent
+                .get("opcode")
+                .and_then(Value::as_str)
+                .and_then(|opcode| block_specs.get(opcode));
+            let is_substack = |name: &str| match parent_spec {
+                Some(spec) => spec.field_names.iter().zip(spec.field_types.iter()).any(
+                    |(field_name, field_type)| {
+                        field_name == name && matches!(field_type, BlockFieldType::Substack)
+                    },
+                ),
+                // The parent is unsupported too, so go by Scratch's names for C-block inputs.
+                None => name.starts_with("SUBSTACK"),
+            };
+            let has_next = serialized_block
+                .get("next")
+                .is_some_and(|next| !next.is_null());
+            match input_name {
+                Some(name) if !is_substack(name) && !has_next => "unsupported_reporter",
+                _ => "unsupported_command",
+            }
+        }
+        None => {
+            let name = opcode.split_once('_').map_or(opcode, |(_, name)| name);
+            if name.starts_with("when")
+                || opcode == "control_start_as_clone"
+                || opcode == "procedures_definition"
+            {
+                "unsupported_hat"
+            } else {
+                "unsupported_command"
+            }
+        }
+    };
+    block_specs.get(placeholder)
+}
+
+fn deserialize_block<'eng>(
+    serialized_block: &Map<String, Value>,
+    blocks: &SerializedBlocks<'_, 'eng>,
+    block_id: &str,
+    depth: usize,
+) -> Result<Block<'eng>, LoadError> {
     if let serde_json::Value::String(opcode) = &serialized_block["opcode"] {
-        match block_specs.get(opcode) {
+        let spec = blocks.block_specs.get(opcode).or_else(|| {
+            // Shadow menus are folded into the inputs they're in, so only the blocks using them need to be supported.
+            if shadow_menu_value(serialized_block).is_none() {
+                blocks.record_placeholder(opcode, depth);
+            }
+            placeholder_spec(
+                opcode,
+                serialized_block,
+                blocks.blocks,
+                blocks.block_specs,
+                block_id,
+            )
+        });
+        match spec {
             Some(spec) => {
                 let inputs = serialized_block["inputs"]
                     .as_object()
-                    .ok_or_else(|| "block has no inputs")?;
+                    .ok_or("block has no inputs")?;
                 let fields = serialized_block["fields"]
                     .as_object()
-                    .ok_or_else(|| "block has no fields")?;
+                    .ok_or("block has no fields")?;
                 let next = &serialized_block["next"];
                 let parent = &serialized_block["parent"];
+                let block_idx = blocks.index_of(block_id)?;
 
                 let mut field_values: Vec<BlockInput> = vec![];
 
                 spec.field_names
-                    .into_iter()
-                    .try_for_each(|name| -> Result<(), &'static str> {
-                        let entry = inputs
-                            .get(name)
-                            .or_else(|| fields.get(name))
-                            .ok_or_else(|| "Could not find block input")?;
-
-                        field_values.push(match entry {
-                            Value::Bool(_) | Value::Number(_) | Value::String(_) => {
-                                BlockInput::Literal(ScalarValue::try_from(entry)?)
-                            }
-                            Value::Array(arr) => deserialize_input_descriptor(
-                                arr,
-                                block_specs,
-                                ids_to_indices,
-                                *ids_to_indices
-                                    .get(block_id)
-                                    .ok_or_else(|| "Nonexistent block ID")?,
-                            )?,
-                            _ => Err("Malformed block input")?,
-                        });
+                    .iter()
+                    .zip(spec.field_types.iter())
+                    .try_for_each(|(name, field_type)| -> Result<(), LoadError> {
+                        field_values.push(
+                            deserialize_block_input(
+                                name, field_type, inputs, fields, blocks, block_idx, depth,
+                            )
+                            .in_input(name)?,
+                        );
                         Ok(())
                     })?;
 
                 let b = Block {
-                    spec: &spec,
+                    spec,
                     field_values: field_values.into_boxed_slice(),
                     next: match next {
-                        Value::String(block_id) => Some(
-                            *ids_to_indices
-                                .get(&block_id)
-                                .ok_or_else(|| "Block references invalid block ID")?,
-                        ),
+                        Value::String(block_id) => Some(blocks.index_of(block_id)?),
                         _ => None,
                     },
                     parent: match parent {
-                        Value::String(block_id) => Some(
-                            *ids_to_indices
-                                .get(&block_id)
-                                .ok_or_else(|| "Block references invalid block ID")?,
-                        ),
+                        Value::String(block_id) => Some(blocks.index_of(block_id)?),
                         _ => None,
                     },
                 };
@@ -200,69 +418,77 @@ fn deserialize_block<'eng>(
 
                 Ok(b)
             }
-            None => Err("Unknown opcode"),
+            None => Err("Missing placeholder block spec")?,
         }
     } else {
-        Err("Opcode is not a string")
+        Err("Opcode is not a string")?
     }
 }
 
+/// Deserialize a target's blocks, along with the opcode of every block that had to be loaded as a placeholder.
 fn deserialize_blocks<'eng>(
     serialized_blocks: &Map<String, Value>,
     eng_data: &'eng EngineData,
-) -> Result<Vec<Block<'eng>>, &'static str> {
-    let block_specs = &eng_data.block_specs;
-
-    let mut ids_to_indices: HashMap<&String, usize> = HashMap::new();
+) -> Result<(Vec<Block<'eng>>, Vec<String>), LoadError> {
+    let blocks = SerializedBlocks {
+        blocks: serialized_blocks,
+        block_specs: &eng_data.block_specs,
+        ids_to_indices: serialized_blocks
+            .keys()
+            .enumerate()
+            .map(|(idx, id)| (id.as_str(), idx))
+            .collect(),
+        unsupported_opcodes: RefCell::default(),
+    };
 
-    let mut idx: usize = 0;
-    serialized_blocks.into_iter().for_each(|kv| {
-        ids_to_indices.insert(kv.0, idx);
-        idx += 1;
-    });
-
-    let num_blocks = idx;
-
-    let mut blocks: Vec<Block> = Vec::with_capacity(num_blocks);
+    let mut d_blocks: Vec<Block> = Vec::with_capacity(serialized_blocks.len());
 
     serialized_blocks
         .into_iter()
-        .try_for_each(|kv| -> Result<(), &'static str> {
-            let serialized_block = kv.1.as_object().ok_or_else(|| "block is not an object")?;
-
-            blocks.push(deserialize_block(
-                serialized_block,
-                block_specs,
-                &ids_to_indices,
-                kv.0,
-            )?);
+        .try_for_each(|kv| -> Result<(), LoadError> {
+            let d_block = match kv.1 {
+                Value::Object(serialized_block) => {
+                    let opcode = serialized_block.get("opcode").and_then(Value::as_str);
+                    deserialize_block(serialized_block, &blocks, kv.0, 0).in_block(kv.0, opcode)?
+                }
+                // A loose variable or list reporter.
+                Value::Array(primitive) => {
+                    deserialize_primitive(primitive, &blocks, None, 0).in_block(kv.0, None)?
+                }
+                _ => Err("block is not an object").in_block(kv.0, None)?,
+            };
+            d_blocks.push(d_block);
             Ok(())
         })?;
 
-    Ok(blocks)
+    Ok((d_blocks, blocks.unsupported_opcodes.into_inner()))
 }
 
 fn deserialize_asset(
     serialized_asset: &Map<String, Value>,
-    archive: &mut ZipArchive<File>,
-) -> Result<asset::Asset, &'static str> {
+    assets: &mut dyn AssetSource,
+    store: &mut AssetStore,
+) -> Result<Rc<asset::Asset>, LoadError> {
     let md5ext = serialized_asset["md5ext"]
         .as_str()
-        .ok_or_else(|| "asset has no md5ext")?;
+        .ok_or("asset has no md5ext")?;
+    deserialize_asset_data(serialized_asset, md5ext, assets, store).in_asset(md5ext)
+}
+
+fn deserialize_asset_data(
+    serialized_asset: &Map<String, Value>,
+    md5ext: &str,
+    assets: &mut dyn AssetSource,
+    store: &mut AssetStore,
+) -> Result<Rc<asset::Asset>, &'static str> {
     let md5_str = serialized_asset["assetId"]
         .as_str()
-        .ok_or_else(|| "asset has no assetId")?;
+        .ok_or("asset has no assetId")?;
     let asset_type_str = serialized_asset["dataFormat"]
         .as_str()
-        .ok_or_else(|| "asset has no dataFormat")?;
+        .ok_or("asset has no dataFormat")?;
 
-    let mut asset_file = archive
-        .by_name(md5ext)
-        .map_err(|_| "asset not found in zip")?;
-    let mut asset_data: Vec<u8> = Vec::with_capacity(asset_file.size() as usize);
-    asset_file
-        .read_to_end(&mut asset_data)
-        .map_err(|_| "could not read asset file")?;
+    let asset_data = assets.read_asset(md5ext)?;
 
     let md5_bytes = hex::decode(md5_str).map_err(|_| "could not decode assetId")?;
 
@@ -275,107 +501,479 @@ fn deserialize_asset(
         _ => Err("unknown asset type"),
     }?;
 
-    Ok(asset::Asset {
-        data: asset_data.into_boxed_slice(),
-        md5_digest: Digest(
-            TryInto::<[u8; 16]>::try_into(md5_bytes).map_err(|_| "could not decode assetId")?,
-        ),
-        asset_type,
-    })
+    let asset_id =
+        Digest(TryInto::<[u8; 16]>::try_into(md5_bytes).map_err(|_| "could not decode assetId")?);
+    store.insert(md5ext, asset_data, asset_id, asset_type)
 }
 
 fn deserialize_costume(
     serialized_costume: &Map<String, Value>,
-    archive: &mut ZipArchive<File>,
-) -> Result<CostumeAsset, &'static str> {
-    let d_asset = deserialize_asset(serialized_costume, archive)?;
+    assets: &mut dyn AssetSource,
+    store: &mut AssetStore,
+) -> Result<CostumeAsset, LoadError> {
+    let d_asset = deserialize_asset(serialized_costume, assets, store)?;
     let rotation_center_x = serialized_costume["rotationCenterX"]
         .as_f64()
-        .ok_or_else(|| "costume has no rotationCenterX")?;
+        .ok_or("costume has no rotationCenterX")?;
     let rotation_center_y = serialized_costume["rotationCenterY"]
         .as_f64()
-        .ok_or_else(|| "costume has no rotationCenterY")?;
+        .ok_or("costume has no rotationCenterY")?;
     let name = serialized_costume["name"]
         .as_str()
-        .ok_or_else(|| "costume has no name")?;
+        .ok_or("costume has no name")?;
+    let bitmap_resolution = serialized_costume
+        .get("bitmapResolution")
+        .and_then(Value::as_f64);
 
     Ok(CostumeAsset {
         asset: d_asset,
         rotation_center: (rotation_center_x, rotation_center_y),
+        bitmap_resolution,
+        name: name.to_string(),
+    })
+}
+
+fn deserialize_sound(
+    serialized_sound: &Map<String, Value>,
+    assets: &mut dyn AssetSource,
+    store: &mut AssetStore,
+) -> Result<SoundAsset, LoadError> {
+    let d_asset = deserialize_asset(serialized_sound, assets, store)?;
+    // Sounds may not be decoded until the middle of a run, so anything that isn't audio is rejected now.
+    if !matches!(
+        d_asset.asset_type,
+        asset::AssetType::WAV | asset::AssetType::MP3
+    ) {
+        let md5ext = serialized_sound["md5ext"].as_str().unwrap_or_default();
+        return Err("sound is not a WAV or MP3 file").in_asset(md5ext);
+    }
+    let name = serialized_sound["name"]
+        .as_str()
+        .ok_or("sound has no name")?;
+    // The format, rate, and sample count are only hints; the decoded audio is the source of truth.
+    let format = serialized_sound
+        .get("format")
+        .and_then(Value::as_str)
+        .map(String::from);
+    let rate = serialized_sound.get("rate").and_then(Value::as_u64);
+    let sample_count = serialized_sound.get("sampleCount").and_then(Value::as_u64);
+
+    Ok(SoundAsset {
+        asset: d_asset,
         name: name.to_string(),
+        format,
+        rate: rate.map(|rate| rate as u32),
+        sample_count: sample_count.map(|sample_count| sample_count as u32),
     })
 }
 
-fn deserialize_target<'eng>(
+fn deserialize_variables(
+    serialized_variables: &Map<String, Value>,
+) -> Result<Box<[Variable]>, &'static str> {
+    serialized_variables
+        .iter()
+        .map(|(id, serialized_variable)| {
+            let serialized_variable = serialized_variable
+                .as_array()
+                .ok_or("variable is not an array")?;
+            let name = serialized_variable
+                .first()
+                .and_then(|name| name.as_str())
+                .ok_or("variable has no name")?;
+            let value =
+                ScalarValue::try_from(serialized_variable.get(1).ok_or("variable has no value")?)?;
+
+            Ok(Variable {
+                id: id.clone(),
+                name: name.to_string(),
+                value,
+            })
+        })
+        .collect()
+}
+
+fn deserialize_lists(serialized_lists: &Map<String, Value>) -> Result<Box<[List]>, &'static str> {
+    serialized_lists
+        .iter()
+        .map(|(id, serialized_list)| {
+            let serialized_list = serialized_list.as_array().ok_or("list is not an array")?;
+            let name = serialized_list
+                .first()
+                .and_then(|name| name.as_str())
+                .ok_or("list has no name")?;
+            let contents = serialized_list
+                .get(1)
+                .and_then(|contents| contents.as_array())
+                .ok_or("list has no contents")?
+                .iter()
+                .map(ScalarValue::try_from)
+                .collect::<Result<Vec<ScalarValue>, &'static str>>()?;
+
+            Ok(List {
+                id: id.clone(),
+                name: name.to_string(),
+                contents,
+            })
+        })
+        .collect()
+}
+
+fn deserialize_broadcasts(
+    serialized_broadcasts: &Map<String, Value>,
+) -> Result<Box<[Broadcast]>, &'static str> {
+    serialized_broadcasts
+        .iter()
+        .map(|(id, name)| {
+            Ok(Broadcast {
+                id: id.clone(),
+                name: name.as_str().ok_or("broadcast has no name")?.to_string(),
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn deserialize_target(
     serialized_target: &Map<String, Value>,
-    archive: &mut ZipArchive<File>,
-    eng_data: &'eng EngineData,
-    renderer: &mut Renderer,
-) -> Result<target::Target, &'static str> {
+    assets: &mut dyn AssetSource,
+    store: &mut AssetStore,
+    eng_data: &EngineData,
+    renderer: &mut dyn Renderer,
+) -> Result<target::Target, LoadError> {
+    let name = serialized_target["name"]
+        .as_str()
+        .ok_or("target has no name")?;
+    deserialize_target_contents(serialized_target, assets, store, eng_data, renderer)
+        .in_target(name)
+}
+
+fn deserialize_target_contents(
+    serialized_target: &Map<String, Value>,
+    assets: &mut dyn AssetSource,
+    store: &mut AssetStore,
+    eng_data: &EngineData,
+    renderer: &mut dyn Renderer,
+) -> Result<target::Target, LoadError> {
     let is_stage = serialized_target["isStage"]
         .as_bool()
-        .ok_or_else(|| "target has no isStage")?;
+        .ok_or("target has no isStage")?;
     let name = serialized_target["name"]
         .as_str()
-        .ok_or_else(|| "target has no name")?;
+        .ok_or("target has no name")?;
     let blocks = serialized_target["blocks"]
         .as_object()
-        .ok_or_else(|| "target has no blocks")?;
+        .ok_or("target has no blocks")?;
     let layer_order = serialized_target["layerOrder"]
         .as_u64()
-        .ok_or_else(|| "target has no layerOrder")?;
+        .ok_or("target has no layerOrder")?;
     let costumes = serialized_target["costumes"]
         .as_array()
-        .ok_or_else(|| "target has no costumes")?;
-    let d_blocks = deserialize_blocks(blocks, eng_data)?;
+        .ok_or("target has no costumes")?;
+    let sounds = serialized_target["sounds"]
+        .as_array()
+        .ok_or("target has no sounds")?;
+    if costumes.is_empty() {
+        return Err("target has no costumes".into());
+    }
+    // Scratch rounds and clamps this rather than rejecting it.
+    let current_costume = serialized_target["currentCostume"]
+        .as_f64()
+        .ok_or("target has no currentCostume")?
+        .round()
+        .clamp(0.0, (costumes.len() - 1) as f64);
+    let volume = serialized_target["volume"]
+        .as_f64()
+        .ok_or("target has no volume")?;
+    let variables = serialized_target["variables"]
+        .as_object()
+        .ok_or("target has no variables")?;
+    let (d_blocks, unsupported_opcodes) = deserialize_blocks(blocks, eng_data)?;
+    let d_variables = deserialize_variables(variables)?;
+    // Older files (and sprites saved on their own) can leave these out.
+    let empty = Map::new();
+    let d_lists = deserialize_lists(
+        serialized_target
+            .get("lists")
+            .and_then(Value::as_object)
+            .unwrap_or(&empty),
+    )?;
+    let d_broadcasts = deserialize_broadcasts(
+        serialized_target
+            .get("broadcasts")
+            .and_then(Value::as_object)
+            .unwrap_or(&empty),
+    )?;
     let mut d_costumes = Vec::with_capacity(costumes.len());
     for costume in costumes {
         d_costumes.push(deserialize_costume(
-            costume
-                .as_object()
-                .ok_or_else(|| "costume is not an object")?,
-            archive,
+            costume.as_object().ok_or("costume is not an object")?,
+            assets,
+            store,
+        )?);
+    }
+    let mut d_sounds = Vec::with_capacity(sounds.len());
+    for sound in sounds {
+        d_sounds.push(deserialize_sound(
+            sound.as_object().ok_or("sound is not an object")?,
+            assets,
+            store,
         )?);
     }
     println!("{:#?}", d_blocks);
     Ok(target::Target {
         scripts: compile_blocks(&d_blocks),
+        blocks: blocks.clone(),
+        comments: serialized_target
+            .get("comments")
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_default(),
+        unsupported_opcodes,
         is_stage,
         name: name.to_string(),
         layer_order: layer_order as u32,
+        variables: d_variables,
+        lists: d_lists,
+        broadcasts: d_broadcasts,
+        current_costume: current_costume as usize,
+        volume,
+        // The stage doesn't have a position, direction, etc. Default to the same values a sprite would.
+        x: serialized_target
+            .get("x")
+            .and_then(Value::as_f64)
+            .unwrap_or(0.0),
+        y: serialized_target
+            .get("y")
+            .and_then(Value::as_f64)
+            .unwrap_or(0.0),
+        direction: serialized_target
+            .get("direction")
+            .and_then(Value::as_f64)
+            .unwrap_or(90.0),
+        size: serialized_target
+            .get("size")
+            .and_then(Value::as_f64)
+            .unwrap_or(100.0),
+        visible: serialized_target
+            .get("visible")
+            .and_then(Value::as_bool)
+            .unwrap_or(true),
+        draggable: serialized_target
+            .get("draggable")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+        rotation_style: serialized_target
+            .get("rotationStyle")
+            .and_then(Value::as_str)
+            .unwrap_or("all around")
+            .to_string(),
+        tempo: serialized_target
+            .get("tempo")
+            .and_then(Value::as_f64)
+            .unwrap_or(60.0),
+        // Projects saved before these existed don't have them; keep it that way when saving.
+        video_state: serialized_target
+            .get("videoState")
+            .and_then(Value::as_str)
+            .map(String::from),
+        video_transparency: serialized_target
+            .get("videoTransparency")
+            .and_then(Value::as_f64),
+        text_to_speech_language: serialized_target
+            .get("textToSpeechLanguage")
+            .map(|language| language.as_str().map(String::from)),
         costumes: d_costumes
             .into_iter()
-            .map(|costume| costume.load(renderer))
+            .map(|costume| costume.load(renderer, store))
             .collect::<Vec<Costume>>()
             .into_boxed_slice(),
+        sounds: d_sounds
+            .into_iter()
+            .map(|sound| sound.load(store))
+            .collect::<Vec<Sound>>()
+            .into_boxed_slice(),
     })
 }
 
-pub fn deserialize_project<'a, 'eng>(
-    archive: &mut ZipArchive<File>,
-    eng_data: &'eng EngineData,
-    renderer: &mut Renderer,
-) -> Result<project::Project, &'a str> {
-    let mut json = String::new();
-    {
-        let mut project_json_file = archive
-            .by_name("project.json")
-            .map_err(|_| "project.json not found")?;
-        project_json_file
-            .read_to_string(&mut json)
-            .map_err(|_| "Could not read project.json")?;
+fn deserialize_meta(serialized_meta: Option<&Value>) -> ProjectMeta {
+    let field = |name: &str| {
+        serialized_meta
+            .and_then(|meta| meta.get(name))
+            .and_then(Value::as_str)
+            .map(String::from)
+    };
+    ProjectMeta {
+        semver: field("semver"),
+        vm: field("vm"),
+        agent: field("agent"),
+    }
+}
+
+fn deserialize_extensions(
+    serialized_extensions: Option<&Value>,
+) -> Result<Vec<String>, &'static str> {
+    let serialized_extensions = match serialized_extensions {
+        Some(serialized_extensions) => serialized_extensions
+            .as_array()
+            .ok_or("extensions is not an array")?,
+        None => return Ok(vec![]),
+    };
+    serialized_extensions
+        .iter()
+        .map(|extension| {
+            extension
+                .as_str()
+                .map(String::from)
+                .ok_or("extension ID is not a string")
+        })
+        .collect()
+}
+
+/// Fail (or warn) before loading anything else, if the project needs extensions that aren't implemented.
+fn check_extensions(
+    extensions: &[String],
+    check: ExtensionCheck,
+    eng_data: &EngineData,
+) -> Result<(), LoadError> {
+    for extension in extensions {
+        if is_extension_supported(extension, eng_data) {
+            continue;
+        }
+        match check {
+            ExtensionCheck::Strict => {
+                return Err("project uses an extension that isn't supported")
+                    .in_extension(extension)
+            }
+            ExtensionCheck::Warn => println!(
+                "Project uses the \"{}\" extension, which isn't supported; its blocks will do nothing",
+                extension
+            ),
+        }
     }
+    Ok(())
+}
 
-    let v: Value = serde_json::from_str(&json).map_err(|_| "Could not deserialize JSON")?;
+fn deserialize_monitor(serialized_monitor: &Map<String, Value>) -> Result<Monitor, &'static str> {
+    let number = |name: &str, default: f64| {
+        serialized_monitor
+            .get(name)
+            .and_then(Value::as_f64)
+            .unwrap_or(default)
+    };
+    Ok(Monitor {
+        id: serialized_monitor
+            .get("id")
+            .and_then(Value::as_str)
+            .ok_or("monitor has no id")?
+            .to_string(),
+        // Scratch treats modes it doesn't recognize as the default.
+        mode: serialized_monitor
+            .get("mode")
+            .and_then(Value::as_str)
+            .and_then(MonitorMode::from_name)
+            .unwrap_or(MonitorMode::Default),
+        opcode: serialized_monitor
+            .get("opcode")
+            .and_then(Value::as_str)
+            .ok_or("monitor has no opcode")?
+            .to_string(),
+        params: serialized_monitor
+            .get("params")
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_default(),
+        sprite_name: serialized_monitor
+            .get("spriteName")
+            .and_then(Value::as_str)
+            .map(String::from),
+        value: serialized_monitor
+            .get("value")
+            .cloned()
+            .unwrap_or(Value::Null),
+        x: number("x", 0.0),
+        y: number("y", 0.0),
+        width: number("width", 0.0),
+        height: number("height", 0.0),
+        visible: serialized_monitor
+            .get("visible")
+            .and_then(Value::as_bool)
+            .unwrap_or(true),
+        slider_min: number("sliderMin", 0.0),
+        slider_max: number("sliderMax", 100.0),
+        is_discrete: serialized_monitor
+            .get("isDiscrete")
+            .and_then(Value::as_bool)
+            .unwrap_or(true),
+    })
+}
+
+fn deserialize_monitors(serialized_monitors: Option<&Value>) -> Result<Vec<Monitor>, &'static str> {
+    let serialized_monitors = match serialized_monitors {
+        Some(serialized_monitors) => serialized_monitors
+            .as_array()
+            .ok_or("monitors is not an array")?,
+        None => return Ok(vec![]),
+    };
+    serialized_monitors
+        .iter()
+        .map(|monitor| deserialize_monitor(monitor.as_object().ok_or("monitor is not an object")?))
+        .collect()
+}
+
+/// What to do when a project uses extensions that aren't implemented.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExtensionCheck {
+    /// Fail to load the project.
+    Strict,
+    /// Print a warning and load the project anyway. The extension's blocks load as placeholders that do nothing.
+    #[default]
+    Warn,
+}
+
+/// Settings for how projects are loaded.
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    pub asset_verification: AssetVerification,
+    pub asset_loading: AssetLoading,
+    pub extension_check: ExtensionCheck,
+}
+
+/// Load a project from any source: an archive, an unpacked directory, or a bare project.json.
+pub fn deserialize_project<S: ProjectSource>(
+    source: &mut S,
+    options: &LoadOptions,
+    eng_data: &EngineData,
+    renderer: &mut dyn Renderer,
+) -> Result<project::Project, LoadError> {
+    let v = source.read_project_json()?;
+    deserialize_project_json(v, source, options, eng_data, renderer)
+}
+
+/// Deserialize an already-parsed `project.json` (in either the sb3 or sb2 layout), reading its assets from `assets`.
+pub fn deserialize_project_json(
+    mut v: Value,
+    assets: &mut dyn AssetSource,
+    options: &LoadOptions,
+    eng_data: &EngineData,
+    renderer: &mut dyn Renderer,
+) -> Result<project::Project, LoadError> {
+    if sb2::is_sb2_project(&v) {
+        v = sb2::convert_project(&v)?;
+    }
+
+    let meta = deserialize_meta(v.get("meta"));
+    let extensions = deserialize_extensions(v.get("extensions"))?;
+    check_extensions(&extensions, options.extension_check, eng_data)?;
+    let monitors = deserialize_monitors(v.get("monitors"))?;
 
     let mut targets = vec![];
+    let mut store = AssetStore::new(options.asset_verification, options.asset_loading);
 
     if let serde_json::Value::Array(serialized_targets) = &v["targets"] {
         serialized_targets
-            .into_iter()
-            .try_for_each(|target| -> Result<(), &str> {
+            .iter()
+            .try_for_each(|target| -> Result<(), LoadError> {
                 if let serde_json::Value::Object(target) = target {
-                    match deserialize_target(&target, archive, &eng_data, renderer) {
+                    match deserialize_target(target, assets, &mut store, eng_data, renderer) {
                         Ok(t) => {
                             targets.push(t);
                             Ok(())
@@ -383,12 +981,124 @@ pub fn deserialize_project<'a, 'eng>(
                         Err(e) => Err(e),
                     }
                 } else {
-                    return Err("Malformed JSON");
+                    Err("Malformed JSON")?
                 }
             })?
     } else {
-        return Err("Malformed JSON");
+        return Err("Malformed JSON".into());
     }
 
-    return Ok(project::Project { targets });
+    Ok(project::Project {
+        targets,
+        meta,
+        extensions,
+        monitors,
+        asset_store: RefCell::new(store),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        audio::output::NullOutput, renderer::headless::HeadlessRenderer, runtime::Runtime,
+    };
+
+    use serde_json::json;
+
+    const SVG: &[u8] = br#"<svg xmlns="http://www.w3.org/2000/svg" width="2" height="2"></svg>"#;
+
+    /// Every asset file has the same contents.
+    struct SvgAssets;
+
+    impl AssetSource for SvgAssets {
+        fn read_asset(&mut self, _name: &str) -> Result<Vec<u8>, &'static str> {
+            Ok(SVG.to_vec())
+        }
+    }
+
+    #[test]
+    fn unsupported_block_in_substack_is_a_command() {
+        let svg_md5 = format!("{:x}", md5::compute(SVG));
+        let project_json = json!({
+            "targets": [{
+                "isStage": true,
+                "name": "Stage",
+                "variables": {},
+                "lists": {},
+                "broadcasts": {},
+                "blocks": {
+                    "flag": {
+                        "opcode": "event_whenflagclicked",
+                        "next": "repeat",
+                        "parent": null,
+                        "inputs": {},
+                        "fields": {},
+                        "shadow": false,
+                        "topLevel": true,
+                    },
+                    "repeat": {
+                        "opcode": "control_repeat",
+                        "next": null,
+                        "parent": "flag",
+                        "inputs": { "TIMES": [1, [6, "3"]], "SUBSTACK": [2, "unknown"] },
+                        "fields": {},
+                        "shadow": false,
+                        "topLevel": false,
+                    },
+                    "unknown": {
+                        "opcode": "looks_notarealblock",
+                        "next": null,
+                        "parent": "repeat",
+                        "inputs": {},
+                        "fields": {},
+                        "shadow": false,
+                        "topLevel": false,
+                    },
+                },
+                "costumes": [{
+                    "name": "backdrop1",
+                    "dataFormat": "svg",
+                    "assetId": svg_md5,
+                    "md5ext": format!("{}.svg", svg_md5),
+                    "rotationCenterX": 1,
+                    "rotationCenterY": 1,
+                }],
+                "sounds": [],
+                "comments": {},
+                "currentCostume": 0,
+                "volume": 100,
+                "layerOrder": 0,
+            }],
+        });
+
+        let eng_data = EngineData::new();
+        let renderer = RefCell::new(HeadlessRenderer::new((480, 360)));
+        let project = deserialize_project_json(
+            project_json,
+            &mut SvgAssets,
+            &LoadOptions::default(),
+            &eng_data,
+            &mut *renderer.borrow_mut(),
+        )
+        .unwrap();
+        assert_eq!(
+            project.targets[0].unsupported_opcodes,
+            ["looks_notarealblock"]
+        );
+
+        let mut runtime = Runtime::new(
+            &project,
+            &eng_data,
+            &renderer,
+            Box::new(NullOutput::new(44100)),
+        );
+        runtime.green_flag();
+        while !runtime.threads_finished() {
+            assert!(runtime.ticks() < 10, "the loop never finished");
+            runtime.step();
+        }
+        // A reporter placeholder would leave a value on the stack on every pass through the loop.
+        assert_eq!(runtime.exec_contexts[0].threads[0].stack_len(), 0);
+    }
 }
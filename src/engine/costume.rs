@@ -1,31 +1,60 @@
 use std::{cell::RefCell, rc::Rc};
 
 use crate::{
-    data::asset::{Asset, AssetType},
-    renderer::{renderer::Renderer, skin::Skin},
+    data::{
+        asset::{Asset, AssetType},
+        asset_store::{AssetLoading, AssetStore},
+    },
+    renderer::{bitmap_skin::decode_bitmap, renderer::Renderer, skin::Skin},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CostumeAsset {
-    pub asset: Asset,
+    pub asset: Rc<Asset>,
     pub rotation_center: (f64, f64),
+    /// How many bitmap pixels make up one stage pixel. Only used for bitmap costumes; `None` if the project doesn't say,
+    /// which means 1.
+    pub bitmap_resolution: Option<f64>,
     pub name: String,
 }
 
 impl CostumeAsset {
-    pub fn load(self, renderer: &mut Renderer) -> Costume {
-        let skin = match self.asset.asset_type {
+    fn resolution(&self) -> f64 {
+        self.bitmap_resolution.unwrap_or(1.0)
+    }
+
+    fn create_skin(&self, renderer: &dyn Renderer) -> Box<dyn Skin> {
+        match self.asset.asset_type {
             AssetType::SVG => renderer.create_svg_skin(&self.asset.data, self.rotation_center),
-            AssetType::PNG => {
-                unimplemented!()
-            }
-            AssetType::JPEG => {
-                unimplemented!()
+            AssetType::PNG | AssetType::JPEG => {
+                let pixmap = decode_bitmap(
+                    &self.asset.data,
+                    matches!(self.asset.asset_type, AssetType::JPEG),
+                )
+                .unwrap_or_else(|err| {
+                    println!("Could not decode costume \"{}\": {}", self.name, err);
+                    tiny_skia::Pixmap::new(1, 1).unwrap()
+                });
+                renderer.create_bitmap_skin(pixmap, self.resolution(), self.rotation_center)
             }
             AssetType::MP3 | AssetType::WAV => {
                 panic!("Costume given non-image asset type")
             }
-        };
+        }
+    }
+
+    /// Create the costume's skin, or reuse the one for an identical costume that's already been loaded. The skin is
+    /// only rasterized now if loading eagerly.
+    pub fn load(self, renderer: &mut dyn Renderer, store: &mut AssetStore) -> Costume {
+        let costume_asset = self.clone();
+        let skin = store.skin(&self.asset, self.rotation_center, self.resolution(), || {
+            renderer.create_lazy_skin(Box::new(move |renderer| {
+                costume_asset.create_skin(renderer)
+            }))
+        });
+        if store.loading() == AssetLoading::Eager {
+            renderer.load_skin(&skin);
+        }
 
         Costume {
             costume_asset: self,
@@ -13,3 +13,9 @@ impl EngineData {
         }
     }
 }
+
+impl Default for EngineData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,6 +1,19 @@
-use std::cmp::Ordering;
+use std::{
+    cmp::Ordering,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{Datelike, Local, Timelike};
 
-use crate::scalar_value::ScalarValue;
+use crate::{
+    audio::{
+        midi::{MusicEvent, MusicEventKind},
+        mixer::{NoteParams, VoiceID},
+        sample_bank::{DRUMS, INSTRUMENTS},
+    },
+    runtime::Runtime,
+    scalar_value::ScalarValue,
+};
 
 use super::{
     instruction::Instruction,
@@ -8,8 +21,71 @@ use super::{
     thread::{Thread, ThreadStatus},
 };
 
-pub fn execute(sprite: &mut Sprite, current_thread: &mut Thread) {
-    let instruction = &current_thread.code.instructions[current_thread.instruction_pointer];
+/// Milliseconds between the Unix epoch and midnight UTC on January 1, 2000.
+const MILLIS_2000: f64 = 946_684_800_000.0;
+const MILLIS_PER_DAY: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+
+/// Most music extension notes and drums that can play at once. Further ones are skipped, like in scratch-vm.
+const MUSIC_CONCURRENCY_LIMIT: usize = 30;
+
+/// Round to the nearest integer the way JavaScript's Math.round does, with halves rounding up.
+fn js_round(n: f64) -> f64 {
+    (n + 0.5).floor()
+}
+
+/// Wrap a 1-indexed menu number (e.g. a drum or instrument) around into a 0-indexed list of the given length.
+fn wrap_menu_index(value: &ScalarValue, len: usize) -> usize {
+    let n = js_round(f64::from(value)) - 1.0;
+    if !n.is_finite() {
+        return 0;
+    }
+    n.rem_euclid(len as f64) as usize
+}
+
+/// Push the runtime time at which the given number of beats will be up, and yield. Like scratch-vm, beat-based blocks
+/// always wait at least one tick, even for 0 beats.
+fn start_beat_timer(runtime: &Runtime, current_thread: &mut Thread, beats: f64) {
+    let duration_msecs = (60.0 / runtime.tempo) * beats * 1000.0;
+    current_thread.push_stack(ScalarValue::Num(runtime.current_msecs + duration_msecs));
+    current_thread.yield_thread();
+}
+
+fn log_music_event(runtime: &mut Runtime, beats: f64, volume: f64, kind: MusicEventKind) {
+    let event = MusicEvent {
+        time: runtime.current_msecs,
+        duration: (60.0 / runtime.tempo) * beats * 1000.0,
+        tempo: runtime.tempo,
+        volume,
+        kind,
+    };
+    runtime.log_music_event(event);
+}
+
+fn clamp_beats(value: &ScalarValue) -> f64 {
+    f64::from(value).clamp(0.0, 100.0)
+}
+
+/// Start playing one of a sprite's sounds, looked up by name or number.
+fn play_sound(runtime: &mut Runtime, sprite_idx: usize, sound: &ScalarValue) -> Option<VoiceID> {
+    let sprite = &runtime.exec_contexts[sprite_idx].sprite;
+    let sound_idx = sprite.lookup_sound(sound)?;
+    let buffer = sprite.target.sounds[sound_idx].buffer();
+    Some(runtime.audio.mixer.play(buffer, sprite_idx, sound_idx))
+}
+
+pub fn execute(runtime: &mut Runtime, sprite_idx: usize, current_thread: &mut Thread) {
+    // Scripts can be empty, e.g. a hat block on its own.
+    let instruction = match current_thread
+        .code
+        .instructions
+        .get(current_thread.instruction_pointer)
+    {
+        Some(instruction) => instruction,
+        None => {
+            current_thread.status = ThreadStatus::Done;
+            return;
+        }
+    };
 
     let mut did_jump = false;
     match instruction {
@@ -51,6 +127,15 @@ pub fn execute(sprite: &mut Sprite, current_thread: &mut Thread) {
             current_thread.request_redraw();
         }
 
+        Instruction::EdgeActivatedHat => {
+            let value = bool::from(&current_thread.pop_stack());
+            let old_value = current_thread.update_edge_activated_value(value);
+            if old_value || !value {
+                current_thread.status = ThreadStatus::Done;
+                did_jump = true;
+            }
+        }
+
         // Operators
         Instruction::Add => {
             let op1 = current_thread.pop_stack();
@@ -86,11 +171,13 @@ pub fn execute(sprite: &mut Sprite, current_thread: &mut Thread) {
         Instruction::GotoXY => {
             let op1 = current_thread.pop_stack();
             let op2 = current_thread.pop_stack();
+            let sprite = &mut runtime.exec_contexts[sprite_idx].sprite;
             sprite.move_to(f64::from(&op1), f64::from(&op2));
             current_thread.request_redraw();
         }
 
         Instruction::MoveSteps => {
+            let sprite = &mut runtime.exec_contexts[sprite_idx].sprite;
             let steps = f64::from(&current_thread.pop_stack());
             let angle = (90.0 - sprite.direction) * (std::f64::consts::PI / 180.0);
             sprite.move_to(
@@ -99,6 +186,278 @@ pub fn execute(sprite: &mut Sprite, current_thread: &mut Thread) {
             );
             current_thread.request_redraw();
         }
+
+        // Events
+        Instruction::WhenGreaterThan => {
+            let value = f64::from(&current_thread.pop_stack());
+            let menu_value = String::from(&current_thread.pop_stack()).to_lowercase();
+            let result = match menu_value.as_str() {
+                "timer" => runtime.clock.project_timer(runtime.current_msecs) > value,
+                // There's no microphone input, which Scratch reports as a loudness of -1.
+                "loudness" => -1.0 > value,
+                _ => false,
+            };
+            current_thread.push_stack(ScalarValue::Bool(result));
+        }
+
+        // Sensing
+        Instruction::DistanceTo => {
+            let menu_value = String::from(&current_thread.pop_stack());
+            let sprite = &runtime.exec_contexts[sprite_idx].sprite;
+            let distance = if sprite.target.is_stage {
+                10000.0
+            } else {
+                let target_pos = if menu_value == "_mouse_" {
+                    Some((runtime.mouse.x, runtime.mouse.y))
+                } else {
+                    runtime
+                        .sprite_by_name(&menu_value)
+                        .map(|other| (other.x, other.y))
+                };
+
+                match target_pos {
+                    Some((x, y)) => ((sprite.x - x).powi(2) + (sprite.y - y).powi(2)).sqrt(),
+                    None => 10000.0,
+                }
+            };
+            current_thread.push_stack(ScalarValue::Num(distance));
+        }
+
+        Instruction::AttributeOf => {
+            let property = String::from(&current_thread.pop_stack());
+            let object = String::from(&current_thread.pop_stack());
+            let attr_sprite = if object == "_stage_" {
+                runtime.stage()
+            } else {
+                runtime.sprite_by_name(&object)
+            };
+            let value = match attr_sprite {
+                Some(attr_sprite) => attribute_of(attr_sprite, &property),
+                None => ScalarValue::Num(0.0),
+            };
+            current_thread.push_stack(value);
+        }
+
+        Instruction::Current => {
+            let menu_value = String::from(&current_thread.pop_stack()).to_lowercase();
+            let now = Local::now();
+            let value = match menu_value.as_str() {
+                "year" => now.year() as f64,
+                "month" => now.month() as f64,
+                "date" => now.day() as f64,
+                "dayofweek" => (now.weekday().num_days_from_sunday() + 1) as f64,
+                "hour" => now.hour() as f64,
+                "minute" => now.minute() as f64,
+                "second" => now.second() as f64,
+                _ => 0.0,
+            };
+            current_thread.push_stack(ScalarValue::Num(value));
+        }
+
+        Instruction::DaysSince2000 => {
+            let millis_since_epoch = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0.0, |duration| duration.as_millis() as f64);
+            current_thread.push_stack(ScalarValue::Num(
+                (millis_since_epoch - MILLIS_2000) / MILLIS_PER_DAY,
+            ));
+        }
+
+        Instruction::Username => {
+            current_thread.push_stack(ScalarValue::Text(Box::new(runtime.username.clone())));
+        }
+
+        Instruction::Timer => {
+            current_thread.push_stack(ScalarValue::Num(
+                runtime.clock.project_timer(runtime.current_msecs),
+            ));
+        }
+
+        Instruction::ResetTimer => {
+            runtime.clock.reset_project_timer(runtime.current_msecs);
+        }
+
+        Instruction::Ask => {
+            let question = String::from(&current_thread.pop_stack());
+            let id = runtime.ask(sprite_idx, question);
+            current_thread.question = Some(id);
+            current_thread.push_stack(ScalarValue::Num(id as f64));
+        }
+
+        Instruction::WaitForAnswer => {
+            let id = f64::from(current_thread.peek_stack()) as usize;
+            if runtime.questions.is_pending(id) {
+                current_thread.yield_thread();
+                did_jump = true;
+            } else {
+                current_thread.question = None;
+                current_thread.pop_stack();
+            }
+        }
+
+        Instruction::SetDragMode => {
+            let drag_mode = String::from(&current_thread.pop_stack());
+            runtime.exec_contexts[sprite_idx].sprite.draggable = drag_mode == "draggable";
+        }
+
+        Instruction::Answer => {
+            current_thread.push_stack(ScalarValue::Text(Box::new(
+                runtime.questions.answer.clone(),
+            )));
+        }
+
+        Instruction::StartSound => {
+            let sound = current_thread.pop_stack();
+            play_sound(runtime, sprite_idx, &sound);
+        }
+
+        Instruction::PlaySound => {
+            let sound = current_thread.pop_stack();
+            let voice_id = play_sound(runtime, sprite_idx, &sound).unwrap_or(0);
+            current_thread.push_stack(ScalarValue::Num(voice_id as f64));
+        }
+
+        Instruction::WaitForSound => {
+            let voice_id = f64::from(current_thread.peek_stack()) as VoiceID;
+            if runtime.audio.mixer.is_playing(voice_id) {
+                current_thread.yield_thread();
+                did_jump = true;
+            } else {
+                current_thread.pop_stack();
+            }
+        }
+
+        Instruction::StopAllSounds => runtime.audio.mixer.stop_all(),
+
+        Instruction::SetVolumeTo => {
+            let volume = f64::from(&current_thread.pop_stack());
+            runtime.exec_contexts[sprite_idx].sprite.volume = volume.clamp(0.0, 100.0);
+            runtime.update_sound_params(sprite_idx);
+        }
+
+        Instruction::ChangeVolumeBy => {
+            let delta = f64::from(&current_thread.pop_stack());
+            let sprite = &mut runtime.exec_contexts[sprite_idx].sprite;
+            sprite.volume = (sprite.volume + delta).clamp(0.0, 100.0);
+            runtime.update_sound_params(sprite_idx);
+        }
+
+        Instruction::Volume => {
+            current_thread.push_stack(ScalarValue::Num(
+                runtime.exec_contexts[sprite_idx].sprite.volume,
+            ));
+        }
+
+        Instruction::SetSoundEffectTo => {
+            let value = f64::from(&current_thread.pop_stack());
+            let effect = String::from(&current_thread.pop_stack());
+            runtime.exec_contexts[sprite_idx]
+                .sprite
+                .sound_effects
+                .set(&effect, value);
+            runtime.update_sound_params(sprite_idx);
+        }
+
+        Instruction::ChangeSoundEffectBy => {
+            let value = f64::from(&current_thread.pop_stack());
+            let effect = String::from(&current_thread.pop_stack());
+            runtime.exec_contexts[sprite_idx]
+                .sprite
+                .sound_effects
+                .change(&effect, value);
+            runtime.update_sound_params(sprite_idx);
+        }
+
+        Instruction::PlayDrum => {
+            let beats = clamp_beats(&current_thread.pop_stack());
+            let drum = wrap_menu_index(&current_thread.pop_stack(), DRUMS.len());
+            if runtime.audio.mixer.num_notes_playing() < MUSIC_CONCURRENCY_LIMIT {
+                let buffer = runtime.audio.sample_bank.drum_sample(drum);
+                let volume = runtime.exec_contexts[sprite_idx].sprite.volume;
+                runtime.audio.mixer.play_note(
+                    buffer,
+                    NoteParams {
+                        gain: (volume / 100.0) as f32,
+                        playback_rate: 1.0,
+                        duration: None,
+                        release: 0.0,
+                    },
+                );
+                log_music_event(runtime, beats, volume, MusicEventKind::Drum { drum });
+            }
+            start_beat_timer(runtime, current_thread, beats);
+        }
+
+        Instruction::PlayNote => {
+            let beats = clamp_beats(&current_thread.pop_stack());
+            let note = f64::from(&current_thread.pop_stack()).clamp(0.0, 130.0);
+            let duration = (60.0 / runtime.tempo) * beats;
+            if duration > 0.0 && runtime.audio.mixer.num_notes_playing() < MUSIC_CONCURRENCY_LIMIT {
+                let sprite = &runtime.exec_contexts[sprite_idx].sprite;
+                let instrument = sprite.instrument;
+                let volume = sprite.volume;
+                let (buffer, playback_rate) = runtime
+                    .audio
+                    .sample_bank
+                    .instrument_sample(instrument, note);
+                runtime.audio.mixer.play_note(
+                    buffer,
+                    NoteParams {
+                        gain: (volume / 100.0) as f32,
+                        playback_rate,
+                        duration: Some(duration),
+                        release: INSTRUMENTS[instrument].release_time,
+                    },
+                );
+                log_music_event(
+                    runtime,
+                    beats,
+                    volume,
+                    MusicEventKind::Note { note, instrument },
+                );
+            }
+            start_beat_timer(runtime, current_thread, beats);
+        }
+
+        Instruction::Rest => {
+            let beats = clamp_beats(&current_thread.pop_stack());
+            start_beat_timer(runtime, current_thread, beats);
+        }
+
+        Instruction::WaitUntilTime => {
+            let end_msecs = f64::from(current_thread.peek_stack());
+            if runtime.current_msecs < end_msecs {
+                current_thread.yield_thread();
+                did_jump = true;
+            } else {
+                current_thread.pop_stack();
+            }
+        }
+
+        Instruction::SetInstrument => {
+            let instrument = wrap_menu_index(&current_thread.pop_stack(), INSTRUMENTS.len());
+            runtime.exec_contexts[sprite_idx].sprite.instrument = instrument;
+        }
+
+        Instruction::SetTempo => {
+            let tempo = f64::from(&current_thread.pop_stack());
+            runtime.tempo = tempo.clamp(20.0, 500.0);
+        }
+
+        Instruction::ChangeTempo => {
+            let delta = f64::from(&current_thread.pop_stack());
+            runtime.tempo = (runtime.tempo + delta).clamp(20.0, 500.0);
+        }
+
+        Instruction::Tempo => current_thread.push_stack(ScalarValue::Num(runtime.tempo)),
+
+        Instruction::ClearSoundEffects => {
+            runtime.exec_contexts[sprite_idx]
+                .sprite
+                .sound_effects
+                .clear();
+            runtime.update_sound_params(sprite_idx);
+        }
     }
 
     if !did_jump {
@@ -108,3 +467,36 @@ pub fn execute(sprite: &mut Sprite, current_thread: &mut Thread) {
         }
     }
 }
+
+/// Implements the "of" sensing block. Sprite-specific properties are checked first, and anything else is treated as the
+/// name of one of the sprite's variables.
+fn attribute_of(sprite: &Sprite, property: &str) -> ScalarValue {
+    if sprite.target.is_stage {
+        match property {
+            "background #" | "backdrop #" => return ScalarValue::Num((sprite.costume + 1) as f64),
+            "backdrop name" => {
+                return ScalarValue::Text(Box::new(sprite.costume_name().to_string()))
+            }
+            "volume" => return ScalarValue::Num(sprite.volume),
+            _ => {}
+        }
+    } else {
+        match property {
+            "x position" => return ScalarValue::Num(sprite.x),
+            "y position" => return ScalarValue::Num(sprite.y),
+            "direction" => return ScalarValue::Num(sprite.direction),
+            "costume #" => return ScalarValue::Num((sprite.costume + 1) as f64),
+            "costume name" => {
+                return ScalarValue::Text(Box::new(sprite.costume_name().to_string()))
+            }
+            "size" => return ScalarValue::Num(sprite.size),
+            "volume" => return ScalarValue::Num(sprite.volume),
+            _ => {}
+        }
+    }
+
+    sprite
+        .lookup_variable_by_name(property)
+        .cloned()
+        .unwrap_or(ScalarValue::Num(0.0))
+}
@@ -19,6 +19,7 @@ pub enum Instruction {
     ReadFrameValue,
     WriteFrameValue,
     RequestRedraw,
+    EdgeActivatedHat, // End the thread unless the predicate on top of the stack just became true
 
     Add,
     Subtract,
@@ -28,4 +29,38 @@ pub enum Instruction {
 
     GotoXY,
     MoveSteps,
+
+    WhenGreaterThan,
+
+    DistanceTo,
+    AttributeOf,
+    Current,
+    DaysSince2000,
+    Username,
+    Timer,
+    ResetTimer,
+    Ask,           // Queue a question and push its ID onto the stack
+    WaitForAnswer, // Wait until the question whose ID is on top of the stack has been answered
+    Answer,
+    SetDragMode,
+
+    StartSound,
+    PlaySound, // Start a sound and push its voice ID onto the stack (0 if there's no such sound)
+    WaitForSound, // Wait until the voice whose ID is on top of the stack has finished playing
+    StopAllSounds,
+    SetVolumeTo,
+    ChangeVolumeBy,
+    Volume,
+    SetSoundEffectTo,    // Pop a value and an effect name
+    ChangeSoundEffectBy, // Pop a value and an effect name
+    ClearSoundEffects,
+
+    PlayDrum,      // Pop beats and a drum, play it, and push the time the beats end at
+    PlayNote,      // Pop beats and a note, play it, and push the time the beats end at
+    Rest,          // Pop beats and push the time they end at
+    WaitUntilTime, // Wait until the runtime clock reaches the time on top of the stack
+    SetInstrument,
+    SetTempo,
+    ChangeTempo,
+    Tempo,
 }
@@ -0,0 +1,43 @@
+/// The state of the mouse, in Scratch stage coordinates.
+#[derive(Debug, Default)]
+pub struct Mouse {
+    pub x: f64,
+    pub y: f64,
+    pub is_down: bool,
+}
+
+impl Mouse {
+    /// Update the mouse position from a position in window ("client") space. Mirrors scratch-vm's mouse IO device,
+    /// which rounds to whole stage units and clamps to the stage bounds.
+    pub fn post_position(
+        &mut self,
+        client_pos: (f64, f64),
+        client_size: (u32, u32),
+        stage_size: (u32, u32),
+    ) {
+        let (stage_width, stage_height) = (stage_size.0 as f64, stage_size.1 as f64);
+        self.x = (stage_width * ((client_pos.0 / client_size.0 as f64) - 0.5))
+            .clamp(stage_width * -0.5, stage_width * 0.5)
+            .round();
+        self.y = (-stage_height * ((client_pos.1 / client_size.1 as f64) - 0.5))
+            .clamp(stage_height * -0.5, stage_height * 0.5)
+            .round();
+    }
+}
+
+/// Keeps track of the project timer, measured against the runtime's clock.
+#[derive(Debug, Default)]
+pub struct Clock {
+    project_timer_start: f64,
+}
+
+impl Clock {
+    /// The project timer's value, in seconds.
+    pub fn project_timer(&self, current_msecs: f64) -> f64 {
+        (current_msecs - self.project_timer_start) / 1000.0
+    }
+
+    pub fn reset_project_timer(&mut self, current_msecs: f64) {
+        self.project_timer_start = current_msecs;
+    }
+}
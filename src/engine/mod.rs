@@ -2,8 +2,13 @@ pub mod costume;
 pub mod engine_data;
 pub mod execute;
 pub mod instruction;
+pub mod io;
+pub mod monitor;
 pub mod project;
+pub mod question;
+pub mod sound;
 pub mod sprite;
 pub mod target;
 pub mod thread;
 pub mod trigger;
+pub mod variable;
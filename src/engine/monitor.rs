@@ -0,0 +1,61 @@
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonitorMode {
+    /// A variable or reporter's name and value.
+    Default,
+    /// Just the value, in large text.
+    Large,
+    /// A variable's value, with a slider for changing it.
+    Slider,
+    /// A list's contents.
+    List,
+}
+
+impl MonitorMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "default" => MonitorMode::Default,
+            "large" => MonitorMode::Large,
+            "slider" => MonitorMode::Slider,
+            "list" => MonitorMode::List,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MonitorMode::Default => "default",
+            MonitorMode::Large => "large",
+            MonitorMode::Slider => "slider",
+            MonitorMode::List => "list",
+        }
+    }
+}
+
+/// A variable, list, or reporter shown on the stage. Scratch keeps a monitor for everything that's ever been shown, so
+/// many are hidden.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    /// For variables and lists, their ID. For other reporters, derived from the opcode and sprite.
+    pub id: String,
+    pub mode: MonitorMode,
+    /// The reporter being shown, e.g. `data_variable` or `motion_xposition`.
+    pub opcode: String,
+    /// The reporter's fields, e.g. which variable or list it shows.
+    pub params: Map<String, Value>,
+    /// The sprite whose local variable or attribute is shown, or None if it's global or the stage's.
+    pub sprite_name: Option<String>,
+    /// The value shown when the project was saved; for lists, their contents.
+    pub value: Value,
+    pub x: f64,
+    pub y: f64,
+    /// Width and height in stage pixels, or 0 to size the monitor to fit its contents.
+    pub width: f64,
+    pub height: f64,
+    pub visible: bool,
+    pub slider_min: f64,
+    pub slider_max: f64,
+    /// Whether the slider only moves in whole numbers.
+    pub is_discrete: bool,
+}
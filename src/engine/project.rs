@@ -1,7 +1,30 @@
-use crate::engine::target::Target;
+use std::cell::RefCell;
+
+use crate::{
+    data::asset_store::AssetStore,
+    engine::{monitor::Monitor, target::Target},
+};
+
+/// Which editor saved a project, as recorded in its `meta` section. Projects converted from older formats may not say.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProjectMeta {
+    /// Version of the project format, e.g. "3.0.0".
+    pub semver: Option<String>,
+    /// Version of scratch-vm that saved the project.
+    pub vm: Option<String>,
+    /// User agent of the browser (or name of the program) that saved the project.
+    pub agent: Option<String>,
+}
 
 /// The "static" part of a Scratch project. E.g. contains targets (sprites' prototypes), blocks, etc
 #[derive(Debug)]
 pub struct Project {
     pub targets: Vec<Target>,
+    pub meta: ProjectMeta,
+    /// IDs of the extensions the project uses, e.g. "music" or "pen".
+    pub extensions: Vec<String>,
+    pub monitors: Vec<Monitor>,
+    /// Every asset the targets use. Kept so that sprites added later, even while the project is running, share
+    /// assets and skins with the ones already loaded.
+    pub asset_store: RefCell<AssetStore>,
 }
@@ -0,0 +1,75 @@
+use std::collections::VecDeque;
+
+/// A question asked by an "ask and wait" block, waiting for the user to answer it.
+#[derive(Debug)]
+pub struct Question {
+    pub id: usize,
+    pub text: String,
+    pub sprite_idx: usize,
+    /// Whether the asking sprite was visible (and not the stage) when it asked. If so, the question is shown in its
+    /// speech bubble instead of the prompt.
+    pub in_bubble: bool,
+}
+
+/// Questions are answered one at a time, in the order they were asked.
+#[derive(Debug, Default)]
+pub struct QuestionQueue {
+    questions: VecDeque<Question>,
+    next_id: usize,
+    /// The most recently submitted answer, reported by the "answer" block.
+    pub answer: String,
+    /// The text typed into the prompt so far.
+    pub text_entry: String,
+}
+
+impl QuestionQueue {
+    /// Add a question to the queue, returning its ID.
+    pub fn push(&mut self, text: String, sprite_idx: usize, in_bubble: bool) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.questions.push_back(Question {
+            id,
+            text,
+            sprite_idx,
+            in_bubble,
+        });
+        id
+    }
+
+    pub fn current(&self) -> Option<&Question> {
+        self.questions.front()
+    }
+
+    pub fn is_pending(&self, id: usize) -> bool {
+        self.questions.iter().any(|question| question.id == id)
+    }
+
+    /// Drop a question that nobody is waiting on anymore. Returns it if it was the one being asked.
+    pub fn cancel(&mut self, id: usize) -> Option<Question> {
+        let idx = self
+            .questions
+            .iter()
+            .position(|question| question.id == id)?;
+        let question = self.questions.remove(idx)?;
+        if idx != 0 {
+            return None;
+        }
+        self.text_entry.clear();
+        Some(question)
+    }
+
+    /// Drop every question, e.g. when the project restarts. Returns the one being asked, if any.
+    pub fn clear(&mut self) -> Option<Question> {
+        let current = self.questions.pop_front();
+        self.questions.clear();
+        self.text_entry.clear();
+        current
+    }
+
+    /// Answer the current question with the text typed into the prompt, returning the question that was answered.
+    pub fn submit(&mut self) -> Option<Question> {
+        let question = self.questions.pop_front()?;
+        self.answer = std::mem::take(&mut self.text_entry);
+        Some(question)
+    }
+}
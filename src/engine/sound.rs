@@ -0,0 +1,118 @@
+use std::cell::LazyCell;
+use std::f64::consts::FRAC_PI_2;
+use std::rc::Rc;
+
+use crate::{
+    audio::{
+        decode::{decode_mp3, decode_wav, SoundBuffer},
+        mixer::VoiceParams,
+    },
+    data::{
+        asset::{Asset, AssetType},
+        asset_store::AssetStore,
+    },
+};
+
+/// A sound's audio, which is decoded the first time it's needed.
+pub type LazySoundBuffer = LazyCell<Rc<SoundBuffer>, Box<dyn FnOnce() -> Rc<SoundBuffer>>>;
+
+#[derive(Debug, Clone)]
+pub struct SoundAsset {
+    pub asset: Rc<Asset>,
+    pub name: String,
+    /// How the audio is encoded, e.g. "adpcm", or "" for plain PCM. `None` if the project doesn't say.
+    pub format: Option<String>,
+    pub rate: Option<u32>,
+    pub sample_count: Option<u32>,
+}
+
+impl SoundAsset {
+    fn decode(&self) -> SoundBuffer {
+        let decoded = match self.asset.asset_type {
+            AssetType::WAV => decode_wav(&self.asset.data),
+            AssetType::MP3 => decode_mp3(&self.asset.data),
+            AssetType::PNG | AssetType::SVG | AssetType::JPEG => Err("not an audio file"),
+        };
+
+        // Like scratch-vm, a sound that fails to decode is treated as silence rather than failing the whole project.
+        decoded.unwrap_or_else(|err| {
+            println!("Could not decode sound \"{}\": {}", self.name, err);
+            SoundBuffer::empty()
+        })
+    }
+
+    /// Set up decoding the sound, or reuse the audio from an identical sound that's already been loaded.
+    pub fn load(self, store: &mut AssetStore) -> Sound {
+        let sound_asset = self.clone();
+        let buffer = store.sound_buffer(&self.asset, move || sound_asset.decode());
+
+        Sound {
+            sound_asset: self,
+            buffer,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Sound {
+    pub sound_asset: SoundAsset,
+    buffer: Rc<LazySoundBuffer>,
+}
+
+impl Sound {
+    /// The sound's audio, decoding it first if it hasn't been yet.
+    pub fn buffer(&self) -> Rc<SoundBuffer> {
+        Rc::clone(LazyCell::force(&self.buffer))
+    }
+}
+
+/// A sprite's pitch and pan effects, in Scratch's units.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SoundEffects {
+    pub pitch: f64,
+    pub pan: f64,
+}
+
+impl SoundEffects {
+    pub fn set(&mut self, effect: &str, value: f64) {
+        match effect.to_ascii_lowercase().as_str() {
+            "pitch" => self.pitch = value.clamp(-360.0, 360.0),
+            "pan" => self.pan = value.clamp(-100.0, 100.0),
+            _ => {}
+        }
+    }
+
+    pub fn change(&mut self, effect: &str, value: f64) {
+        match effect.to_ascii_lowercase().as_str() {
+            "pitch" => self.set(effect, self.pitch + value),
+            "pan" => self.set(effect, self.pan + value),
+            _ => {}
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = SoundEffects::default();
+    }
+
+    /// Mixer parameters for a sprite with these effects and the given volume (0-100).
+    pub fn voice_params(&self, volume: f64) -> VoiceParams {
+        // Each 10 units of pitch is one semitone, as in scratch-audio.
+        let playback_rate = 2f64.powf(self.pitch / 10.0 / 12.0);
+
+        // Equal-power panning. scratch-audio bypasses the pan effect entirely when it's 0, so a centered sound plays
+        // at full volume in both channels rather than being attenuated.
+        let (left_gain, right_gain) = if self.pan == 0.0 {
+            (1.0, 1.0)
+        } else {
+            let p = (self.pan + 100.0) / 200.0;
+            ((p * FRAC_PI_2).cos(), (p * FRAC_PI_2).sin())
+        };
+
+        VoiceParams {
+            gain: (volume / 100.0) as f32,
+            playback_rate,
+            left_gain: left_gain as f32,
+            right_gain: right_gain as f32,
+        }
+    }
+}
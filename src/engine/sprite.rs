@@ -1,8 +1,22 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use glam::Vec2;
+
+use crate::engine::sound::SoundEffects;
 use crate::engine::target::Target;
 use crate::renderer::renderer::{DrawableID, Renderer};
+use crate::renderer::text_bubble::BubbleType;
+use crate::scalar_value::ScalarValue;
+
+/// A speech bubble currently shown above a sprite.
+pub struct Bubble {
+    pub drawable: DrawableID,
+    pub bubble_type: BubbleType,
+    pub text: String,
+    pub on_sprite_right: bool,
+    size: Vec2,
+}
 
 pub struct Sprite<'t, 'r> {
     pub x: f64,
@@ -10,34 +24,223 @@ pub struct Sprite<'t, 'r> {
     pub direction: f64,
     pub size: f64,
     pub visible: bool,
+    pub costume: usize,
+    pub volume: f64,
+    pub sound_effects: SoundEffects,
+    /// Index of the music extension instrument this sprite plays notes with.
+    pub instrument: usize,
+    pub draggable: bool,
+    /// Whether the user is currently dragging this sprite. Scripts can't move a sprite while it's being dragged.
+    pub dragging: bool,
+    /// Current variable values, in the same order as the target's variables.
+    pub variables: Vec<ScalarValue>,
     pub target: &'t Target,
-    pub layer_order: u32,
     pub drawable: DrawableID,
-    pub renderer: &'r RefCell<Renderer>,
+    pub renderer: &'r RefCell<dyn Renderer>,
+    pub bubble: Option<Bubble>,
 }
 
 impl<'t, 'r> Sprite<'t, 'r> {
-    pub fn new(target: &'t Target, renderer: &'r RefCell<Renderer>) -> Self {
+    pub fn new(target: &'t Target, renderer: &'r RefCell<dyn Renderer>) -> Self {
+        let drawable = renderer
+            .borrow_mut()
+            .create_drawable(Rc::clone(&target.costumes[target.current_costume].skin));
+        renderer
+            .borrow_mut()
+            .update_drawable_position(drawable, (target.x, target.y));
+        renderer
+            .borrow_mut()
+            .update_drawable_visibility(drawable, target.visible);
+
         Sprite {
-            x: 0.0,
-            y: 0.0,
-            direction: 0.0,
-            size: 100.0,
-            visible: true,
+            x: target.x,
+            y: target.y,
+            direction: target.direction,
+            size: target.size,
+            visible: target.visible,
+            costume: target.current_costume,
+            volume: target.volume,
+            sound_effects: SoundEffects::default(),
+            instrument: 0,
+            draggable: target.draggable,
+            dragging: false,
+            variables: target
+                .variables
+                .iter()
+                .map(|variable| variable.value.clone())
+                .collect(),
             target,
-            layer_order: target.layer_order,
-            drawable: renderer
-                .borrow_mut()
-                .create_drawable(Rc::clone(&target.costumes[0].skin)),
+            drawable,
             renderer,
+            bubble: None,
         }
     }
 
     pub fn move_to(&mut self, x: f64, y: f64) {
+        if self.dragging {
+            return;
+        }
+        self.set_xy(x, y);
+    }
+
+    /// Move the sprite while it's being dragged, overriding the usual restriction on moving dragged sprites.
+    pub fn drag_to(&mut self, x: f64, y: f64) {
+        self.set_xy(x, y);
+    }
+
+    fn set_xy(&mut self, x: f64, y: f64) {
+        if self.target.is_stage {
+            return;
+        }
+
+        let (x, y) = self
+            .renderer
+            .borrow_mut()
+            .get_fenced_position_of_drawable(self.drawable, (x, y));
         self.x = x;
         self.y = y;
         self.renderer
             .borrow_mut()
             .update_drawable_position(self.drawable, (self.x, self.y));
+        self.position_bubble();
+    }
+
+    pub fn go_to_front(&mut self) {
+        self.renderer
+            .borrow_mut()
+            .move_drawable_to_front(self.drawable);
+    }
+
+    /// Show a speech bubble with the given text, or remove the current one if the text is empty.
+    pub fn set_bubble(&mut self, bubble_type: BubbleType, text: &str) {
+        if text.is_empty() || !self.visible {
+            if let Some(bubble) = self.bubble.take() {
+                self.renderer.borrow_mut().destroy_drawable(bubble.drawable);
+            }
+            return;
+        }
+
+        let on_sprite_right = self
+            .bubble
+            .as_ref()
+            .is_none_or(|bubble| bubble.on_sprite_right);
+        self.render_bubble(bubble_type, text.to_string(), on_sprite_right);
+        self.position_bubble();
+    }
+
+    fn render_bubble(&mut self, bubble_type: BubbleType, text: String, on_sprite_right: bool) {
+        let mut renderer = self.renderer.borrow_mut();
+        let skin = renderer.create_text_bubble_skin(bubble_type, &text, on_sprite_right);
+        let size = skin.borrow().get_size();
+
+        let drawable = match &self.bubble {
+            Some(bubble) => {
+                renderer.update_drawable_skin(bubble.drawable, skin);
+                bubble.drawable
+            }
+            None => renderer.create_overlay_drawable(skin),
+        };
+
+        self.bubble = Some(Bubble {
+            drawable,
+            bubble_type,
+            text,
+            on_sprite_right,
+            size,
+        });
+    }
+
+    /// Keep the bubble next to the top of the sprite and inside the stage, flipping it to the sprite's other side if
+    /// it would go offstage. Mirrors scratch-vm's bubble positioning.
+    fn position_bubble(&mut self) {
+        let bubble = match &self.bubble {
+            Some(bubble) => bubble,
+            None => return,
+        };
+
+        let (target_bounds, stage_size) = {
+            let mut renderer = self.renderer.borrow_mut();
+            (
+                renderer.get_drawable_bounds(self.drawable),
+                renderer.stage_size(),
+            )
+        };
+        let stage_right = stage_size.0 as f32 * 0.5;
+        let stage_left = -stage_right;
+        let stage_top = stage_size.1 as f32 * 0.5;
+        let size = bubble.size;
+
+        let flip = if bubble.on_sprite_right {
+            size.x + target_bounds.right > stage_right && target_bounds.left - size.x > stage_left
+        } else {
+            target_bounds.left - size.x < stage_left && size.x + target_bounds.right < stage_right
+        };
+        if flip {
+            let bubble_type = bubble.bubble_type;
+            let text = bubble.text.clone();
+            let on_sprite_right = !bubble.on_sprite_right;
+            self.render_bubble(bubble_type, text, on_sprite_right);
+        }
+
+        let bubble = self.bubble.as_ref().unwrap();
+        let size = bubble.size;
+        let left = if bubble.on_sprite_right {
+            target_bounds
+                .right
+                .min(stage_right - size.x)
+                .max(stage_left)
+        } else {
+            (target_bounds.left - size.x)
+                .max(stage_left)
+                .min(stage_right - size.x)
+        };
+        let top = (target_bounds.top + size.y).min(stage_top);
+
+        self.renderer.borrow_mut().update_drawable_position(
+            bubble.drawable,
+            (
+                (left + (size.x * 0.5)) as f64,
+                (top - (size.y * 0.5)) as f64,
+            ),
+        );
+    }
+
+    pub fn costume_name(&self) -> &str {
+        &self.target.costumes[self.costume].costume_asset.name
+    }
+
+    /// Find a sound by name or, failing that, by its (1-indexed, wrapped) position in the sound list.
+    pub fn lookup_sound(&self, sound: &ScalarValue) -> Option<usize> {
+        let sounds = &self.target.sounds;
+        if sounds.is_empty() {
+            return None;
+        }
+
+        let name: String = sound.into();
+        if let Some(idx) = sounds
+            .iter()
+            .position(|sound| sound.sound_asset.name == name)
+        {
+            return Some(idx);
+        }
+
+        let index = match sound {
+            ScalarValue::Num(n) if n.is_finite() => n.trunc() as i64,
+            _ => name
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())?
+                .trunc() as i64,
+        };
+        Some((index - 1).rem_euclid(sounds.len() as i64) as usize)
+    }
+
+    pub fn lookup_variable_by_name(&self, name: &str) -> Option<&ScalarValue> {
+        self.target
+            .variables
+            .iter()
+            .position(|variable| variable.name == name)
+            .map(|idx| &self.variables[idx])
     }
 }
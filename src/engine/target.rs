@@ -1,11 +1,53 @@
-use crate::{engine::costume::Costume, engine::instruction::Script};
+use crate::{
+    engine::costume::Costume,
+    engine::instruction::Script,
+    engine::sound::Sound,
+    engine::variable::{Broadcast, List, Variable},
+};
+
+use serde_json::{Map, Value};
 
 /// The "prototype" of a sprite. Each Sprite object is an instance that refers back to one of these.
 #[derive(Debug)]
 pub struct Target {
     pub scripts: Box<[Script]>,
+    /// The blocks that the scripts were compiled from, as they appear in project.json. Kept so that the project can
+    /// be saved again.
+    pub blocks: Map<String, Value>,
+    /// Workspace comments, as they appear in project.json. Also only kept for saving.
+    pub comments: Map<String, Value>,
+    /// The opcode of every block (or primitive) that was loaded as a do-nothing placeholder because it isn't
+    /// supported, once per block.
+    pub unsupported_opcodes: Vec<String>,
     pub is_stage: bool,
     pub name: String,
     pub layer_order: u32,
     pub costumes: Box<[Costume]>,
+    pub sounds: Box<[Sound]>,
+    pub variables: Box<[Variable]>,
+    pub lists: Box<[List]>,
+    /// Broadcast messages. In Scratch these all live on the stage, but sprites loaded on their own keep any they
+    /// introduce.
+    pub broadcasts: Box<[Broadcast]>,
+    pub current_costume: usize,
+    pub volume: f64,
+    pub x: f64,
+    pub y: f64,
+    pub direction: f64,
+    pub size: f64,
+    pub visible: bool,
+    pub draggable: bool,
+    /// "all around", "left-right", or "don't rotate".
+    pub rotation_style: String,
+    /// Music extension tempo, in beats per minute. Only stored on the stage.
+    pub tempo: f64,
+    /// Video sensing extension state: "on", "off", or "on-flipped". Only stored on the stage; `None` if the project
+    /// doesn't say, which Scratch treats as "on".
+    pub video_state: Option<String>,
+    /// Video sensing extension transparency, from 0 to 100. Only stored on the stage; `None` if the project doesn't
+    /// say, which Scratch treats as 50.
+    pub video_transparency: Option<f64>,
+    /// Text to speech extension language. Only stored on the stage; `None` if the project doesn't say, and
+    /// `Some(None)` if it saves no language, to follow the editor's.
+    pub text_to_speech_language: Option<Option<String>>,
 }
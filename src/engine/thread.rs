@@ -26,6 +26,11 @@ pub struct Thread<'a> {
     pub instruction_pointer: usize,
     pub status: ThreadStatus,
     pub redraw_requested: bool,
+    /// The last result of this thread's hat predicate, if the hat is edge-activated. Persists across restarts.
+    edge_activated_value: bool,
+    /// The question this thread asked and is waiting to have answered. If the thread is restarted, nobody is waiting
+    /// for the answer anymore, so the runtime drops the question.
+    pub question: Option<usize>,
 }
 
 impl<'a> Thread<'a> {
@@ -37,6 +42,8 @@ impl<'a> Thread<'a> {
             instruction_pointer: 0,
             status: ThreadStatus::Done,
             redraw_requested: false,
+            edge_activated_value: false,
+            question: None,
         }
     }
 
@@ -55,6 +62,11 @@ impl<'a> Thread<'a> {
         self.stack_frames.clear();
     }
 
+    /// Store the latest result of this thread's edge-activated hat predicate, returning the previous one.
+    pub fn update_edge_activated_value(&mut self, value: bool) -> bool {
+        std::mem::replace(&mut self.edge_activated_value, value)
+    }
+
     pub fn resume(&mut self) {
         self.status = ThreadStatus::Running;
     }
@@ -75,6 +87,11 @@ impl<'a> Thread<'a> {
         self.stack.last().unwrap()
     }
 
+    /// How many values are on the stack. Scripts leave it empty when they finish.
+    pub fn stack_len(&self) -> usize {
+        self.stack.len()
+    }
+
     pub fn push_stack_frame(&mut self) {
         self.stack_frames.push(StackFrame {
             frame_value: ScalarValue::Num(0.0),
@@ -89,11 +106,9 @@ impl<'a> Thread<'a> {
         self.stack_frames.last_mut().unwrap()
     }
 
+    /// Pop the top of the stack into the current stack frame's value.
     pub fn write_frame_value(&mut self) {
-        // TODO: lots of duplicated code here because the helper functions must
-        // borrow the entire struct. There's gotta be a better way to do this
-        self.stack_frames.push(StackFrame {
-            frame_value: self.stack.last().unwrap().clone(),
-        })
+        let value = self.stack.pop().unwrap();
+        self.stack_frames.last_mut().unwrap().frame_value = value;
     }
 }
@@ -2,8 +2,20 @@
 pub enum Trigger {
     WhenFlagClicked,
     WhenSpriteClicked,
+    WhenStageClicked,
     WhenKeyPressed(char),
     WhenBackdropSwitches(String),
     WhenIReceive(String),
     WhenIStartAsAClone,
+    WhenGreaterThan,
+    /// A hat block that isn't supported. Its script never runs.
+    Unsupported,
+}
+
+impl Trigger {
+    /// Edge-activated hats are checked every frame, and only start their script when their condition goes from false
+    /// to true.
+    pub fn is_edge_activated(&self) -> bool {
+        matches!(self, Trigger::WhenGreaterThan)
+    }
 }
@@ -0,0 +1,22 @@
+use crate::scalar_value::ScalarValue;
+
+#[derive(Debug)]
+pub struct Variable {
+    pub id: String,
+    pub name: String,
+    pub value: ScalarValue,
+}
+
+#[derive(Debug)]
+pub struct List {
+    pub id: String,
+    pub name: String,
+    pub contents: Vec<ScalarValue>,
+}
+
+/// A broadcast message. Only the name matters when sending one; the ID is kept so it can be saved back out.
+#[derive(Debug)]
+pub struct Broadcast {
+    pub id: String,
+    pub name: String,
+}
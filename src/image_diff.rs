@@ -0,0 +1,85 @@
+//! Comparing a rendered stage against a reference image, for regression tests. Renderers (and GPUs) don't all
+//! rasterize and blend identically, so pixels are allowed to differ by a small amount in each channel.
+
+use std::fmt;
+
+use tiny_skia::{ColorU8, Pixmap, PremultipliedColorU8};
+
+pub struct ImageDiff {
+    pub width: u32,
+    pub height: u32,
+    /// How much any channel of a pixel may differ before the pixel counts as different.
+    pub tolerance: u8,
+    /// How many pixels differ by more than the tolerance in at least one channel.
+    pub differing_pixels: usize,
+    /// The largest difference in any channel of any pixel.
+    pub max_difference: u8,
+    /// Where the images differ: differing pixels are red, and the rest are a faded copy of the expected image.
+    pub diff_image: Pixmap,
+}
+
+fn channels(pixel: PremultipliedColorU8) -> [u8; 4] {
+    let color: ColorU8 = pixel.demultiply();
+    [color.red(), color.green(), color.blue(), color.alpha()]
+}
+
+impl ImageDiff {
+    pub fn new(expected: &Pixmap, actual: &Pixmap, tolerance: u8) -> Result<Self, &'static str> {
+        if expected.width() != actual.width() || expected.height() != actual.height() {
+            return Err("images are different sizes");
+        }
+
+        let mut diff_image = Pixmap::new(expected.width(), expected.height()).unwrap();
+        let mut differing_pixels = 0;
+        let mut max_difference = 0;
+        let pixels = expected.pixels().iter().zip(actual.pixels());
+        for ((expected, actual), diff) in pixels.zip(diff_image.pixels_mut()) {
+            let expected = channels(*expected);
+            let difference = expected
+                .iter()
+                .zip(channels(*actual).iter())
+                .map(|(a, b)| a.abs_diff(*b))
+                .max()
+                .unwrap_or(0);
+            max_difference = max_difference.max(difference);
+
+            *diff = if difference > tolerance {
+                differing_pixels += 1;
+                PremultipliedColorU8::from_rgba(255, 0, 0, 255).unwrap()
+            } else {
+                let [r, g, b, _] = expected.map(|channel| 191 + channel / 4);
+                PremultipliedColorU8::from_rgba(r, g, b, 255).unwrap()
+            };
+        }
+
+        Ok(ImageDiff {
+            width: expected.width(),
+            height: expected.height(),
+            tolerance,
+            differing_pixels,
+            max_difference,
+            diff_image,
+        })
+    }
+
+    pub fn matches(&self) -> bool {
+        self.differing_pixels == 0
+    }
+}
+
+impl fmt::Display for ImageDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.matches() {
+            write!(f, "Images match")?;
+        } else {
+            write!(
+                f,
+                "{} of {} pixels differ by more than {}",
+                self.differing_pixels,
+                self.width as usize * self.height as usize,
+                self.tolerance
+            )?;
+        }
+        writeln!(f, " (largest difference is {})", self.max_difference)
+    }
+}
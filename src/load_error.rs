@@ -0,0 +1,136 @@
+use std::{error::Error, fmt};
+
+/// Why a project couldn't be loaded, and where in the project the problem is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadError {
+    pub message: &'static str,
+    pub location: Box<LoadLocation>,
+}
+
+/// Where in a project a loading error happened. Each part is only known if the error happened inside it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadLocation {
+    /// Name of the sprite or stage being loaded.
+    pub target: Option<String>,
+    pub block_id: Option<String>,
+    pub opcode: Option<String>,
+    /// Name of the block input or field being loaded.
+    pub input: Option<String>,
+    /// File name of the costume or sound being loaded.
+    pub asset: Option<String>,
+    /// ID of the extension being checked.
+    pub extension: Option<String>,
+}
+
+impl LoadError {
+    pub fn new(message: &'static str) -> Self {
+        LoadError {
+            message,
+            location: Box::default(),
+        }
+    }
+}
+
+impl From<&'static str> for LoadError {
+    fn from(message: &'static str) -> Self {
+        LoadError::new(message)
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        let LoadLocation {
+            target,
+            block_id,
+            opcode,
+            input,
+            asset,
+            extension,
+        } = &*self.location;
+
+        let mut location = vec![];
+        if let Some(target) = target {
+            location.push(format!("target \"{}\"", target));
+        }
+        match (block_id, opcode) {
+            (Some(block_id), Some(opcode)) => {
+                location.push(format!("block \"{}\" ({})", block_id, opcode))
+            }
+            (Some(block_id), None) => location.push(format!("block \"{}\"", block_id)),
+            (None, Some(opcode)) => location.push(format!("block {}", opcode)),
+            (None, None) => {}
+        }
+        if let Some(input) = input {
+            location.push(format!("input \"{}\"", input));
+        }
+        if let Some(asset) = asset {
+            location.push(format!("asset \"{}\"", asset));
+        }
+        if let Some(extension) = extension {
+            location.push(format!("extension \"{}\"", extension));
+        }
+
+        if !location.is_empty() {
+            write!(f, " (in {})", location.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for LoadError {}
+
+/// Adds location info to a loading error as it's passed up. Errors are tagged from the inside out, so the innermost
+/// (most specific) location is kept if it's already set.
+pub trait LoadContext<T> {
+    fn in_target(self, name: &str) -> Result<T, LoadError>;
+    fn in_block(self, block_id: &str, opcode: Option<&str>) -> Result<T, LoadError>;
+    fn in_input(self, name: &str) -> Result<T, LoadError>;
+    fn in_asset(self, name: &str) -> Result<T, LoadError>;
+    fn in_extension(self, id: &str) -> Result<T, LoadError>;
+}
+
+impl<T, E: Into<LoadError>> LoadContext<T> for Result<T, E> {
+    fn in_target(self, name: &str) -> Result<T, LoadError> {
+        self.map_err(|err| {
+            let mut err = err.into();
+            err.location.target.get_or_insert_with(|| name.to_string());
+            err
+        })
+    }
+
+    fn in_block(self, block_id: &str, opcode: Option<&str>) -> Result<T, LoadError> {
+        self.map_err(|err| {
+            let mut err = err.into();
+            if err.location.block_id.is_none() {
+                err.location.block_id = Some(block_id.to_string());
+                err.location.opcode = opcode.map(String::from);
+            }
+            err
+        })
+    }
+
+    fn in_input(self, name: &str) -> Result<T, LoadError> {
+        self.map_err(|err| {
+            let mut err = err.into();
+            err.location.input.get_or_insert_with(|| name.to_string());
+            err
+        })
+    }
+
+    fn in_asset(self, name: &str) -> Result<T, LoadError> {
+        self.map_err(|err| {
+            let mut err = err.into();
+            err.location.asset.get_or_insert_with(|| name.to_string());
+            err
+        })
+    }
+
+    fn in_extension(self, id: &str) -> Result<T, LoadError> {
+        self.map_err(|err| {
+            let mut err = err.into();
+            err.location.extension.get_or_insert_with(|| id.to_string());
+            err
+        })
+    }
+}
@@ -1,46 +1,413 @@
+pub mod audio;
 pub mod engine;
 pub mod renderer;
 
 pub mod data;
 
 pub mod blocks;
+pub mod compatibility;
 pub mod compile;
 pub mod deserialize;
+pub mod image_diff;
+pub mod load_error;
+pub mod recording;
 pub mod runtime;
+pub mod sb1;
+pub mod sb2;
 pub mod scalar_value;
+pub mod serialize;
+pub mod source;
+pub mod sprite3;
 
-use crate::engine::{engine_data::EngineData, trigger::Trigger};
+use crate::{
+    audio::{
+        midi::write_midi,
+        output::{BufferOutput, NullOutput},
+        wav::write_wav,
+    },
+    compatibility::CompatibilityReport,
+    data::asset_store::{AssetLoading, AssetVerification},
+    deserialize::{ExtensionCheck, LoadOptions},
+    engine::{engine_data::EngineData, project::Project},
+    image_diff::ImageDiff,
+    recording::{FrameRecorder, PngSequence, Y4mWriter},
+    source::{zip_from_bytes, ProjectDirectory, ProjectFile},
+};
 
-use renderer::renderer::Renderer;
-use runtime::Runtime;
+use renderer::{
+    headless::HeadlessRenderer, renderer::Renderer, software_renderer::SoftwareRenderer,
+    wgpu_renderer::WgpuRenderer,
+};
+use runtime::{Runtime, FIXED_STEPS_PER_SECOND};
 use std::{
     cell::RefCell,
     error::Error,
     fs,
+    io::BufWriter,
+    path::{Path, PathBuf},
     time::{Duration, Instant},
 };
 use winit::{
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, MouseButton, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     platform::run_return::EventLoopExtRunReturn,
     window::Window,
 };
-use zip;
+
+/// Sample rate used when rendering audio to a file.
+const RENDER_SAMPLE_RATE: u32 = 44100;
+
+/// How long a headless run without a set number of ticks can go on for: ten minutes of project time. Scripts with
+/// forever loops, or that ask a question nobody is there to answer, would otherwise never finish.
+const MAX_HEADLESS_TICKS: u64 = (10.0 * 60.0 * FIXED_STEPS_PER_SECOND) as u64;
+
+struct Options {
+    /// An sb3, sb2, or sb file, an unpacked project directory, or a bare project.json.
+    project_path: PathBuf,
+    /// Where a bare project.json's assets are. Defaults to the folder it's in.
+    assets_path: Option<PathBuf>,
+    /// `.sprite3` files to add to the project after loading it.
+    add_sprites: Vec<PathBuf>,
+    /// Run headless and write the project's audio to this WAV file instead of opening a window.
+    render_audio: Option<PathBuf>,
+    /// Run headless and write the music extension's notes to this MIDI file instead of opening a window.
+    export_midi: Option<PathBuf>,
+    /// Print which of the project's blocks aren't supported, instead of running it.
+    compat_report: bool,
+    /// Run without a window or GPU, until every script finishes (or for `ticks` ticks). Runs that never finish are
+    /// stopped after `MAX_HEADLESS_TICKS`.
+    headless: bool,
+    /// Draw the stage on the CPU when headless, instead of only keeping track of what's on it. Implies `headless`.
+    software_render: bool,
+    /// Run headless and save the project to this sb3 file, in the state it's in once it stops.
+    save: Option<PathBuf>,
+    /// Run headless and save a PNG of the stage to this file once it stops. Implies `software_render`.
+    screenshot: Option<PathBuf>,
+    /// Size of the screenshot, and of recorded frames, relative to the stage.
+    screenshot_scale: f32,
+    /// Run headless and save the stage after every tick, as a Y4M video if this ends in `.y4m` and otherwise as a
+    /// directory of PNGs. Implies `software_render`.
+    record: Option<PathBuf>,
+    /// Run headless and compare the stage once it stops against this PNG, failing if they differ. Implies
+    /// `software_render`.
+    compare_to: Option<PathBuf>,
+    /// How much each channel of a pixel may differ from the reference image.
+    tolerance: u8,
+    /// Where to save an image showing which pixels differ from the reference image.
+    diff_image: Option<PathBuf>,
+    /// How many ticks to run for when headless. Implies `headless`.
+    ticks: Option<u64>,
+    /// How assets and extensions are checked, and when assets are decoded.
+    load_options: LoadOptions,
+}
+
+fn usage(program: &str) -> String {
+    format!(
+        "Usage: {} <filename> [--compat-report] [--headless] [--software-render] [--strict-assets] [--strict-extensions] [--lazy-assets] [--assets <dir>] [--add-sprite <sprite.sprite3>]... [--render-audio <out.wav>] [--export-midi <out.mid>] [--save <out.sb3>] [--screenshot <out.png>] [--screenshot-scale <s>] [--record <dir | out.y4m>] [--compare-to <expected.png> [--tolerance <n>] [--diff-image <diff.png>]] [--ticks <n> | --seconds <s>]",
+        program
+    )
+}
+
+fn parse_args() -> Result<Options, String> {
+    let args: Vec<String> = std::env::args().collect();
+    let program = args.first().map_or("tabi", String::as_str);
+
+    let mut project_path = None;
+    let mut assets_path = None;
+    let mut add_sprites = vec![];
+    let mut render_audio = None;
+    let mut export_midi = None;
+    let mut save = None;
+    let mut screenshot = None;
+    let mut screenshot_scale = 1.0;
+    let mut record = None;
+    let mut compare_to = None;
+    let mut tolerance = 0;
+    let mut diff_image = None;
+    let mut compat_report = false;
+    let mut headless = false;
+    let mut software_render = false;
+    let mut ticks = None;
+    let mut load_options = LoadOptions::default();
+
+    let mut args_iter = args.iter().skip(1);
+    while let Some(arg) = args_iter.next() {
+        let mut value = || {
+            args_iter
+                .next()
+                .ok_or_else(|| format!("{} needs a value\n{}", arg, usage(program)))
+        };
+        match arg.as_str() {
+            "--assets" => assets_path = Some(PathBuf::from(value()?)),
+            "--add-sprite" => add_sprites.push(PathBuf::from(value()?)),
+            "--render-audio" => render_audio = Some(PathBuf::from(value()?)),
+            "--export-midi" => export_midi = Some(PathBuf::from(value()?)),
+            "--compat-report" => compat_report = true,
+            "--headless" => headless = true,
+            "--software-render" => software_render = true,
+            "--strict-assets" => load_options.asset_verification = AssetVerification::Strict,
+            "--strict-extensions" => load_options.extension_check = ExtensionCheck::Strict,
+            "--lazy-assets" => load_options.asset_loading = AssetLoading::Lazy,
+            "--save" => save = Some(PathBuf::from(value()?)),
+            "--screenshot" => screenshot = Some(PathBuf::from(value()?)),
+            "--screenshot-scale" => {
+                screenshot_scale = value()?
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|scale| scale.is_finite() && *scale > 0.0)
+                    .ok_or_else(|| "--screenshot-scale must be a positive number".to_string())?
+            }
+            "--record" => record = Some(PathBuf::from(value()?)),
+            "--compare-to" => compare_to = Some(PathBuf::from(value()?)),
+            "--tolerance" => {
+                tolerance = value()?
+                    .parse::<u8>()
+                    .map_err(|_| "--tolerance must be a whole number from 0 to 255".to_string())?
+            }
+            "--diff-image" => diff_image = Some(PathBuf::from(value()?)),
+            "--ticks" => {
+                ticks = Some(
+                    value()?
+                        .parse::<u64>()
+                        .map_err(|_| "--ticks must be a whole number".to_string())?,
+                )
+            }
+            "--seconds" => {
+                let seconds = value()?
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|seconds| *seconds >= 0.0)
+                    .ok_or_else(|| "--seconds must be a non-negative number".to_string())?;
+                ticks = Some((seconds * 1000.0 / Runtime::fixed_step_msecs(1)).round() as u64);
+            }
+            _ if project_path.is_none() && !arg.starts_with("--") => {
+                project_path = Some(PathBuf::from(arg))
+            }
+            _ => return Err(usage(program)),
+        }
+    }
+
+    let project_path = project_path.ok_or_else(|| usage(program))?;
+    if (render_audio.is_some()
+        || export_midi.is_some()
+        || save.is_some()
+        || screenshot.is_some()
+        || record.is_some()
+        || compare_to.is_some())
+        && ticks.is_none()
+        && !headless
+    {
+        return Err(format!(
+            "--render-audio, --export-midi, --save, --screenshot, --record, and --compare-to need --ticks, --seconds, or --headless\n{}",
+            usage(program)
+        ));
+    }
+    if diff_image.is_some() && compare_to.is_none() {
+        return Err(format!(
+            "--diff-image needs --compare-to\n{}",
+            usage(program)
+        ));
+    }
+    // Screenshots and recordings need a renderer that actually draws.
+    let software_render =
+        software_render || screenshot.is_some() || record.is_some() || compare_to.is_some();
+
+    Ok(Options {
+        project_path,
+        assets_path,
+        add_sprites,
+        render_audio,
+        export_midi,
+        compat_report,
+        headless,
+        software_render,
+        save,
+        screenshot,
+        screenshot_scale,
+        record,
+        compare_to,
+        tolerance,
+        diff_image,
+        ticks,
+        load_options,
+    })
+}
+
+/// Load a project: an unpacked directory, a bare project.json, or a file that's either a zip archive (sb3 or sb2) or
+/// a Scratch 1.x project. Then add any extra sprites to it.
+fn load_project(
+    options: &Options,
+    eng_data: &EngineData,
+    renderer: &mut dyn Renderer,
+) -> Result<Project, Box<dyn Error>> {
+    let path = &options.project_path;
+    let mut project = if path.is_dir() {
+        let mut source = ProjectDirectory { path: path.clone() };
+        deserialize::deserialize_project(&mut source, &options.load_options, eng_data, renderer)?
+    } else if path.extension().is_some_and(|ext| ext == "json") {
+        let assets_path = match &options.assets_path {
+            Some(assets_path) => assets_path.clone(),
+            None => path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+        };
+        let mut source = ProjectFile {
+            json_path: path.clone(),
+            assets_path,
+        };
+        deserialize::deserialize_project(&mut source, &options.load_options, eng_data, renderer)?
+    } else {
+        load_project_file(&fs::read(path)?, &options.load_options, eng_data, renderer)?
+    };
+
+    for sprite_path in &options.add_sprites {
+        let mut archive = zip::ZipArchive::new(fs::File::open(sprite_path)?)?;
+        sprite3::add_sprite_to_project(&mut archive, &mut project, eng_data, renderer)?;
+    }
+
+    Ok(project)
+}
+
+/// Load a project file's contents, which are either a zip archive (sb3 or sb2) or a Scratch 1.x project.
+fn load_project_file(
+    data: &[u8],
+    options: &LoadOptions,
+    eng_data: &EngineData,
+    renderer: &mut dyn Renderer,
+) -> Result<Project, Box<dyn Error>> {
+    Ok(if sb1::is_sb1_project(data) {
+        let (project_json, mut assets) = sb1::convert_project(data)?;
+        deserialize::deserialize_project_json(
+            project_json,
+            &mut assets,
+            options,
+            eng_data,
+            renderer,
+        )?
+    } else {
+        deserialize::deserialize_project(&mut zip_from_bytes(data)?, options, eng_data, renderer)?
+    })
+}
+
+/// Run the project without a window or GPU: click the green flag, then step until every script has finished (giving up
+/// after `MAX_HEADLESS_TICKS`), or for exactly `options.ticks` ticks if that's set, recording the stage after each
+/// tick if asked to. Afterwards, write everything it played to a WAV and/or MIDI file, save the project as it is,
+/// and/or take a screenshot of the stage and compare it against a reference image. The clock advances one frame per
+/// tick rather than following wall time, so the output is the same on every run.
+fn run_headless(options: &Options, eng_data: &EngineData) -> Result<(), Box<dyn Error>> {
+    let renderer: Box<RefCell<dyn Renderer>> = if options.software_render {
+        Box::new(RefCell::new(SoftwareRenderer::new((480, 360))))
+    } else {
+        Box::new(RefCell::new(HeadlessRenderer::new((480, 360))))
+    };
+    let project = load_project(options, eng_data, &mut *renderer.borrow_mut())?;
+
+    let output = BufferOutput::new(RENDER_SAMPLE_RATE);
+    let mut runtime = Runtime::new(&project, eng_data, &*renderer, Box::new(output.clone()));
+    runtime.use_fixed_timestep();
+    runtime.record_music();
+    let mut recorder: Option<Box<dyn FrameRecorder>> = match &options.record {
+        Some(path)
+            if path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("y4m")) =>
+        {
+            Some(Box::new(Y4mWriter::new(
+                BufWriter::new(fs::File::create(path)?),
+                FIXED_STEPS_PER_SECOND as u32,
+            )))
+        }
+        Some(path) => Some(Box::new(PngSequence::create(path.clone())?)),
+        None => None,
+    };
+
+    runtime.green_flag();
+    loop {
+        let done = match options.ticks {
+            Some(ticks) => runtime.ticks() >= ticks,
+            None if runtime.ticks() >= MAX_HEADLESS_TICKS => {
+                println!(
+                    "Stopping after {} ticks, since some scripts never finish; use --ticks or --seconds to run longer",
+                    MAX_HEADLESS_TICKS
+                );
+                true
+            }
+            None => runtime.ticks() > 0 && runtime.threads_finished(),
+        };
+        if done {
+            break;
+        }
+        runtime.step();
+        if let Some(recorder) = &mut recorder {
+            recorder.write_frame(&renderer.borrow_mut().snapshot(options.screenshot_scale)?)?;
+        }
+    }
+    if let Some(recorder) = &mut recorder {
+        recorder.finish()?;
+    }
+    let ticks = runtime.ticks();
+    runtime.render_audio_until(Runtime::fixed_step_msecs(ticks));
+    println!("Ran for {} ticks", ticks);
+
+    if let Some(audio_path) = &options.render_audio {
+        let mut out_file = BufWriter::new(fs::File::create(audio_path)?);
+        write_wav(&mut out_file, &output.take_samples(), RENDER_SAMPLE_RATE)?;
+    }
+    if let Some(midi_path) = &options.export_midi {
+        let mut out_file = BufWriter::new(fs::File::create(midi_path)?);
+        write_midi(&mut out_file, runtime.music_events())?;
+    }
+    if let Some(save_path) = &options.save {
+        serialize::serialize_runtime(&runtime, fs::File::create(save_path)?)?;
+    }
+    if options.screenshot.is_some() || options.compare_to.is_some() {
+        let snapshot = renderer.borrow_mut().snapshot(options.screenshot_scale)?;
+        if let Some(screenshot_path) = &options.screenshot {
+            fs::write(screenshot_path, snapshot.encode_png()?)?;
+        }
+        if let Some(expected_path) = &options.compare_to {
+            let expected = tiny_skia::Pixmap::decode_png(&fs::read(expected_path)?)?;
+            let diff = ImageDiff::new(&expected, &snapshot, options.tolerance)?;
+            print!("{}", diff);
+            if let Some(diff_path) = &options.diff_image {
+                fs::write(diff_path, diff.diff_image.encode_png()?)?;
+            }
+            if !diff.matches() {
+                return Err("the stage doesn't match the reference image".into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Load the project without running it, and print its compatibility report.
+fn print_compatibility_report(
+    options: &Options,
+    eng_data: &EngineData,
+) -> Result<(), Box<dyn Error>> {
+    let mut renderer = HeadlessRenderer::new((480, 360));
+    let project = load_project(options, eng_data, &mut renderer)?;
+    print!("{}", CompatibilityReport::new(&project, eng_data));
+    Ok(())
+}
 
 fn run() -> Result<(), Box<dyn Error>> {
     env_logger::init();
 
     let eng_data = EngineData::new();
 
-    let args: Vec<_> = std::env::args().collect();
-    if args.len() < 2 {
-        println!("Usage: {} <filename>", args[0]);
-        std::process::exit(1);
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(message) => {
+            println!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    if options.compat_report {
+        return print_compatibility_report(&options, &eng_data);
+    }
+    if options.headless || options.software_render || options.ticks.is_some() {
+        return run_headless(&options, &eng_data);
     }
-    let fname = std::path::Path::new(&args[1]);
-    let file = fs::File::open(&fname).unwrap();
-
-    let mut archive = zip::ZipArchive::new(file).unwrap();
 
     println!("{:?}", eng_data.block_specs);
 
@@ -53,20 +420,25 @@ fn run() -> Result<(), Box<dyn Error>> {
     });
     let size = window.inner_size();
 
-    let renderer = RefCell::new(Renderer::with_window(
+    let renderer = RefCell::new(WgpuRenderer::with_window(
         &window,
         (size.width, size.height),
         (480, 360),
     ));
 
-    let project =
-        deserialize::deserialize_project(&mut archive, &eng_data, &mut renderer.borrow_mut())?;
+    let project = load_project(&options, &eng_data, &mut *renderer.borrow_mut())?;
 
     println!("{:?}", project);
 
-    let mut runtime = Runtime::new(&project, &eng_data, &renderer);
+    let mut runtime = Runtime::new(
+        &project,
+        &eng_data,
+        &renderer,
+        Box::new(NullOutput::new(44100)),
+    );
+    runtime.resize((size.width, size.height));
 
-    runtime.start_hats(&Trigger::WhenFlagClicked);
+    runtime.green_flag();
 
     let mut last_update_inst = Instant::now();
 
@@ -93,6 +465,23 @@ fn run() -> Result<(), Box<dyn Error>> {
                 event: WindowEvent::Resized(size),
                 ..
             } => runtime.resize((size.width, size.height)),
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } => runtime.mouse_move((position.x, position.y)),
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    },
+                ..
+            } => runtime.mouse_button(state == ElementState::Pressed),
+            Event::WindowEvent {
+                event: WindowEvent::ReceivedCharacter(c),
+                ..
+            } => runtime.post_character(c),
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
@@ -105,7 +494,10 @@ fn run() -> Result<(), Box<dyn Error>> {
 }
 
 fn main() {
-    run().ok();
+    if let Err(err) = run() {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
 
     std::process::exit(0);
 }
@@ -0,0 +1,114 @@
+//! Recording the stage one frame per tick, as a folder of numbered PNGs or as an uncompressed Y4M video. Frames follow
+//! the runtime's ticks rather than wall time, so recording N ticks always gives N frames, however long they take to
+//! render.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use tiny_skia::Pixmap;
+
+pub trait FrameRecorder {
+    fn write_frame(&mut self, frame: &Pixmap) -> io::Result<()>;
+    /// Write out anything that's still buffered, once the last frame has been written.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Each frame as its own PNG, named by the tick it was drawn on: `frame_000001.png` is the stage after one tick, the
+/// same as a screenshot taken with `--ticks 1`.
+pub struct PngSequence {
+    dir: PathBuf,
+    frames_written: u64,
+}
+
+impl PngSequence {
+    pub fn create(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(PngSequence {
+            dir,
+            frames_written: 0,
+        })
+    }
+}
+
+impl FrameRecorder for PngSequence {
+    fn write_frame(&mut self, frame: &Pixmap) -> io::Result<()> {
+        self.frames_written += 1;
+        let png = frame.encode_png().map_err(io::Error::other)?;
+        fs::write(
+            self.dir
+                .join(format!("frame_{:06}.png", self.frames_written)),
+            png,
+        )
+    }
+}
+
+/// A YUV4MPEG2 stream. Chroma isn't subsampled (4:4:4), so the stage can be any size, and colors are converted with
+/// BT.601 at limited range, which is what players assume when a Y4M file doesn't say.
+pub struct Y4mWriter<W: Write> {
+    writer: W,
+    frames_per_second: u32,
+    /// Set by the first frame, since the header needs it. Every other frame must be the same size.
+    size: Option<(u32, u32)>,
+}
+
+fn rgb_to_yuv(r: u8, g: u8, b: u8) -> [u8; 3] {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let y = 16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0;
+    let u = 128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0;
+    let v = 128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0;
+    [y.round() as u8, u.round() as u8, v.round() as u8]
+}
+
+impl<W: Write> Y4mWriter<W> {
+    pub fn new(writer: W, frames_per_second: u32) -> Self {
+        Y4mWriter {
+            writer,
+            frames_per_second,
+            size: None,
+        }
+    }
+}
+
+impl<W: Write> FrameRecorder for Y4mWriter<W> {
+    fn write_frame(&mut self, frame: &Pixmap) -> io::Result<()> {
+        let size = (frame.width(), frame.height());
+        match self.size {
+            None => {
+                writeln!(
+                    self.writer,
+                    "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                    size.0, size.1, self.frames_per_second
+                )?;
+                self.size = Some(size);
+            }
+            Some(first_size) if first_size != size => {
+                return Err(io::Error::other(
+                    "frame is a different size than the first one",
+                ));
+            }
+            Some(_) => {}
+        }
+
+        // The stage is opaque, so premultiplied colors are the real ones.
+        let yuv: Vec<[u8; 3]> = frame
+            .pixels()
+            .iter()
+            .map(|pixel| rgb_to_yuv(pixel.red(), pixel.green(), pixel.blue()))
+            .collect();
+        self.writer.write_all(b"FRAME\n")?;
+        for plane in 0..3 {
+            let plane: Vec<u8> = yuv.iter().map(|pixel| pixel[plane]).collect();
+            self.writer.write_all(&plane)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
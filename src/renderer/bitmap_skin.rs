@@ -1 +1,87 @@
+use glam::Vec2;
+use tiny_skia::{Pixmap, PremultipliedColorU8};
 
+use super::{image_skin::ImageSkin, skin::Skin, wgpu_renderer::GpuState};
+
+/// Decode a PNG or JPEG costume into premultiplied RGBA.
+pub fn decode_bitmap(data: &[u8], is_jpeg: bool) -> Result<Pixmap, &'static str> {
+    if !is_jpeg {
+        return Pixmap::decode_png(data).map_err(|_| "Could not decode PNG");
+    }
+
+    let mut decoder = jpeg_decoder::Decoder::new(data);
+    let pixels = decoder.decode().map_err(|_| "Could not decode JPEG")?;
+    let info = decoder.info().ok_or("Could not decode JPEG")?;
+    let mut pixmap =
+        Pixmap::new(info.width as u32, info.height as u32).ok_or("JPEG has no pixels")?;
+    let rgb = |idx: usize| -> [u8; 3] {
+        match info.pixel_format {
+            jpeg_decoder::PixelFormat::L8 => [pixels[idx]; 3],
+            // Big-endian; keep the high byte.
+            jpeg_decoder::PixelFormat::L16 => [pixels[idx * 2]; 3],
+            jpeg_decoder::PixelFormat::RGB24 => {
+                [pixels[idx * 3], pixels[idx * 3 + 1], pixels[idx * 3 + 2]]
+            }
+            jpeg_decoder::PixelFormat::CMYK32 => {
+                let cmyk = &pixels[idx * 4..idx * 4 + 4];
+                let channel = |c: u8| ((255 - c) as u16 * (255 - cmyk[3]) as u16 / 255) as u8;
+                [channel(cmyk[0]), channel(cmyk[1]), channel(cmyk[2])]
+            }
+        }
+    };
+    for (idx, pixel) in pixmap.pixels_mut().iter_mut().enumerate() {
+        let [r, g, b] = rgb(idx);
+        *pixel = PremultipliedColorU8::from_rgba(r, g, b, 255).unwrap();
+    }
+
+    Ok(pixmap)
+}
+
+pub struct BitmapSkin {
+    /// The decoded image, used for hit-testing.
+    image: ImageSkin,
+    #[allow(dead_code)]
+    texture: wgpu::Texture,
+    texture_view: wgpu::TextureView,
+}
+
+impl BitmapSkin {
+    /// `resolution` is how many bitmap pixels make up one stage pixel; the rotation center is in bitmap pixels.
+    pub(super) fn new(
+        gpu_state: &GpuState,
+        pixmap: Pixmap,
+        resolution: f64,
+        rotation_center: Vec2,
+    ) -> Self {
+        let image = ImageSkin::from_bitmap(pixmap, resolution, rotation_center);
+        let (texture, texture_view) = gpu_state.create_texture(image.pixmap(), "BitmapSkin");
+
+        BitmapSkin {
+            image,
+            texture,
+            texture_view,
+        }
+    }
+}
+
+impl Skin for BitmapSkin {
+    fn get_size(&self) -> Vec2 {
+        self.image.get_size()
+    }
+
+    fn get_rotation_center(&self) -> Vec2 {
+        self.image.get_rotation_center()
+    }
+
+    fn get_texture(&mut self, _scale: f32) -> Option<&wgpu::TextureView> {
+        Some(&self.texture_view)
+    }
+
+    fn get_pixmap(&self) -> Option<&tiny_skia::Pixmap> {
+        self.image.get_pixmap()
+    }
+
+    fn is_touching(&self, tex_coord: Vec2) -> bool {
+        self.image.is_touching(tex_coord)
+    }
+}
@@ -1,18 +1,24 @@
+use std::rc::Rc;
+
 use glam::Vec2;
 use wgpu::TextureView;
 
 use super::skin::Skin;
 
+/// A skin with nothing in it: it draws as fully transparent and never touches anything.
 pub struct BlankSkin {
     size: Vec2,
     rotation_center: Vec2,
+    /// A single transparent pixel, shared between all blank skins. Only the wgpu renderer needs one.
+    texture_view: Option<Rc<TextureView>>,
 }
 
 impl BlankSkin {
-    pub fn new(size: Vec2, rotation_center: Vec2) -> Self {
+    pub fn new(size: Vec2, rotation_center: Vec2, texture_view: Option<Rc<TextureView>>) -> Self {
         BlankSkin {
             size,
             rotation_center,
+            texture_view,
         }
     }
 }
@@ -26,7 +32,11 @@ impl Skin for BlankSkin {
         self.rotation_center
     }
 
-    fn get_texture(&mut self, _scale: f32) -> &TextureView {
-        unimplemented!()
+    fn get_texture(&mut self, _scale: f32) -> Option<&TextureView> {
+        self.texture_view.as_deref()
+    }
+
+    fn is_touching(&self, _tex_coord: Vec2) -> bool {
+        false
     }
 }
@@ -1,130 +1,63 @@
 use std::cell::RefCell;
+use std::mem;
 use std::rc::Rc;
 
-use bytemuck::{Pod, Zeroable};
 use glam::{Affine2, Vec2};
 
-use super::common::RendererState;
-use super::renderer::GpuState;
+use super::rect::Rect;
 use super::skin::Skin;
 
-pub(super) struct DrawableRendererState {
-    pub bind_group_layout: wgpu::BindGroupLayout,
-}
-
-impl RendererState for DrawableRendererState {
-    fn init(device: &wgpu::Device) -> Self {
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Drawable"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: wgpu::BufferSize::new(24),
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-        });
-
-        DrawableRendererState { bind_group_layout }
-    }
-}
-
-#[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable)]
-struct DrawableUniforms {
-    matrix: [f32; 6],
-}
-
 pub struct Drawable {
     matrix: Affine2,
+    inverse_matrix: Affine2,
     skin: Rc<RefCell<dyn Skin>>,
     position: Vec2,
     rotation: f32,
     scale: Vec2,
     matrix_dirty: bool,
     inverse_dirty: bool,
-    bind_group_dirty: bool,
-
-    uniform_buf: wgpu::Buffer,
-    pub bind_group: wgpu::BindGroup,
+    /// Whether the transform has changed since the renderer last took the changes.
+    transform_changed: bool,
+    /// Whether the skin has changed (or finished loading) since the renderer last took the changes.
+    skin_changed: bool,
+    /// Whether the skin had loaded when the matrix was last updated.
+    skin_loaded: bool,
+    pub visible: bool,
 }
 
 impl Drawable {
-    pub(super) fn new(
-        skin: Rc<RefCell<dyn Skin>>,
-        gpu_state: &GpuState,
-        state: &DrawableRendererState,
-    ) -> Self {
-        let uniform_buf = gpu_state.device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Drawable.uniform_buf"),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            size: 24,
-            mapped_at_creation: false,
-        });
-
-        let bind_group = gpu_state
-            .device
-            .create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &state.bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: uniform_buf.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::TextureView(
-                            skin.borrow_mut().get_texture(1f32),
-                        ),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::Sampler(&gpu_state.sampler_linear),
-                    },
-                ],
-                label: None,
-            });
+    pub(super) fn new(skin: Rc<RefCell<dyn Skin>>) -> Self {
+        let skin_loaded = skin.borrow().is_loaded();
 
         Self {
             matrix: Affine2::default(),
+            inverse_matrix: Affine2::default(),
             skin,
             position: Vec2::default(),
             rotation: 0f32,
             scale: Vec2::new(1f32, 1f32),
             matrix_dirty: true,
             inverse_dirty: true,
-            bind_group_dirty: true,
-
-            uniform_buf,
-            bind_group,
+            transform_changed: true,
+            skin_changed: true,
+            skin_loaded,
+            visible: true,
         }
     }
 
     fn set_matrix_dirty(&mut self) {
         self.matrix_dirty = true;
         self.inverse_dirty = true;
-        self.bind_group_dirty = true;
+        self.transform_changed = true;
+    }
+
+    /// A lazily loaded skin gets a new size and texture once it's loaded.
+    fn check_skin_loaded(&mut self) {
+        if !self.skin_loaded && self.skin.borrow().is_loaded() {
+            self.skin_loaded = true;
+            self.skin_changed = true;
+            self.set_matrix_dirty();
+        }
     }
 
     // TODO
@@ -167,32 +100,67 @@ impl Drawable {
     }
 
     pub fn get_matrix(&mut self) -> Affine2 {
+        self.check_skin_loaded();
         if self.matrix_dirty {
             self.calculate_transform();
         }
         self.matrix
     }
 
+    fn get_inverse_matrix(&mut self) -> Affine2 {
+        self.check_skin_loaded();
+        if self.inverse_dirty {
+            self.inverse_matrix = self.get_matrix().inverse();
+            self.inverse_dirty = false;
+        }
+        self.inverse_matrix
+    }
+
+    /// Whether the drawable's skin is opaque at the given point, in stage coordinates.
+    pub fn is_touching(&mut self, point: Vec2) -> bool {
+        // Transform from stage coordinates into the unit quad, then into texture coordinates.
+        let local = self.get_inverse_matrix().transform_point2(point);
+        let tex_coord = Vec2::new(local.x + 0.5, 0.5 - local.y);
+        self.skin.borrow().is_touching(tex_coord)
+    }
+
     pub fn get_skin(&self) -> Rc<RefCell<dyn Skin>> {
         Rc::clone(&self.skin)
     }
 
     pub fn set_skin(&mut self, skin: Rc<RefCell<dyn Skin>>) {
+        self.skin_loaded = skin.borrow().is_loaded();
         self.skin = skin;
+        self.skin_changed = true;
         self.set_matrix_dirty();
     }
 
-    pub(super) fn update_bind_group(&mut self, gpu_state: &GpuState) {
-        if self.bind_group_dirty {
-            let mat = self.get_matrix();
-            gpu_state.queue.write_buffer(
-                &self.uniform_buf,
-                0,
-                bytemuck::bytes_of(&DrawableUniforms {
-                    matrix: mat.to_cols_array(),
-                }),
-            );
-            self.bind_group_dirty = false;
+    /// The axis-aligned bounding box of this drawable, in stage coordinates.
+    pub fn get_bounds(&mut self) -> Rect {
+        let matrix = self.get_matrix();
+        let corners = [
+            matrix.transform_point2(Vec2::new(-0.5, -0.5)),
+            matrix.transform_point2(Vec2::new(0.5, -0.5)),
+            matrix.transform_point2(Vec2::new(-0.5, 0.5)),
+            matrix.transform_point2(Vec2::new(0.5, 0.5)),
+        ];
+        let min = corners.iter().fold(corners[0], |acc, c| acc.min(*c));
+        let max = corners.iter().fold(corners[0], |acc, c| acc.max(*c));
+        Rect {
+            left: min.x,
+            right: max.x,
+            bottom: min.y,
+            top: max.y,
         }
     }
+
+    /// Whether the skin and the transform have changed since this was last called. Renderers that keep their own
+    /// per-drawable state (like GPU buffers) use this to know what to update.
+    pub(super) fn take_changes(&mut self) -> (bool, bool) {
+        self.check_skin_loaded();
+        (
+            mem::take(&mut self.skin_changed),
+            mem::take(&mut self.transform_changed),
+        )
+    }
 }
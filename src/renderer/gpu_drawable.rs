@@ -0,0 +1,151 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use bytemuck::{Pod, Zeroable};
+
+use super::common::RendererState;
+use super::drawable::Drawable;
+use super::skin::Skin;
+use super::wgpu_renderer::GpuState;
+
+pub(super) struct DrawableRendererState {
+    pub bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl RendererState for DrawableRendererState {
+    fn init(device: &wgpu::Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Drawable"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(32),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        DrawableRendererState { bind_group_layout }
+    }
+}
+
+/// The 2x2 part of the matrix is passed as a vec4 rather than a mat2x2, since some backends pad a uniform mat2x2's
+/// columns out to 16 bytes.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct DrawableUniforms {
+    matrix: [f32; 6],
+    _padding: [f32; 2],
+}
+
+fn create_bind_group(
+    skin: &Rc<RefCell<dyn Skin>>,
+    uniform_buf: &wgpu::Buffer,
+    gpu_state: &GpuState,
+    state: &DrawableRendererState,
+) -> wgpu::BindGroup {
+    gpu_state
+        .device
+        .create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &state.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        skin.borrow_mut()
+                            .get_texture(1f32)
+                            .expect("Skin has no texture--was it created by a different renderer?"),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&gpu_state.sampler_linear),
+                },
+            ],
+            label: None,
+        })
+}
+
+/// The GPU resources for drawing a drawable: its transform, uploaded as a uniform, and its skin's texture.
+pub(super) struct GpuDrawable {
+    uniform_buf: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl GpuDrawable {
+    pub(super) fn new(
+        drawable: &mut Drawable,
+        gpu_state: &GpuState,
+        state: &DrawableRendererState,
+    ) -> Self {
+        let uniform_buf = gpu_state.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Drawable.uniform_buf"),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            size: 32,
+            mapped_at_creation: false,
+        });
+        let bind_group = create_bind_group(&drawable.get_skin(), &uniform_buf, gpu_state, state);
+
+        let mut gpu_drawable = GpuDrawable {
+            uniform_buf,
+            bind_group,
+        };
+        drawable.take_changes();
+        gpu_drawable.write_uniforms(drawable, gpu_state);
+        gpu_drawable
+    }
+
+    fn write_uniforms(&mut self, drawable: &mut Drawable, gpu_state: &GpuState) {
+        let mat = drawable.get_matrix();
+        gpu_state.queue.write_buffer(
+            &self.uniform_buf,
+            0,
+            bytemuck::bytes_of(&DrawableUniforms {
+                matrix: mat.to_cols_array(),
+                _padding: [0.0; 2],
+            }),
+        );
+    }
+
+    /// Bring the GPU resources up to date with any changes to the drawable since it was last drawn.
+    pub(super) fn update(
+        &mut self,
+        drawable: &mut Drawable,
+        gpu_state: &GpuState,
+        state: &DrawableRendererState,
+    ) {
+        let (skin_changed, transform_changed) = drawable.take_changes();
+        if skin_changed {
+            self.bind_group =
+                create_bind_group(&drawable.get_skin(), &self.uniform_buf, gpu_state, state);
+        }
+        if transform_changed {
+            self.write_uniforms(drawable, gpu_state);
+        }
+    }
+}
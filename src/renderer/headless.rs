@@ -0,0 +1,74 @@
+//! A renderer that never draws anything, for running projects without a window or GPU. Skins are rasterized on the
+//! CPU so that sizes, bounds, and hit-testing still behave as they do on screen.
+
+use std::cell::RefCell;
+
+use glam::Vec2;
+
+use super::{
+    blank_skin::BlankSkin, image_skin::ImageSkin, renderer::Renderer, scene::Scene, skin::Skin,
+};
+
+pub struct HeadlessRenderer {
+    scene: Scene,
+}
+
+impl HeadlessRenderer {
+    pub fn new(stage_size: (u32, u32)) -> Self {
+        HeadlessRenderer {
+            scene: Scene::new(stage_size),
+        }
+    }
+}
+
+impl Renderer for HeadlessRenderer {
+    fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    fn scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+    /// Nothing is drawn, but skins that would be on screen are still loaded first.
+    fn draw(&mut self) {
+        self.scene.request_visible_skins();
+    }
+
+    fn resize(&mut self, _size: (u32, u32)) {}
+
+    fn snapshot(&mut self, _scale: f32) -> Result<tiny_skia::Pixmap, &'static str> {
+        Err("the headless renderer doesn't draw the stage; use the software renderer")
+    }
+
+    fn blank_skin(&self, size: Vec2, rotation_center: Vec2) -> BlankSkin {
+        BlankSkin::new(size, rotation_center, None)
+    }
+
+    fn create_svg_skin(&self, svg_data: &[u8], rotation_center: (f64, f64)) -> Box<dyn Skin> {
+        Box::new(ImageSkin::from_svg_data(
+            svg_data,
+            Vec2::new(rotation_center.0 as f32, rotation_center.1 as f32),
+            self.scene.svg_options(),
+        ))
+    }
+
+    fn create_bitmap_skin(
+        &self,
+        pixmap: tiny_skia::Pixmap,
+        resolution: f64,
+        rotation_center: (f64, f64),
+    ) -> Box<dyn Skin> {
+        Box::new(ImageSkin::from_bitmap(
+            pixmap,
+            resolution,
+            Vec2::new(rotation_center.0 as f32, rotation_center.1 as f32),
+        ))
+    }
+
+    fn load_skin(&self, skin: &RefCell<dyn Skin>) {
+        if !skin.borrow().is_loaded() {
+            skin.borrow_mut().load(self);
+        }
+    }
+}
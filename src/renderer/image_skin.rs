@@ -0,0 +1,83 @@
+use glam::Vec2;
+use tiny_skia::Pixmap;
+
+use super::skin::Skin;
+
+/// A skin whose image is kept in memory, rasterized on the CPU. The wgpu skins use one for hit-testing, and renderers
+/// without a GPU use it as-is.
+pub struct ImageSkin {
+    /// Size in stage pixels, which differs from the pixmap's size for high-resolution bitmaps.
+    size: Vec2,
+    rotation_center: Vec2,
+    pixmap: Pixmap,
+}
+
+impl ImageSkin {
+    pub fn from_svg(rtree: &usvg::Tree, rotation_center: Vec2) -> Self {
+        let viewbox_rect = rtree.svg_node().view_box.rect;
+        let rotation_center = Vec2::new(
+            rotation_center.x - (viewbox_rect.x() as f32),
+            rotation_center.y - (viewbox_rect.y() as f32),
+        );
+        let size = Vec2::new(viewbox_rect.width() as f32, viewbox_rect.height() as f32);
+
+        let mut pixmap = Pixmap::new(size.x as u32, size.y as u32).unwrap();
+        resvg::render(
+            rtree,
+            usvg::FitTo::Original,
+            tiny_skia::Transform::default(),
+            pixmap.as_mut(),
+        )
+        .unwrap();
+
+        ImageSkin {
+            size,
+            rotation_center,
+            pixmap,
+        }
+    }
+
+    pub fn from_svg_data(svg_data: &[u8], rotation_center: Vec2, options: &usvg::Options) -> Self {
+        let rtree = usvg::Tree::from_data(svg_data, &options.to_ref()).unwrap();
+        Self::from_svg(&rtree, rotation_center)
+    }
+
+    /// `resolution` is how many bitmap pixels make up one stage pixel; the rotation center is in bitmap pixels.
+    pub fn from_bitmap(pixmap: Pixmap, resolution: f64, rotation_center: Vec2) -> Self {
+        let resolution = resolution as f32;
+        ImageSkin {
+            size: Vec2::new(pixmap.width() as f32, pixmap.height() as f32) / resolution,
+            rotation_center: rotation_center / resolution,
+            pixmap,
+        }
+    }
+
+    pub fn pixmap(&self) -> &Pixmap {
+        &self.pixmap
+    }
+}
+
+impl Skin for ImageSkin {
+    fn get_pixmap(&self) -> Option<&Pixmap> {
+        Some(&self.pixmap)
+    }
+
+    fn get_size(&self) -> Vec2 {
+        self.size
+    }
+
+    fn get_rotation_center(&self) -> Vec2 {
+        self.rotation_center
+    }
+
+    fn is_touching(&self, tex_coord: Vec2) -> bool {
+        if !(0.0..1.0).contains(&tex_coord.x) || !(0.0..1.0).contains(&tex_coord.y) {
+            return false;
+        }
+        let x = (tex_coord.x * self.pixmap.width() as f32) as u32;
+        let y = (tex_coord.y * self.pixmap.height() as f32) as u32;
+        self.pixmap
+            .pixel(x, y)
+            .is_some_and(|pixel| pixel.alpha() > 0)
+    }
+}
@@ -0,0 +1,66 @@
+use glam::Vec2;
+use wgpu::TextureView;
+
+use super::{blank_skin::BlankSkin, renderer::Renderer, skin::Skin};
+
+/// Creates a skin's real contents. Given the renderer so it can rasterize and upload the image.
+pub type SkinLoader = Box<dyn FnOnce(&dyn Renderer) -> Box<dyn Skin>>;
+
+/// A skin whose image is only decoded and uploaded when it's first needed. Until then, it's blank.
+pub struct LazySkin {
+    placeholder: BlankSkin,
+    loader: Option<SkinLoader>,
+    skin: Option<Box<dyn Skin>>,
+}
+
+impl LazySkin {
+    pub(super) fn new(placeholder: BlankSkin, loader: SkinLoader) -> Self {
+        LazySkin {
+            placeholder,
+            loader: Some(loader),
+            skin: None,
+        }
+    }
+
+    fn current(&self) -> &dyn Skin {
+        match &self.skin {
+            Some(skin) => skin.as_ref(),
+            None => &self.placeholder,
+        }
+    }
+}
+
+impl Skin for LazySkin {
+    fn get_texture(&mut self, scale: f32) -> Option<&TextureView> {
+        match &mut self.skin {
+            Some(skin) => skin.get_texture(scale),
+            None => self.placeholder.get_texture(scale),
+        }
+    }
+
+    fn get_pixmap(&self) -> Option<&tiny_skia::Pixmap> {
+        self.current().get_pixmap()
+    }
+
+    fn get_size(&self) -> Vec2 {
+        self.current().get_size()
+    }
+
+    fn get_rotation_center(&self) -> Vec2 {
+        self.current().get_rotation_center()
+    }
+
+    fn is_touching(&self, tex_coord: Vec2) -> bool {
+        self.current().is_touching(tex_coord)
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.skin.is_some()
+    }
+
+    fn load(&mut self, renderer: &dyn Renderer) {
+        if let Some(loader) = self.loader.take() {
+            self.skin = Some(loader(renderer));
+        }
+    }
+}
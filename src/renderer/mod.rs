@@ -2,6 +2,16 @@ pub mod bitmap_skin;
 pub mod blank_skin;
 pub mod common;
 pub mod drawable;
+pub mod gpu_drawable;
+pub mod headless;
+pub mod image_skin;
+pub mod lazy_skin;
+pub mod rect;
+#[allow(clippy::module_inception)]
 pub mod renderer;
+pub mod scene;
 pub mod skin;
+pub mod software_renderer;
 pub mod svg_skin;
+pub mod text_bubble;
+pub mod wgpu_renderer;
@@ -1,386 +1,179 @@
-use std::{borrow::Cow, cell::RefCell, collections::HashMap, mem, rc::Rc};
+use std::{cell::RefCell, rc::Rc};
 
-use bytemuck::{Pod, Zeroable};
-use futures::executor::block_on;
 use glam::Vec2;
-use wgpu::util::DeviceExt;
-use winit::window::Window;
 
 use super::{
     blank_skin::BlankSkin,
-    common::RendererState,
-    drawable::{Drawable, DrawableRendererState},
+    lazy_skin::{LazySkin, SkinLoader},
+    rect::Rect,
+    scene::Scene,
     skin::Skin,
-    svg_skin::SVGSkin,
+    text_bubble::{bubble_svg, prompt_svg, BubbleType},
 };
 
-const NUM_INDICES: usize = 6;
-
 #[derive(Clone, Copy, Hash, PartialEq, Eq)]
-pub struct DrawableID(usize);
-
-pub(super) struct GpuState {
-    pub surface: wgpu::Surface,
-    pub surface_config: wgpu::SurfaceConfiguration,
-    pub device: wgpu::Device,
-    pub queue: wgpu::Queue,
-    pub render_pipeline: wgpu::RenderPipeline,
-    pub vertex_buf: wgpu::Buffer,
-    pub index_buf: wgpu::Buffer,
-    pub stage_bind_group: wgpu::BindGroup,
-    pub sampler_nearest: wgpu::Sampler,
-    pub sampler_linear: wgpu::Sampler,
-}
-
-#[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable)]
-struct StageUniforms {
-    size: [f32; 2],
-}
-
-pub struct Renderer {
-    gpu_state: GpuState,
-    drawable_renderer_state: DrawableRendererState,
-    drawables: HashMap<DrawableID, Drawable>,
-    draw_list: Vec<DrawableID>,
-    skins: Vec<Rc<RefCell<dyn Skin>>>,
-    stage_size: (u32, u32),
-    next_drawable_id: usize,
+pub struct DrawableID(pub(super) usize);
+
+/// The size of a snapshot taken at the given scale, in pixels. Never zero, so there's always an image.
+pub(super) fn snapshot_size(stage_size: (u32, u32), scale: f32) -> (u32, u32) {
+    (
+        ((stage_size.0 as f32 * scale).round() as u32).max(1),
+        ((stage_size.1 as f32 * scale).round() as u32).max(1),
+    )
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable)]
-struct Vertex {
-    _pos: [f32; 2],
-    _tex_coord: [f32; 2],
-}
-
-static QUAD_VERTS: [Vertex; 4] = [
-    Vertex {
-        _pos: [-0.5, -0.5],
-        _tex_coord: [0.0, 1.0],
-    },
-    Vertex {
-        _pos: [0.5, -0.5],
-        _tex_coord: [1.0, 1.0],
-    },
-    Vertex {
-        _pos: [-0.5, 0.5],
-        _tex_coord: [0.0, 0.0],
-    },
-    Vertex {
-        _pos: [0.5, 0.5],
-        _tex_coord: [1.0, 0.0],
-    },
-];
-
-static QUAD_INDICES: [u16; NUM_INDICES] = [0, 1, 2, 1, 2, 3];
-
-impl Renderer {
-    pub fn with_window(window: &Window, size: (u32, u32), stage_size: (u32, u32)) -> Self {
-        let instance = wgpu::Instance::new(wgpu::Backends::all());
-        let surface = unsafe { instance.create_surface(window) };
-        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            // Request an adapter which can render to our surface
-            compatible_surface: Some(&surface),
-            force_fallback_adapter: false
-        }))
-        .expect("Failed to find an appropriate adapter");
-
-        // Create the logical device and command queue
-        let (device, queue) = block_on(adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                features: wgpu::Features::empty(),
-                limits: wgpu::Limits::default(),
-            },
-            None,
-        ))
-        .expect("Failed to create device");
-
-        let drawable_renderer_state = DrawableRendererState::init(&device);
-
-        let vertex_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&QUAD_VERTS),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-
-        let index_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&QUAD_INDICES),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-
-        // Load the shaders from disk
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
-        });
-
-        let stage_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Drawable"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: wgpu::BufferSize::new(8),
-                    },
-                    count: None,
-                }],
-            });
-
-        let stage_uniform_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: None,
-            contents: bytemuck::bytes_of(&StageUniforms {
-                size: [stage_size.0 as f32, stage_size.1 as f32],
-            }),
-            usage: wgpu::BufferUsages::UNIFORM,
-        });
-
-        let stage_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &stage_bind_group_layout,
-            label: Some("Stage"),
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: stage_uniform_buf.as_entire_binding(),
-            }],
-        });
-
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[
-                &stage_bind_group_layout,
-                &drawable_renderer_state.bind_group_layout,
-            ],
-            push_constant_ranges: &[],
-        });
-
-        let vertex_buffers = [wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<Vertex>() as u64,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    format: wgpu::VertexFormat::Float32x2,
-                    offset: 0,
-                    shader_location: 0,
-                },
-                wgpu::VertexAttribute {
-                    format: wgpu::VertexFormat::Float32x2,
-                    offset: (mem::size_of::<f32>() * 2) as u64,
-                    shader_location: 1,
-                },
-            ],
-        }];
-
-        let surface_format = wgpu::TextureFormat::Bgra8Unorm;
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &vertex_buffers,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_format.into(),
-                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::default(),
-                })],
-            }),
-            primitive: wgpu::PrimitiveState::default(),
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None
-        });
-
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.0,
-            height: size.1,
-            present_mode: wgpu::PresentMode::AutoNoVsync,
-        };
-
-        surface.configure(&device, &surface_config);
-
-        let sampler_nearest = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("mip"),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+/// Draws the stage. Implementations only differ in how skins are made and how the scene is drawn; keeping track of
+/// drawables, skins, and bounds is shared, through the `Scene` each one keeps.
+pub trait Renderer {
+    fn scene(&self) -> &Scene;
+    fn scene_mut(&mut self) -> &mut Scene;
+
+    fn draw(&mut self);
+    fn resize(&mut self, size: (u32, u32));
+
+    /// Draw the stage as it is now at `scale` times its size (1.0 for stage resolution), and return the image as
+    /// RGBA. The stage is opaque, so the image's colors are the same whether or not they're premultiplied.
+    fn snapshot(&mut self, scale: f32) -> Result<tiny_skia::Pixmap, &'static str>;
+
+    fn blank_skin(&self, size: Vec2, rotation_center: Vec2) -> BlankSkin;
+    fn create_svg_skin(&self, svg_data: &[u8], rotation_center: (f64, f64)) -> Box<dyn Skin>;
+    fn create_bitmap_skin(
+        &self,
+        pixmap: tiny_skia::Pixmap,
+        resolution: f64,
+        rotation_center: (f64, f64),
+    ) -> Box<dyn Skin>;
 
-        let sampler_linear = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("mip"),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        });
+    /// Load a skin now, if it loads lazily and hasn't been loaded yet. Each renderer passes itself to the skin's
+    /// loader, which is why this has no default.
+    fn load_skin(&self, skin: &RefCell<dyn Skin>);
 
-        let gpu_state = GpuState {
-            surface,
-            surface_config,
-            device,
-            queue,
-            render_pipeline,
-            vertex_buf,
-            index_buf,
-            stage_bind_group,
-            sampler_nearest,
-            sampler_linear,
-        };
+    fn stage_size(&self) -> (u32, u32) {
+        self.scene().stage_size()
+    }
 
-        Self {
-            gpu_state,
-            drawable_renderer_state,
-            drawables: HashMap::new(),
-            draw_list: Vec::new(),
-            skins: Vec::new(),
-            stage_size,
-            next_drawable_id: 0,
-        }
+    fn create_blank_skin(&mut self) -> Rc<RefCell<dyn Skin>> {
+        let skin = self.blank_skin(Vec2::new(100f32, 75f32), Vec2::new(50.0, 37.5));
+        self.scene_mut().add_skin(Rc::new(RefCell::new(skin)))
     }
 
-    fn draw_these(&mut self, texture_view: &wgpu::TextureView) {
-        let mut encoder = self
-            .gpu_state
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    /// Create a skin that's blank until it's loaded. It's loaded when something needs to know its shape (e.g. for
+    /// sensing or fencing), and otherwise in the background by `load_pending_skins`, starting with the skins that are
+    /// already on stage.
+    fn create_lazy_skin(&mut self, loader: SkinLoader) -> Rc<RefCell<dyn Skin>> {
+        // Zero-sized, so that nothing is drawn where the real image will be.
+        let placeholder = self.blank_skin(Vec2::ZERO, Vec2::ZERO);
+        self.scene_mut()
+            .add_skin(Rc::new(RefCell::new(LazySkin::new(placeholder, loader))))
+    }
 
-        let drawables = &mut self.drawables;
-        for drawable_id in self.draw_list.iter() {
-            let drawable = drawables
-                .get_mut(drawable_id)
-                .expect("Drawable does not exist--did the draw list get out of sync with the set of drawables?");
-            drawable.update_bind_group(&self.gpu_state);
+    /// Load up to `max` lazily loaded skins that haven't been loaded yet: first the ones that are waiting to be drawn,
+    /// then any others in the order they were created.
+    fn load_pending_skins(&mut self, max: usize) {
+        for _ in 0..max {
+            match self.scene_mut().next_pending_skin() {
+                Some(skin) => self.load_skin(&skin),
+                None => return,
+            }
         }
+    }
 
-        {
-            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: texture_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-            rpass.set_pipeline(&self.gpu_state.render_pipeline);
-            rpass.set_index_buffer(
-                self.gpu_state.index_buf.slice(..),
-                wgpu::IndexFormat::Uint16,
-            );
-            rpass.set_vertex_buffer(0, self.gpu_state.vertex_buf.slice(..));
-            rpass.set_bind_group(0, &self.gpu_state.stage_bind_group, &[]);
+    /// Create a skin for a speech bubble. Text bubble skins are short-lived, so unlike costume skins, they are owned
+    /// only by the drawables that use them.
+    fn create_text_bubble_skin(
+        &mut self,
+        bubble_type: BubbleType,
+        text: &str,
+        on_sprite_right: bool,
+    ) -> Rc<RefCell<dyn Skin>> {
+        let svg = bubble_svg(
+            self.scene().svg_options(),
+            bubble_type,
+            text,
+            on_sprite_right,
+        );
+        Rc::new(RefCell::new(
+            self.create_svg_skin(svg.as_bytes(), (0.0, 0.0)),
+        ))
+    }
 
-            for drawable_id in self.draw_list.iter() {
-                let drawable = drawables.get(drawable_id).unwrap();
-                rpass.set_bind_group(1, &drawable.bind_group, &[]);
-                rpass.draw_indexed(0..NUM_INDICES as u32, 0, 0..1);
-            }
-        }
+    /// Create a skin for the "ask" prompt, sized to fit the width of the stage.
+    fn create_prompt_skin(&mut self, question: &str, answer: &str) -> Rc<RefCell<dyn Skin>> {
+        let svg = prompt_svg(
+            self.scene().svg_options(),
+            question,
+            answer,
+            self.stage_size().0 as f64,
+        );
+        Rc::new(RefCell::new(
+            self.create_svg_skin(svg.as_bytes(), (0.0, 0.0)),
+        ))
+    }
 
-        let command_buffers = Some(encoder.finish());
+    fn create_drawable(&mut self, skin: Rc<RefCell<dyn Skin>>) -> DrawableID {
+        self.scene_mut().create_drawable(skin)
+    }
 
-        self.gpu_state.queue.submit(command_buffers);
+    /// Create a drawable that is drawn above every drawable in the draw list.
+    fn create_overlay_drawable(&mut self, skin: Rc<RefCell<dyn Skin>>) -> DrawableID {
+        self.scene_mut().create_overlay_drawable(skin)
     }
 
-    pub fn draw(&mut self) {
-        let frame = self
-            .gpu_state
-            .surface
-            .get_current_texture()
-            .expect("Failed to acquire next swap chain texture");
+    fn destroy_drawable(&mut self, drawable_id: DrawableID) {
+        self.scene_mut().destroy_drawable(drawable_id)
+    }
 
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-        self.draw_these(&view);
-        frame.present();
+    fn update_drawable_skin(&mut self, drawable_id: DrawableID, skin: Rc<RefCell<dyn Skin>>) {
+        self.scene_mut().drawable_mut(drawable_id).set_skin(skin)
     }
 
-    pub fn resize(&mut self, size: (u32, u32)) {
-        self.gpu_state.surface_config.width = size.0;
-        self.gpu_state.surface_config.height = size.1;
-        self.gpu_state
-            .surface
-            .configure(&self.gpu_state.device, &self.gpu_state.surface_config);
+    fn update_drawable_visibility(&mut self, drawable_id: DrawableID, visible: bool) {
+        self.scene_mut().drawable_mut(drawable_id).visible = visible
     }
 
-    pub fn create_blank_skin(&mut self) -> Rc<RefCell<dyn Skin>> {
-        let s = Rc::new(RefCell::new(BlankSkin::new(
-            Vec2::new(100f32, 75f32),
-            Vec2::new(50.0, 37.5),
-        )));
-        self.skins.push(s);
-        Rc::clone(&self.skins[self.skins.len() - 1])
+    fn get_drawable_bounds(&mut self, drawable_id: DrawableID) -> Rect {
+        self.load_skin(&self.scene().drawable(drawable_id).get_skin());
+        self.scene_mut().drawable_mut(drawable_id).get_bounds()
     }
 
-    pub fn create_svg_skin(
-        &mut self,
-        svg_data: &[u8],
-        rotation_center: (f64, f64),
-    ) -> Rc<RefCell<dyn Skin>> {
-        let s = Rc::new(RefCell::new(SVGSkin::new(
-            &self.gpu_state,
-            svg_data,
-            Vec2::new(rotation_center.0 as f32, rotation_center.0 as f32),
-        )));
-        self.skins.push(s);
-        Rc::clone(&self.skins[self.skins.len() - 1])
+    /// Move a drawable to the top of the draw list (below any overlays).
+    fn move_drawable_to_front(&mut self, drawable_id: DrawableID) {
+        self.scene_mut().move_drawable_to_front(drawable_id)
     }
 
-    fn add_to_draw_list(&mut self, drawable_id: DrawableID) {
-        self.draw_list.push(drawable_id);
+    /// Find the topmost visible drawable whose skin is opaque at the given point, in stage coordinates.
+    fn pick(&mut self, point: (f64, f64)) -> Option<DrawableID> {
+        for skin in self.scene().visible_skins() {
+            self.load_skin(&skin);
+        }
+        self.scene_mut()
+            .pick(Vec2::new(point.0 as f32, point.1 as f32))
     }
 
-    pub fn create_drawable(&mut self, skin: Rc<RefCell<dyn Skin>>) -> DrawableID {
-        let next_drawable_id = self.next_drawable_id;
-        self.next_drawable_id += 1;
-        let id = DrawableID(next_drawable_id);
-        let d = Drawable::new(skin, &self.gpu_state, &self.drawable_renderer_state);
-        self.drawables.insert(id, d);
-        self.add_to_draw_list(id);
-        id
+    /// Adjust a position that a drawable would move to so that it stays at least partially on the stage.
+    fn get_fenced_position_of_drawable(
+        &mut self,
+        drawable_id: DrawableID,
+        position: (f64, f64),
+    ) -> (f64, f64) {
+        self.load_skin(&self.scene().drawable(drawable_id).get_skin());
+        self.scene_mut()
+            .get_fenced_position_of_drawable(drawable_id, position)
     }
 
-    pub fn update_drawable_position(&mut self, drawable_id: DrawableID, position: (f64, f64)) {
-        self.drawables
-            .get_mut(&drawable_id)
-            .expect("Invalid drawable ID")
+    fn update_drawable_position(&mut self, drawable_id: DrawableID, position: (f64, f64)) {
+        self.scene_mut()
+            .drawable_mut(drawable_id)
             .set_position(Vec2::new(position.0 as f32, position.1 as f32))
     }
 
-    pub fn update_drawable_rotation_scale(
+    fn update_drawable_rotation_scale(
         &mut self,
         drawable_id: DrawableID,
         rotation: f32,
         scale: (f64, f64),
     ) {
-        let drawable = self
-            .drawables
-            .get_mut(&drawable_id)
-            .expect("Invalid drawable ID");
+        let drawable = self.scene_mut().drawable_mut(drawable_id);
         drawable.set_rotation(rotation);
         drawable.set_scale(Vec2::new(scale.0 as f32, scale.1 as f32));
     }
@@ -0,0 +1,202 @@
+//! What's on stage, independent of how it's drawn: the drawables and their order, the skins they use, and the
+//! stage's size. Every renderer keeps one of these and does its drawing from it.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use glam::Vec2;
+
+use super::{drawable::Drawable, renderer::DrawableID, skin::Skin};
+
+/// How far inside the stage a sprite is kept by fencing, at most.
+const FENCE_WIDTH: f32 = 15.0;
+
+pub struct Scene {
+    drawables: HashMap<DrawableID, Drawable>,
+    draw_list: Vec<DrawableID>,
+    /// Drawables that are always drawn above the draw list, such as speech bubbles and the "ask" prompt.
+    overlay_list: Vec<DrawableID>,
+    skins: Vec<Rc<RefCell<dyn Skin>>>,
+    /// Lazily loaded skins that visible drawables are waiting on, to be loaded before any others.
+    requested_skins: Vec<Rc<RefCell<dyn Skin>>>,
+    svg_options: usvg::Options,
+    stage_size: (u32, u32),
+    next_drawable_id: usize,
+}
+
+impl Scene {
+    pub fn new(stage_size: (u32, u32)) -> Self {
+        let mut svg_options = usvg::Options::default();
+        svg_options.fontdb.load_system_fonts();
+
+        Scene {
+            drawables: HashMap::new(),
+            draw_list: Vec::new(),
+            overlay_list: Vec::new(),
+            skins: Vec::new(),
+            requested_skins: Vec::new(),
+            svg_options,
+            stage_size,
+            next_drawable_id: 0,
+        }
+    }
+
+    pub fn stage_size(&self) -> (u32, u32) {
+        self.stage_size
+    }
+
+    pub fn svg_options(&self) -> &usvg::Options {
+        &self.svg_options
+    }
+
+    /// Keep a skin for as long as the scene exists, so it can be loaded in the background.
+    pub fn add_skin(&mut self, skin: Rc<RefCell<dyn Skin>>) -> Rc<RefCell<dyn Skin>> {
+        self.skins.push(Rc::clone(&skin));
+        skin
+    }
+
+    /// Queue the skins of visible drawables that haven't loaded yet, so they're loaded before any others.
+    pub fn request_visible_skins(&mut self) {
+        for drawable_id in self.draw_list.iter().chain(self.overlay_list.iter()) {
+            let drawable = self
+                .drawables
+                .get(drawable_id)
+                .expect("Drawable does not exist--did the draw list get out of sync with the set of drawables?");
+            let skin = drawable.get_skin();
+            if drawable.visible
+                && !skin.borrow().is_loaded()
+                && !self
+                    .requested_skins
+                    .iter()
+                    .any(|requested| Rc::ptr_eq(requested, &skin))
+            {
+                self.requested_skins.push(skin);
+            }
+        }
+    }
+
+    /// The next lazily loaded skin to load: one that's waiting to be drawn, or else the first one created that hasn't
+    /// loaded yet.
+    pub fn next_pending_skin(&mut self) -> Option<Rc<RefCell<dyn Skin>>> {
+        match self.requested_skins.pop() {
+            Some(skin) => Some(skin),
+            None => self
+                .skins
+                .iter()
+                .find(|skin| !skin.borrow().is_loaded())
+                .map(Rc::clone),
+        }
+    }
+
+    fn insert_drawable(&mut self, skin: Rc<RefCell<dyn Skin>>) -> DrawableID {
+        let id = DrawableID(self.next_drawable_id);
+        self.next_drawable_id += 1;
+        self.drawables.insert(id, Drawable::new(skin));
+        id
+    }
+
+    pub fn create_drawable(&mut self, skin: Rc<RefCell<dyn Skin>>) -> DrawableID {
+        let id = self.insert_drawable(skin);
+        self.draw_list.push(id);
+        id
+    }
+
+    /// Create a drawable that is drawn above every drawable in the draw list.
+    pub fn create_overlay_drawable(&mut self, skin: Rc<RefCell<dyn Skin>>) -> DrawableID {
+        let id = self.insert_drawable(skin);
+        self.overlay_list.push(id);
+        id
+    }
+
+    pub fn destroy_drawable(&mut self, drawable_id: DrawableID) {
+        self.drawables.remove(&drawable_id);
+        self.draw_list.retain(|id| *id != drawable_id);
+        self.overlay_list.retain(|id| *id != drawable_id);
+    }
+
+    pub fn contains_drawable(&self, drawable_id: DrawableID) -> bool {
+        self.drawables.contains_key(&drawable_id)
+    }
+
+    pub fn drawable(&self, drawable_id: DrawableID) -> &Drawable {
+        self.drawables
+            .get(&drawable_id)
+            .expect("Invalid drawable ID")
+    }
+
+    pub fn drawable_mut(&mut self, drawable_id: DrawableID) -> &mut Drawable {
+        self.drawables
+            .get_mut(&drawable_id)
+            .expect("Invalid drawable ID")
+    }
+
+    /// Every drawable, in the order they're drawn: the draw list from back to front, then the overlays.
+    pub fn draw_order(&self) -> Vec<DrawableID> {
+        self.draw_list
+            .iter()
+            .chain(self.overlay_list.iter())
+            .copied()
+            .collect()
+    }
+
+    /// The skins of every visible drawable.
+    pub fn visible_skins(&self) -> Vec<Rc<RefCell<dyn Skin>>> {
+        self.drawables
+            .values()
+            .filter(|drawable| drawable.visible)
+            .map(Drawable::get_skin)
+            .collect()
+    }
+
+    /// Move a drawable to the top of the draw list (below any overlays).
+    pub fn move_drawable_to_front(&mut self, drawable_id: DrawableID) {
+        if let Some(index) = self.draw_list.iter().position(|id| *id == drawable_id) {
+            self.draw_list.remove(index);
+            self.draw_list.push(drawable_id);
+        }
+    }
+
+    /// Find the topmost visible drawable whose skin is opaque at the given point, in stage coordinates. Skins that
+    /// haven't loaded yet are blank, so they never match.
+    pub fn pick(&mut self, point: Vec2) -> Option<DrawableID> {
+        let drawables = &mut self.drawables;
+        self.draw_list.iter().rev().copied().find(|drawable_id| {
+            let drawable = drawables.get_mut(drawable_id).expect("Invalid drawable ID");
+            drawable.visible
+                && drawable.get_bounds().contains_point(point.x, point.y)
+                && drawable.is_touching(point)
+        })
+    }
+
+    /// Adjust a position that a drawable would move to so that it stays at least partially on the stage. Mirrors
+    /// scratch-render's fencing.
+    pub fn get_fenced_position_of_drawable(
+        &mut self,
+        drawable_id: DrawableID,
+        position: (f64, f64),
+    ) -> (f64, f64) {
+        let stage_size = self.stage_size;
+        let drawable = self.drawable_mut(drawable_id);
+        let current_position = drawable.get_position();
+        let (mut x, mut y) = position;
+        let dx = x as f32 - current_position.x;
+        let dy = y as f32 - current_position.y;
+        let aabb = drawable.get_bounds();
+        let inset = (aabb.width().min(aabb.height()) / 2.0).floor();
+
+        let sx = (stage_size.0 as f32 * 0.5) - FENCE_WIDTH.min(inset);
+        if aabb.right + dx < -sx {
+            x = (current_position.x - (sx + aabb.right)).ceil() as f64;
+        } else if aabb.left + dx > sx {
+            x = (current_position.x + (sx - aabb.left)).floor() as f64;
+        }
+
+        let sy = (stage_size.1 as f32 * 0.5) - FENCE_WIDTH.min(inset);
+        if aabb.top + dy < -sy {
+            y = (current_position.y - (sy + aabb.top)).ceil() as f64;
+        } else if aabb.bottom + dy > sy {
+            y = (current_position.y + (sy - aabb.bottom)).floor() as f64;
+        }
+
+        (x, y)
+    }
+}
@@ -4,7 +4,8 @@ struct VertexOutput {
 };
 
 struct Drawable {
-    transform: mat2x2<f32>,
+    // The columns of a mat2x2.
+    transform: vec4<f32>,
     translation: vec2<f32>,
 };
 @group(1)
@@ -45,7 +46,8 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
     out.tex_coord = tex_coord;
-    out.position = vec4<f32>(((r_drawable.transform * position) + r_drawable.translation) / (r_stage.size * 0.5), 0.0, 1.0);
+    var transform: mat2x2<f32> = mat2x2<f32>(r_drawable.transform.xy, r_drawable.transform.zw);
+    out.position = vec4<f32>(((transform * position) + r_drawable.translation) / (r_stage.size * 0.5), 0.0, 1.0);
     return out;
 }
 
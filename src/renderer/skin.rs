@@ -1,12 +1,62 @@
 use core::fmt;
 
 use glam::Vec2;
+use tiny_skia::Pixmap;
 use wgpu::TextureView;
 
+use super::renderer::Renderer;
+
 pub trait Skin {
-    fn get_texture(&mut self, scale: f32) -> &TextureView;
+    /// The skin's GPU texture. Only skins created by the wgpu renderer have one.
+    fn get_texture(&mut self, _scale: f32) -> Option<&TextureView> {
+        None
+    }
+    /// The skin's image in memory, for renderers that draw on the CPU. Blank skins don't have one.
+    fn get_pixmap(&self) -> Option<&Pixmap> {
+        None
+    }
     fn get_size(&self) -> Vec2;
     fn get_rotation_center(&self) -> Vec2;
+    /// Whether the skin is opaque at the given texture coordinate (in the range 0-1, with +y pointing down).
+    fn is_touching(&self, _tex_coord: Vec2) -> bool {
+        true
+    }
+    /// Whether the skin's image is ready. Skins that load lazily are blank until they've loaded.
+    fn is_loaded(&self) -> bool {
+        true
+    }
+    /// Finish loading the skin, if it loads lazily and hasn't yet.
+    fn load(&mut self, _renderer: &dyn Renderer) {}
+}
+
+impl<S: Skin + ?Sized> Skin for Box<S> {
+    fn get_texture(&mut self, scale: f32) -> Option<&TextureView> {
+        (**self).get_texture(scale)
+    }
+
+    fn get_pixmap(&self) -> Option<&Pixmap> {
+        (**self).get_pixmap()
+    }
+
+    fn get_size(&self) -> Vec2 {
+        (**self).get_size()
+    }
+
+    fn get_rotation_center(&self) -> Vec2 {
+        (**self).get_rotation_center()
+    }
+
+    fn is_touching(&self, tex_coord: Vec2) -> bool {
+        (**self).is_touching(tex_coord)
+    }
+
+    fn is_loaded(&self) -> bool {
+        (**self).is_loaded()
+    }
+
+    fn load(&mut self, renderer: &dyn Renderer) {
+        (**self).load(renderer)
+    }
 }
 
 impl fmt::Debug for dyn Skin {
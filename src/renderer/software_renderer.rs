@@ -0,0 +1,163 @@
+//! A renderer that draws the stage on the CPU with tiny-skia, for getting real pixels on machines without a GPU. It
+//! composites the draw list the same way the wgpu renderer's shader does: each skin is a unit quad transformed by its
+//! drawable's matrix, sampled bilinearly, and blended with premultiplied alpha over a white stage.
+
+use std::cell::RefCell;
+
+use glam::{Affine2, Vec2};
+use tiny_skia::{Color, FilterQuality, Pixmap, PixmapPaint, Transform};
+
+use super::{
+    blank_skin::BlankSkin,
+    image_skin::ImageSkin,
+    renderer::{snapshot_size, Renderer},
+    scene::Scene,
+    skin::Skin,
+};
+
+/// How far skins are shifted up and left, in target pixels. Edges that land exactly on pixel centers then cover the
+/// same pixels as on the GPU, which fills pixels on top and left edges but not bottom and right ones.
+const TIE_NUDGE: f32 = 1.0 / 256.0;
+
+/// Draws the stage without a GPU.
+///
+/// Graphic effects (ghost, color, fisheye, whirl, pixelate, mosaic, and brightness) are not drawn, so sprites always
+/// look as if their effects were cleared, and there is no pen layer, so anything drawn with the pen is missing. The
+/// wgpu renderer doesn't draw them either. Screenshots of projects that use them will differ from Scratch's, and
+/// `--compare-to` reports those differences like any other.
+pub struct SoftwareRenderer {
+    scene: Scene,
+    /// The last frame drawn. Stage coordinates are stretched to fill it, like the wgpu renderer's render target.
+    pixmap: Pixmap,
+}
+
+fn create_target(size: (u32, u32)) -> Pixmap {
+    Pixmap::new(size.0.max(1), size.1.max(1)).unwrap()
+}
+
+impl SoftwareRenderer {
+    pub fn new(stage_size: (u32, u32)) -> Self {
+        SoftwareRenderer {
+            scene: Scene::new(stage_size),
+            pixmap: create_target(stage_size),
+        }
+    }
+
+    /// The last frame drawn, as premultiplied RGBA.
+    pub fn pixmap(&self) -> &Pixmap {
+        &self.pixmap
+    }
+}
+
+/// The transform from a skin's pixels to the target's pixels, for a drawable with the given matrix.
+fn skin_transform(
+    matrix: Affine2,
+    skin_pixmap: &Pixmap,
+    stage_size: (u32, u32),
+    target: &Pixmap,
+) -> Transform {
+    // Skin pixels (+y down) into the unit quad (+y up), centered on the origin.
+    let skin_to_quad = Affine2::from_cols(
+        Vec2::new(1.0 / skin_pixmap.width() as f32, 0.0),
+        Vec2::new(0.0, -1.0 / skin_pixmap.height() as f32),
+        Vec2::new(-0.5, 0.5),
+    );
+    // Stage coordinates (+y up, origin in the center) into target pixels.
+    let stage_to_target = Affine2::from_cols(
+        Vec2::new(target.width() as f32 / stage_size.0 as f32, 0.0),
+        Vec2::new(0.0, -(target.height() as f32) / stage_size.1 as f32),
+        Vec2::new(target.width() as f32 * 0.5, target.height() as f32 * 0.5),
+    );
+    let transform = stage_to_target * matrix * skin_to_quad;
+    Transform::from_row(
+        transform.matrix2.x_axis.x,
+        transform.matrix2.x_axis.y,
+        transform.matrix2.y_axis.x,
+        transform.matrix2.y_axis.y,
+        transform.translation.x - TIE_NUDGE,
+        transform.translation.y - TIE_NUDGE,
+    )
+}
+
+/// Draw every visible drawable in the scene onto the target, stretching the stage to fill it.
+fn draw_scene(scene: &mut Scene, target: &mut Pixmap) {
+    target.fill(Color::WHITE);
+    let paint = PixmapPaint {
+        quality: FilterQuality::Bilinear,
+        ..PixmapPaint::default()
+    };
+
+    let stage_size = scene.stage_size();
+    for drawable_id in scene.draw_order() {
+        let drawable = scene.drawable_mut(drawable_id);
+        if !drawable.visible {
+            continue;
+        }
+        let matrix = drawable.get_matrix();
+        let skin = drawable.get_skin();
+        let skin = skin.borrow();
+        // Blank skins, including lazily loaded ones that haven't loaded yet, have nothing to draw.
+        let skin_pixmap = match skin.get_pixmap() {
+            Some(skin_pixmap) => skin_pixmap,
+            None => continue,
+        };
+        let transform = skin_transform(matrix, skin_pixmap, stage_size, target);
+        target.draw_pixmap(0, 0, skin_pixmap.as_ref(), &paint, transform, None);
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    fn scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+    fn draw(&mut self) {
+        draw_scene(&mut self.scene, &mut self.pixmap);
+        self.scene.request_visible_skins();
+    }
+
+    fn resize(&mut self, size: (u32, u32)) {
+        self.pixmap = create_target(size);
+    }
+
+    fn snapshot(&mut self, scale: f32) -> Result<Pixmap, &'static str> {
+        let mut snapshot = create_target(snapshot_size(self.scene.stage_size(), scale));
+        draw_scene(&mut self.scene, &mut snapshot);
+        Ok(snapshot)
+    }
+
+    fn blank_skin(&self, size: Vec2, rotation_center: Vec2) -> BlankSkin {
+        BlankSkin::new(size, rotation_center, None)
+    }
+
+    fn create_svg_skin(&self, svg_data: &[u8], rotation_center: (f64, f64)) -> Box<dyn Skin> {
+        Box::new(ImageSkin::from_svg_data(
+            svg_data,
+            Vec2::new(rotation_center.0 as f32, rotation_center.1 as f32),
+            self.scene.svg_options(),
+        ))
+    }
+
+    fn create_bitmap_skin(
+        &self,
+        pixmap: Pixmap,
+        resolution: f64,
+        rotation_center: (f64, f64),
+    ) -> Box<dyn Skin> {
+        Box::new(ImageSkin::from_bitmap(
+            pixmap,
+            resolution,
+            Vec2::new(rotation_center.0 as f32, rotation_center.1 as f32),
+        ))
+    }
+
+    fn load_skin(&self, skin: &RefCell<dyn Skin>) {
+        if !skin.borrow().is_loaded() {
+            skin.borrow_mut().load(self);
+        }
+    }
+}
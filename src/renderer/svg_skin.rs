@@ -1,65 +1,31 @@
 use glam::Vec2;
 
-use super::{renderer::GpuState, skin::Skin};
+use super::{image_skin::ImageSkin, skin::Skin, wgpu_renderer::GpuState};
 
 pub struct SVGSkin {
-    size: Vec2,
-    rotation_center: Vec2,
+    #[allow(dead_code)]
     rtree: usvg::Tree,
+    /// The rasterized image, used for hit-testing.
+    image: ImageSkin,
+    #[allow(dead_code)]
     texture: wgpu::Texture,
     texture_view: wgpu::TextureView,
 }
 
 impl SVGSkin {
-    pub(super) fn new(gpu_state: &GpuState, svg_data: &[u8], rotation_center: Vec2) -> Self {
-        let opt = usvg::Options::default();
-        let rtree = usvg::Tree::from_data(svg_data, &opt.to_ref()).unwrap();
-        let viewbox_rect = rtree.svg_node().view_box.rect;
-        let rotation_center = Vec2::new(
-            rotation_center.x - (viewbox_rect.x() as f32),
-            rotation_center.y - (viewbox_rect.y() as f32),
-        );
-        let size = Vec2::new(viewbox_rect.width() as f32, viewbox_rect.height() as f32);
-
-        let mut pixmap = tiny_skia::Pixmap::new(size.x as u32, size.y as u32).unwrap();
-        resvg::render(&rtree, usvg::FitTo::Original, tiny_skia::Transform::default(), pixmap.as_mut()).unwrap();
-        pixmap.data();
-
-        let texture_extent = wgpu::Extent3d {
-            width: size.x.round() as u32,
-            height: size.y.round() as u32,
-            depth_or_array_layers: 1,
-        };
-        let texture = gpu_state.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("SVGSkin"),
-            size: texture_extent,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-        });
-        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        gpu_state.queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            pixmap.data(),
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(size.x.round() as u32 * 4),
-                rows_per_image: None,
-            },
-            texture_extent,
-        );
+    pub(super) fn new(
+        gpu_state: &GpuState,
+        svg_data: &[u8],
+        rotation_center: Vec2,
+        options: &usvg::Options,
+    ) -> Self {
+        let rtree = usvg::Tree::from_data(svg_data, &options.to_ref()).unwrap();
+        let image = ImageSkin::from_svg(&rtree, rotation_center);
+        let (texture, texture_view) = gpu_state.create_texture(image.pixmap(), "SVGSkin");
 
         SVGSkin {
-            size,
-            rotation_center,
             rtree,
+            image,
             texture,
             texture_view,
         }
@@ -68,14 +34,22 @@ impl SVGSkin {
 
 impl Skin for SVGSkin {
     fn get_size(&self) -> Vec2 {
-        self.size
+        self.image.get_size()
     }
 
     fn get_rotation_center(&self) -> Vec2 {
-        self.rotation_center
+        self.image.get_rotation_center()
+    }
+
+    fn get_texture(&mut self, _scale: f32) -> Option<&wgpu::TextureView> {
+        Some(&self.texture_view)
+    }
+
+    fn get_pixmap(&self) -> Option<&tiny_skia::Pixmap> {
+        self.image.get_pixmap()
     }
 
-    fn get_texture(&mut self, _scale: f32) -> &wgpu::TextureView {
-        &self.texture_view
+    fn is_touching(&self, tex_coord: Vec2) -> bool {
+        self.image.is_touching(tex_coord)
     }
 }
@@ -0,0 +1,235 @@
+use usvg::NodeExt;
+
+/// The two kinds of speech bubble a sprite can show.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BubbleType {
+    Say,
+    Think,
+}
+
+const FONT_FAMILY: &str = "Helvetica, Arial, DejaVu Sans, sans-serif";
+const FONT_SIZE: f64 = 14.0;
+const TEXT_COLOR: &str = "#575E75";
+
+const BUBBLE_MAX_LINE_WIDTH: f64 = 170.0;
+const BUBBLE_MIN_WIDTH: f64 = 50.0;
+const BUBBLE_PADDING: f64 = 10.0;
+const BUBBLE_LINE_HEIGHT: f64 = 16.0;
+const BUBBLE_CORNER_RADIUS: f64 = 16.0;
+const BUBBLE_TAIL_HEIGHT: f64 = 12.0;
+const BUBBLE_STROKE_WIDTH: f64 = 4.0;
+
+const PROMPT_MARGIN: f64 = 8.0;
+const PROMPT_QUESTION_HEIGHT: f64 = 24.0;
+const PROMPT_INPUT_HEIGHT: f64 = 32.0;
+const PROMPT_ACCENT_COLOR: &str = "#4C97FF";
+
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Measure the width of a line of text by laying it out with usvg. Returns 0 if no fonts are available.
+fn measure_text(options: &usvg::Options, text: &str, font_size: f64) -> f64 {
+    let svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1"><text x="0" y="0" xml:space="preserve" font-family="{}" font-size="{}">{}</text></svg>"#,
+        FONT_FAMILY,
+        font_size,
+        escape_xml(text)
+    );
+    usvg::Tree::from_str(&svg, &options.to_ref())
+        .ok()
+        .and_then(|tree| tree.root().calculate_bbox())
+        .map_or(0.0, |bbox| bbox.width())
+}
+
+/// Greedily wrap text into lines no wider than `max_width`, breaking words that don't fit on a line by themselves.
+fn wrap_text(options: &usvg::Options, text: &str, max_width: f64) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+
+            if measure_text(options, &candidate, FONT_SIZE) <= max_width {
+                line = candidate;
+                continue;
+            }
+
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+
+            for c in word.chars() {
+                line.push(c);
+                if line.chars().count() > 1 && measure_text(options, &line, FONT_SIZE) > max_width {
+                    line.pop();
+                    lines.push(std::mem::replace(&mut line, c.to_string()));
+                }
+            }
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Generate the SVG for a speech bubble. The bubble's tail points down and to the left if it's on the sprite's right,
+/// and down and to the right otherwise.
+pub fn bubble_svg(
+    options: &usvg::Options,
+    bubble_type: BubbleType,
+    text: &str,
+    on_sprite_right: bool,
+) -> String {
+    let lines = wrap_text(options, text, BUBBLE_MAX_LINE_WIDTH);
+    let text_width = lines
+        .iter()
+        .map(|line| measure_text(options, line, FONT_SIZE))
+        .fold(0.0, f64::max);
+
+    // Skins are rasterized at whole-pixel sizes.
+    let width = (text_width.max(BUBBLE_MIN_WIDTH) + (BUBBLE_PADDING * 2.0)).ceil();
+    let height = (lines.len() as f64 * BUBBLE_LINE_HEIGHT) + (BUBBLE_PADDING * 2.0);
+    let r = BUBBLE_CORNER_RADIUS;
+    let margin = BUBBLE_STROKE_WIDTH * 0.5;
+
+    let outline = match bubble_type {
+        BubbleType::Say => format!(
+            r#"<path d="M {r} 0 H {right} A {r} {r} 0 0 1 {w} {r} V {bottom} A {r} {r} 0 0 1 {right} {h} H {tail_start} L {tail_tip_x} {tail_tip_y} L {r} {h} A {r} {r} 0 0 1 0 {bottom} V {r} A {r} {r} 0 0 1 {r} 0 Z"/>"#,
+            r = r,
+            w = width,
+            h = height,
+            right = width - r,
+            bottom = height - r,
+            tail_start = r + 16.0,
+            tail_tip_x = r - 4.0,
+            tail_tip_y = height + BUBBLE_TAIL_HEIGHT,
+        ),
+        BubbleType::Think => format!(
+            r#"<rect width="{w}" height="{h}" rx="{r}"/><circle cx="{r}" cy="{c1}" r="4"/><circle cx="{c2x}" cy="{c2y}" r="2"/>"#,
+            r = r,
+            w = width,
+            h = height,
+            c1 = height + 4.0,
+            c2x = r - 6.0,
+            c2y = height + 10.0,
+        ),
+    };
+
+    let flip = if on_sprite_right {
+        String::new()
+    } else {
+        format!(r#" transform="translate({} 0) scale(-1 1)""#, width)
+    };
+
+    let text_lines: String = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            format!(
+                r#"<text x="{}" y="{}" xml:space="preserve">{}</text>"#,
+                width * 0.5,
+                BUBBLE_PADDING + (BUBBLE_LINE_HEIGHT * (i as f64 + 1.0)) - 4.0,
+                escape_xml(line)
+            )
+        })
+        .collect();
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{vx} {vy} {vw} {vh}" width="{vw}" height="{vh}"><g fill="#FFFFFF" stroke="#000000" stroke-opacity="0.15" stroke-width="{stroke}"{flip}>{outline}</g><g font-family="{font}" font-size="{font_size}" fill="{color}" text-anchor="middle">{text_lines}</g></svg>"##,
+        vx = -margin,
+        vy = -margin,
+        vw = width + (margin * 2.0),
+        vh = height + BUBBLE_TAIL_HEIGHT + (margin * 2.0),
+        stroke = BUBBLE_STROKE_WIDTH,
+        flip = flip,
+        outline = outline,
+        font = FONT_FAMILY,
+        font_size = FONT_SIZE,
+        color = TEXT_COLOR,
+        text_lines = text_lines,
+    )
+}
+
+/// Generate the SVG for the "ask" prompt shown at the bottom of the stage: an optional question above a text entry
+/// box containing the answer typed so far.
+pub fn prompt_svg(
+    options: &usvg::Options,
+    question: &str,
+    answer: &str,
+    stage_width: f64,
+) -> String {
+    let width = stage_width - (PROMPT_MARGIN * 2.0);
+    let question_height = if question.is_empty() {
+        0.0
+    } else {
+        PROMPT_QUESTION_HEIGHT
+    };
+    let height = question_height + PROMPT_INPUT_HEIGHT + (PROMPT_MARGIN * 2.0);
+
+    let input_top = PROMPT_MARGIN + question_height;
+    let input_width = width - (PROMPT_MARGIN * 2.0);
+    let text_x = PROMPT_MARGIN + 12.0;
+    let text_baseline = input_top + (PROMPT_INPUT_HEIGHT * 0.5) + 4.0;
+    let cursor_x = text_x + measure_text(options, answer, 12.0) + 1.0;
+    let button_cx = PROMPT_MARGIN + input_width - (PROMPT_INPUT_HEIGHT * 0.5);
+    let button_cy = input_top + (PROMPT_INPUT_HEIGHT * 0.5);
+
+    let question_text = if question.is_empty() {
+        String::new()
+    } else {
+        format!(
+            r#"<text x="{}" y="{}" font-weight="bold" xml:space="preserve">{}</text>"#,
+            PROMPT_MARGIN + 4.0,
+            PROMPT_MARGIN + 14.0,
+            escape_xml(question)
+        )
+    };
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {w} {h}" width="{w}" height="{h}"><defs><clipPath id="input-clip"><rect x="{ix}" y="{iy}" width="{iw}" height="{ih}"/></clipPath></defs><rect x="0.5" y="0.5" width="{bw}" height="{bh}" rx="8" fill="#FFFFFF" stroke="#D9D9D9"/><g font-family="{font}" font-size="12" fill="{color}">{question_text}<rect x="{ix}" y="{iy}" width="{iw}" height="{ih}" rx="{ir}" fill="#FFFFFF" stroke="{accent}" stroke-width="2"/><g clip-path="url(#input-clip)"><text x="{tx}" y="{ty}" xml:space="preserve">{answer}</text><path d="M {cx} {cy0} V {cy1}" stroke="{color}"/></g></g><circle cx="{bx}" cy="{by}" r="12" fill="{accent}"/><path d="M {c0x} {c0y} L {c1x} {c1y} L {c2x} {c2y}" fill="none" stroke="#FFFFFF" stroke-width="2.5" stroke-linecap="round" stroke-linejoin="round"/></svg>"##,
+        w = width,
+        h = height,
+        bw = width - 1.0,
+        bh = height - 1.0,
+        ix = PROMPT_MARGIN,
+        iy = input_top,
+        iw = input_width,
+        ih = PROMPT_INPUT_HEIGHT,
+        ir = PROMPT_INPUT_HEIGHT * 0.5,
+        font = FONT_FAMILY,
+        color = TEXT_COLOR,
+        accent = PROMPT_ACCENT_COLOR,
+        question_text = question_text,
+        tx = text_x,
+        ty = text_baseline,
+        answer = escape_xml(answer),
+        cx = cursor_x,
+        cy0 = text_baseline - 11.0,
+        cy1 = text_baseline + 3.0,
+        bx = button_cx,
+        by = button_cy,
+        c0x = button_cx - 5.0,
+        c0y = button_cy,
+        c1x = button_cx - 1.5,
+        c1y = button_cy + 4.0,
+        c2x = button_cx + 5.0,
+        c2y = button_cy - 4.0,
+    )
+}
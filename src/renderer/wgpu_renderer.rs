@@ -0,0 +1,577 @@
+use std::{borrow::Cow, cell::RefCell, collections::HashMap, mem, rc::Rc, sync::mpsc};
+
+use bytemuck::{Pod, Zeroable};
+use futures::executor::block_on;
+use glam::Vec2;
+use wgpu::util::DeviceExt;
+use winit::window::Window;
+
+use super::{
+    bitmap_skin::BitmapSkin,
+    blank_skin::BlankSkin,
+    common::RendererState,
+    gpu_drawable::{DrawableRendererState, GpuDrawable},
+    renderer::{snapshot_size, DrawableID, Renderer},
+    scene::Scene,
+    skin::Skin,
+    svg_skin::SVGSkin,
+};
+
+const NUM_INDICES: usize = 6;
+
+/// Where the stage is drawn to: a window's surface, or a texture that's never shown.
+pub(super) enum RenderTarget {
+    Surface {
+        surface: wgpu::Surface,
+        config: wgpu::SurfaceConfiguration,
+    },
+    Offscreen(wgpu::Texture),
+}
+
+pub(super) struct GpuState {
+    pub render_target: RenderTarget,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub render_pipeline: wgpu::RenderPipeline,
+    pub vertex_buf: wgpu::Buffer,
+    pub index_buf: wgpu::Buffer,
+    pub stage_bind_group: wgpu::BindGroup,
+    #[allow(dead_code)]
+    pub sampler_nearest: wgpu::Sampler,
+    pub sampler_linear: wgpu::Sampler,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct StageUniforms {
+    size: [f32; 2],
+}
+
+impl GpuState {
+    /// Upload an image to a new texture.
+    pub fn create_texture(
+        &self,
+        pixmap: &tiny_skia::Pixmap,
+        label: &str,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture_extent = wgpu::Extent3d {
+            width: pixmap.width(),
+            height: pixmap.height(),
+            depth_or_array_layers: 1,
+        };
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: texture_extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixmap.data(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(pixmap.width() * 4),
+                rows_per_image: None,
+            },
+            texture_extent,
+        );
+        (texture, texture_view)
+    }
+}
+
+/// Draws the stage on the GPU, to a window or an offscreen texture.
+pub struct WgpuRenderer {
+    gpu_state: GpuState,
+    drawable_renderer_state: DrawableRendererState,
+    /// GPU resources for each drawable in the scene, created when it's first drawn.
+    gpu_drawables: HashMap<DrawableID, GpuDrawable>,
+    /// A single transparent pixel, which blank skins use as their texture.
+    blank_texture_view: Rc<wgpu::TextureView>,
+    scene: Scene,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Vertex {
+    _pos: [f32; 2],
+    _tex_coord: [f32; 2],
+}
+
+static QUAD_VERTS: [Vertex; 4] = [
+    Vertex {
+        _pos: [-0.5, -0.5],
+        _tex_coord: [0.0, 1.0],
+    },
+    Vertex {
+        _pos: [0.5, -0.5],
+        _tex_coord: [1.0, 1.0],
+    },
+    Vertex {
+        _pos: [-0.5, 0.5],
+        _tex_coord: [0.0, 0.0],
+    },
+    Vertex {
+        _pos: [0.5, 0.5],
+        _tex_coord: [1.0, 0.0],
+    },
+];
+
+static QUAD_INDICES: [u16; NUM_INDICES] = [0, 1, 2, 1, 2, 3];
+
+const TARGET_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8Unorm;
+
+fn create_offscreen_texture(device: &wgpu::Device, size: (u32, u32)) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Offscreen stage"),
+        size: wgpu::Extent3d {
+            width: size.0.max(1),
+            height: size.1.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: TARGET_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+    })
+}
+
+impl WgpuRenderer {
+    pub fn with_window(window: &Window, size: (u32, u32), stage_size: (u32, u32)) -> Self {
+        let instance = wgpu::Instance::new(wgpu::Backends::all());
+        let surface = unsafe { instance.create_surface(window) };
+        Self::new(&instance, Some(surface), size, stage_size)
+    }
+
+    /// Create a renderer that draws to an offscreen texture at stage size, for running projects without a window. This
+    /// still needs a GPU.
+    pub fn offscreen(stage_size: (u32, u32)) -> Self {
+        let instance = wgpu::Instance::new(wgpu::Backends::all());
+        Self::new(&instance, None, stage_size, stage_size)
+    }
+
+    fn new(
+        instance: &wgpu::Instance,
+        surface: Option<wgpu::Surface>,
+        size: (u32, u32),
+        stage_size: (u32, u32),
+    ) -> Self {
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            // Request an adapter which can render to our surface
+            compatible_surface: surface.as_ref(),
+            force_fallback_adapter: false,
+        }))
+        .expect("Failed to find an appropriate adapter");
+
+        // Create the logical device and command queue
+        let (device, queue) = block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        ))
+        .expect("Failed to create device");
+
+        let drawable_renderer_state = DrawableRendererState::init(&device);
+
+        let vertex_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::cast_slice(&QUAD_VERTS),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Index Buffer"),
+            contents: bytemuck::cast_slice(&QUAD_INDICES),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        // Load the shaders from disk
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
+        });
+
+        let stage_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Drawable"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(8),
+                    },
+                    count: None,
+                }],
+            });
+
+        let stage_uniform_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::bytes_of(&StageUniforms {
+                size: [stage_size.0 as f32, stage_size.1 as f32],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let stage_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &stage_bind_group_layout,
+            label: Some("Stage"),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: stage_uniform_buf.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[
+                &stage_bind_group_layout,
+                &drawable_renderer_state.bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let vertex_buffers = [wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Vertex>() as u64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x2,
+                    offset: 0,
+                    shader_location: 0,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x2,
+                    offset: (mem::size_of::<f32>() * 2) as u64,
+                    shader_location: 1,
+                },
+            ],
+        }];
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &vertex_buffers,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: TARGET_FORMAT,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::default(),
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let render_target = match surface {
+            Some(surface) => {
+                let config = wgpu::SurfaceConfiguration {
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    format: TARGET_FORMAT,
+                    width: size.0,
+                    height: size.1,
+                    present_mode: wgpu::PresentMode::AutoNoVsync,
+                };
+                surface.configure(&device, &config);
+                RenderTarget::Surface { surface, config }
+            }
+            None => RenderTarget::Offscreen(create_offscreen_texture(&device, size)),
+        };
+
+        let sampler_nearest = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("mip"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let sampler_linear = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("mip"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let gpu_state = GpuState {
+            render_target,
+            device,
+            queue,
+            render_pipeline,
+            vertex_buf,
+            index_buf,
+            stage_bind_group,
+            sampler_nearest,
+            sampler_linear,
+        };
+
+        // Textures start out zeroed, which is fully transparent.
+        let blank_texture_view = Rc::new(
+            gpu_state
+                .device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("BlankSkin"),
+                    size: wgpu::Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+        );
+
+        Self {
+            gpu_state,
+            drawable_renderer_state,
+            gpu_drawables: HashMap::new(),
+            blank_texture_view,
+            scene: Scene::new(stage_size),
+        }
+    }
+
+    fn draw_these(&mut self, texture_view: &wgpu::TextureView) {
+        let mut encoder = self
+            .gpu_state
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        let draw_order = self.scene.draw_order();
+        let scene = &mut self.scene;
+        self.gpu_drawables
+            .retain(|drawable_id, _| scene.contains_drawable(*drawable_id));
+        for drawable_id in &draw_order {
+            let drawable = scene.drawable_mut(*drawable_id);
+            match self.gpu_drawables.get_mut(drawable_id) {
+                Some(gpu_drawable) => {
+                    gpu_drawable.update(drawable, &self.gpu_state, &self.drawable_renderer_state)
+                }
+                None => {
+                    let gpu_drawable =
+                        GpuDrawable::new(drawable, &self.gpu_state, &self.drawable_renderer_state);
+                    self.gpu_drawables.insert(*drawable_id, gpu_drawable);
+                }
+            }
+        }
+        scene.request_visible_skins();
+
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: texture_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(&self.gpu_state.render_pipeline);
+            rpass.set_index_buffer(
+                self.gpu_state.index_buf.slice(..),
+                wgpu::IndexFormat::Uint16,
+            );
+            rpass.set_vertex_buffer(0, self.gpu_state.vertex_buf.slice(..));
+            rpass.set_bind_group(0, &self.gpu_state.stage_bind_group, &[]);
+
+            for drawable_id in &draw_order {
+                if !self.scene.drawable(*drawable_id).visible {
+                    continue;
+                }
+                rpass.set_bind_group(1, &self.gpu_drawables[drawable_id].bind_group, &[]);
+                rpass.draw_indexed(0..NUM_INDICES as u32, 0, 0..1);
+            }
+        }
+
+        let command_buffers = Some(encoder.finish());
+
+        self.gpu_state.queue.submit(command_buffers);
+    }
+}
+
+impl Renderer for WgpuRenderer {
+    fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    fn scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+    fn draw(&mut self) {
+        match &self.gpu_state.render_target {
+            RenderTarget::Surface { surface, .. } => {
+                let frame = surface
+                    .get_current_texture()
+                    .expect("Failed to acquire next swap chain texture");
+
+                let view = frame
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                self.draw_these(&view);
+                frame.present();
+            }
+            RenderTarget::Offscreen(texture) => {
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                self.draw_these(&view);
+            }
+        }
+    }
+
+    fn resize(&mut self, size: (u32, u32)) {
+        match &mut self.gpu_state.render_target {
+            RenderTarget::Surface { surface, config } => {
+                config.width = size.0;
+                config.height = size.1;
+                surface.configure(&self.gpu_state.device, config);
+            }
+            RenderTarget::Offscreen(texture) => {
+                *texture = create_offscreen_texture(&self.gpu_state.device, size);
+            }
+        }
+    }
+
+    fn snapshot(&mut self, scale: f32) -> Result<tiny_skia::Pixmap, &'static str> {
+        let size = snapshot_size(self.scene.stage_size(), scale);
+        let texture = create_offscreen_texture(&self.gpu_state.device, size);
+        self.draw_these(&texture.create_view(&wgpu::TextureViewDescriptor::default()));
+
+        // Rows of a texture copy have to be padded out to the copy alignment.
+        let row_bytes = size.0 * 4;
+        let padded_row_bytes = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = self
+            .gpu_state
+            .device
+            .create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Snapshot"),
+                size: (padded_row_bytes * size.1) as u64,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+        let mut encoder = self
+            .gpu_state
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.gpu_state.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.gpu_state.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .ok()
+            .and_then(Result::ok)
+            .ok_or("Could not read the stage back from the GPU")?;
+
+        let mut snapshot = tiny_skia::Pixmap::new(size.0, size.1).unwrap();
+        {
+            let data = slice.get_mapped_range();
+            let rows = data.chunks(padded_row_bytes as usize);
+            for (src, dest) in rows.zip(snapshot.data_mut().chunks_mut(row_bytes as usize)) {
+                // The stage is drawn in BGRA.
+                for (src, dest) in src.chunks(4).zip(dest.chunks_mut(4)) {
+                    dest.copy_from_slice(&[src[2], src[1], src[0], src[3]]);
+                }
+            }
+        }
+        buffer.unmap();
+        Ok(snapshot)
+    }
+
+    fn blank_skin(&self, size: Vec2, rotation_center: Vec2) -> BlankSkin {
+        BlankSkin::new(
+            size,
+            rotation_center,
+            Some(Rc::clone(&self.blank_texture_view)),
+        )
+    }
+
+    fn create_svg_skin(&self, svg_data: &[u8], rotation_center: (f64, f64)) -> Box<dyn Skin> {
+        Box::new(SVGSkin::new(
+            &self.gpu_state,
+            svg_data,
+            Vec2::new(rotation_center.0 as f32, rotation_center.1 as f32),
+            self.scene.svg_options(),
+        ))
+    }
+
+    fn create_bitmap_skin(
+        &self,
+        pixmap: tiny_skia::Pixmap,
+        resolution: f64,
+        rotation_center: (f64, f64),
+    ) -> Box<dyn Skin> {
+        Box::new(BitmapSkin::new(
+            &self.gpu_state,
+            pixmap,
+            resolution,
+            Vec2::new(rotation_center.0 as f32, rotation_center.1 as f32),
+        ))
+    }
+
+    fn load_skin(&self, skin: &RefCell<dyn Skin>) {
+        if !skin.borrow().is_loaded() {
+            skin.borrow_mut().load(self);
+        }
+    }
+}
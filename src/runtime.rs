@@ -1,26 +1,57 @@
 use crate::{
+    audio::{audio_engine::AudioEngine, midi::MusicEvent, output::AudioOutput},
     engine::{
         engine_data::EngineData,
         execute::execute,
+        io::{Clock, Mouse},
         project::Project,
+        question::{Question, QuestionQueue},
         sprite::Sprite,
+        target::Target,
         thread::{Thread, ThreadStatus},
         trigger::Trigger,
     },
-    renderer::renderer::Renderer,
+    renderer::{
+        renderer::{DrawableID, Renderer},
+        text_bubble::BubbleType,
+    },
 };
 
 use std::{
     cell::RefCell,
+    mem,
     time::{Duration, Instant},
 };
 
 pub struct Runtime<'a, 'eng, 'r> {
-    engine_data: &'eng EngineData,
-    project: &'a Project,
-    renderer: &'r RefCell<Renderer>,
-    exec_contexts: Vec<ExecutionContext<'a, 'r>>,
+    #[allow(dead_code)]
+    pub(crate) engine_data: &'eng EngineData,
+    #[allow(dead_code)]
+    pub(crate) project: &'a Project,
+    pub(crate) renderer: &'r RefCell<dyn Renderer>,
+    pub(crate) exec_contexts: Vec<ExecutionContext<'a, 'r>>,
     redraw_requested: bool,
+    client_size: (u32, u32),
+    pub(crate) mouse: Mouse,
+    pub(crate) username: String,
+    pub(crate) clock: Clock,
+    /// Milliseconds elapsed on the runtime's clock. Updated at the start of each step, so it stays constant within
+    /// a frame.
+    pub(crate) current_msecs: f64,
+    clock_start: Instant,
+    /// Whether the clock advances by exactly one frame per step instead of following wall time, so that runs are
+    /// reproducible.
+    fixed_timestep: bool,
+    ticks: u64,
+    pub(crate) questions: QuestionQueue,
+    prompt_drawable: Option<DrawableID>,
+    client_mouse_pos: (f64, f64),
+    mouse_press: Option<MousePress>,
+    pub(crate) audio: AudioEngine,
+    /// Music extension tempo, in beats per minute.
+    pub(crate) tempo: f64,
+    /// Every note and drum the music extension has played, if the host asked to record them.
+    music_log: Option<Vec<MusicEvent>>,
 }
 
 pub struct ExecutionContext<'a, 'r> {
@@ -30,43 +61,166 @@ pub struct ExecutionContext<'a, 'r> {
 
 const STEP_TIME: Duration = Duration::from_nanos(33333333);
 
+/// Steps per second when running on a fixed timestep. Matches STEP_TIME, but exact, so that e.g. one second is exactly
+/// 30 steps.
+pub const FIXED_STEPS_PER_SECOND: f64 = 30.0;
+
+/// Space between the "ask" prompt and the bottom of the stage.
+const PROMPT_MARGIN: f64 = 8.0;
+
+/// How far the mouse must move while pressed, in window pixels, before a click becomes a drag. Same as scratch-gui.
+const DRAG_THRESHOLD: f64 = 3.0;
+
+/// How many lazily loaded costumes to load after each step. Costumes that are on stage go first; keeping this small
+/// means a project that's still loading doesn't stutter.
+const SKINS_LOADED_PER_STEP: usize = 2;
+
+/// A mouse press that may turn into a drag.
+struct MousePress {
+    client_pos: (f64, f64),
+    /// The execution context of the sprite that was pressed on, if any.
+    ctx_idx: Option<usize>,
+    /// Offset from the mouse position to the dragged sprite's position, once dragging has started.
+    drag_offset: Option<(f64, f64)>,
+}
+
 impl<'a, 'eng, 'r> Runtime<'a, 'eng, 'r> {
     pub fn new(
         project: &'a Project,
         engine_data: &'eng EngineData,
-        renderer: &'r RefCell<Renderer>,
+        renderer: &'r RefCell<dyn Renderer>,
+        audio_output: Box<dyn AudioOutput>,
     ) -> Self {
         let mut exec_contexts = Vec::new();
 
-        project.targets.iter().for_each(|target| {
-            let threads: Vec<Thread> = target
-                .scripts
-                .iter()
-                .map(|script| Thread::new(script))
-                .collect();
+        // Drawables are stacked in the order they're created, so sprites are created from back to front.
+        let mut targets: Vec<&Target> = project.targets.iter().collect();
+        targets.sort_by_key(|target| target.layer_order);
+        targets.into_iter().for_each(|target| {
+            let threads: Vec<Thread> = target.scripts.iter().map(Thread::new).collect();
             let sprite = Sprite::new(target, renderer);
             exec_contexts.push(ExecutionContext { sprite, threads });
         });
 
+        let client_size = renderer.borrow().stage_size();
+
         let mut rt = Runtime {
             engine_data,
             exec_contexts,
             project,
             renderer,
             redraw_requested: false,
+            client_size,
+            mouse: Mouse::default(),
+            username: String::new(),
+            clock: Clock::default(),
+            current_msecs: 0.0,
+            clock_start: Instant::now(),
+            fixed_timestep: false,
+            ticks: 0,
+            questions: QuestionQueue::default(),
+            prompt_drawable: None,
+            client_mouse_pos: (0.0, 0.0),
+            mouse_press: None,
+            audio: AudioEngine::new(audio_output),
+            tempo: project
+                .targets
+                .iter()
+                .find(|target| target.is_stage)
+                .map_or(60.0, |stage| stage.tempo),
+            music_log: None,
         };
 
-        rt.exec_contexts
-            .sort_by_cached_key(|ctx| ctx.sprite.target.layer_order);
+        for ctx_idx in 0..rt.exec_contexts.len() {
+            rt.update_sound_params(ctx_idx);
+        }
 
         rt
     }
 
+    /// The targets of every sprite in the project, including any added since it started running.
+    pub fn targets(&self) -> Vec<&'a Target> {
+        self.exec_contexts
+            .iter()
+            .map(|ctx| ctx.sprite.target)
+            .collect()
+    }
+
+    /// Add a sprite to the running project, in front of all the others. Its scripts wait for their hats to be
+    /// triggered like everyone else's; nothing starts right away. Returns the index of its execution context.
+    ///
+    /// The target has to outlive the runtime, just like the project's own targets.
+    pub fn add_sprite(&mut self, target: &'a Target) -> usize {
+        let threads: Vec<Thread> = target.scripts.iter().map(Thread::new).collect();
+        let sprite = Sprite::new(target, self.renderer);
+        self.exec_contexts
+            .push(ExecutionContext { sprite, threads });
+
+        let ctx_idx = self.exec_contexts.len() - 1;
+        self.update_sound_params(ctx_idx);
+        self.redraw_requested = true;
+        ctx_idx
+    }
+
     pub fn start_hats(&mut self, trigger: &Trigger) {
+        let mut abandoned_questions = vec![];
         for ExecutionContext { threads, .. } in &mut self.exec_contexts {
             for thread in threads {
                 // TODO: only some trigger types restart running threads.
                 if thread.trigger_matches(trigger) {
+                    abandoned_questions.extend(thread.question.take());
+                    thread.start()
+                }
+            }
+        }
+        self.cancel_questions(&abandoned_questions);
+    }
+
+    /// Send a sprite's current volume and sound effects to the mixer.
+    pub(crate) fn update_sound_params(&mut self, ctx_idx: usize) {
+        let sprite = &self.exec_contexts[ctx_idx].sprite;
+        let params = sprite.sound_effects.voice_params(sprite.volume);
+        self.audio.mixer.set_params(ctx_idx, params);
+    }
+
+    /// Start hats matching the trigger, but only in one sprite's scripts.
+    fn start_sprite_hats(&mut self, ctx_idx: usize, trigger: &Trigger) {
+        let mut abandoned_questions = vec![];
+        for thread in &mut self.exec_contexts[ctx_idx].threads {
+            if thread.trigger_matches(trigger) {
+                abandoned_questions.extend(thread.question.take());
+                thread.start()
+            }
+        }
+        self.cancel_questions(&abandoned_questions);
+    }
+
+    /// Start the project: stop any playing sounds, drop any questions being asked, clear sound effects, reset the
+    /// timer, and run all "when green flag clicked" scripts.
+    pub fn green_flag(&mut self) {
+        self.audio.mixer.stop_all();
+        for ExecutionContext { threads, .. } in &mut self.exec_contexts {
+            for thread in threads {
+                thread.question = None;
+            }
+        }
+        if let Some(question) = self.questions.clear() {
+            self.finish_question(question);
+        }
+        for ctx_idx in 0..self.exec_contexts.len() {
+            self.exec_contexts[ctx_idx].sprite.sound_effects.clear();
+            self.update_sound_params(ctx_idx);
+        }
+        self.clock.reset_project_timer(self.current_msecs);
+        self.start_hats(&Trigger::WhenFlagClicked);
+    }
+
+    /// Evaluate every edge-activated hat. Scripts that are already running are left alone; the rest are started, and
+    /// end immediately unless their hat's condition has just become true.
+    fn start_edge_activated_hats(&mut self) {
+        for ExecutionContext { threads, .. } in &mut self.exec_contexts {
+            for thread in threads {
+                if thread.code.trigger.is_edge_activated() && thread.status == ThreadStatus::Done {
                     thread.start()
                 }
             }
@@ -80,10 +234,14 @@ impl<'a, 'eng, 'r> Runtime<'a, 'eng, 'r> {
         loop {
             let mut num_active_threads = 0;
 
-            for ExecutionContext { sprite, threads } in &mut self.exec_contexts {
-                for thread in threads {
+            for ctx_idx in 0..self.exec_contexts.len() {
+                // Threads are taken out of their context while they run so that they can access the rest of the
+                // runtime (e.g. other sprites) mutably.
+                let mut threads = mem::take(&mut self.exec_contexts[ctx_idx].threads);
+
+                for thread in &mut threads {
                     if thread.status == ThreadStatus::Done {
-                        break;
+                        continue;
                     }
 
                     if thread.status == ThreadStatus::YieldTick && !ran_first_tick {
@@ -93,7 +251,7 @@ impl<'a, 'eng, 'r> Runtime<'a, 'eng, 'r> {
                     if thread.status == ThreadStatus::Running
                         || thread.status == ThreadStatus::Yield
                     {
-                        execute(sprite, thread);
+                        execute(self, ctx_idx, thread);
                     }
 
                     if thread.status == ThreadStatus::Yield {
@@ -111,6 +269,8 @@ impl<'a, 'eng, 'r> Runtime<'a, 'eng, 'r> {
                         self.redraw_requested = true;
                     }
                 }
+
+                self.exec_contexts[ctx_idx].threads = threads;
             }
 
             ran_first_tick = true;
@@ -124,13 +284,318 @@ impl<'a, 'eng, 'r> Runtime<'a, 'eng, 'r> {
         }
     }
 
+    /// Advance the clock by exactly one frame per step, regardless of how long steps actually take.
+    pub fn use_fixed_timestep(&mut self) {
+        self.fixed_timestep = true;
+    }
+
+    /// The number of steps run so far.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Whether every thread has finished. Nothing more will run until something starts a script again.
+    pub fn threads_finished(&self) -> bool {
+        self.exec_contexts.iter().all(|ctx| {
+            ctx.threads
+                .iter()
+                .all(|thread| thread.status == ThreadStatus::Done)
+        })
+    }
+
+    /// Milliseconds elapsed on the runtime's clock after the given number of fixed-timestep steps.
+    pub fn fixed_step_msecs(ticks: u64) -> f64 {
+        (ticks as f64 * 1000.0) / FIXED_STEPS_PER_SECOND
+    }
+
+    /// Start recording the notes and drums played by the music extension.
+    pub fn record_music(&mut self) {
+        self.music_log.get_or_insert_with(Vec::new);
+    }
+
+    /// The notes and drums played since recording started, in the order they were played.
+    pub fn music_events(&self) -> &[MusicEvent] {
+        self.music_log.as_deref().unwrap_or(&[])
+    }
+
+    pub(crate) fn log_music_event(&mut self, event: MusicEvent) {
+        if let Some(music_log) = &mut self.music_log {
+            music_log.push(event);
+        }
+    }
+
+    /// Mix all audio up to the given time on the runtime's clock. Steps do this automatically; call it after the last
+    /// step to flush the final frame's audio.
+    pub fn render_audio_until(&mut self, msecs: f64) {
+        self.audio.render_until(msecs);
+    }
+
     pub fn step(&mut self) {
+        self.current_msecs = if self.fixed_timestep {
+            Self::fixed_step_msecs(self.ticks)
+        } else {
+            self.clock_start.elapsed().as_secs_f64() * 1000.0
+        };
+        self.ticks += 1;
+        // Mix audio up to now before running threads, so sounds started this step begin at this step's time.
+        self.audio.render_until(self.current_msecs);
         self.redraw_requested = false;
+        self.start_edge_activated_hats();
         self.step_threads();
-        self.renderer.borrow_mut().draw();
+        let mut renderer = self.renderer.borrow_mut();
+        renderer.load_pending_skins(SKINS_LOADED_PER_STEP);
+        renderer.draw();
     }
 
     pub fn resize(&mut self, size: (u32, u32)) {
+        self.client_size = size;
         self.renderer.borrow_mut().resize(size);
     }
+
+    /// Post a new mouse position, in window coordinates. Moving the mouse far enough while pressing on a draggable
+    /// sprite starts dragging it.
+    pub fn mouse_move(&mut self, position: (f64, f64)) {
+        self.client_mouse_pos = position;
+        let stage_size = self.renderer.borrow().stage_size();
+        self.mouse
+            .post_position(position, self.client_size, stage_size);
+
+        let press = match &mut self.mouse_press {
+            Some(press) => press,
+            None => return,
+        };
+
+        if press.drag_offset.is_none() {
+            let dx = position.0 - press.client_pos.0;
+            let dy = position.1 - press.client_pos.1;
+            if (dx * dx) + (dy * dy) <= DRAG_THRESHOLD * DRAG_THRESHOLD {
+                return;
+            }
+
+            // Only draggable sprites can be dragged. The press is still tracked so that releasing it won't count as
+            // a click.
+            let ctx_idx = match press.ctx_idx {
+                Some(ctx_idx) if self.exec_contexts[ctx_idx].sprite.draggable => ctx_idx,
+                _ => {
+                    press.ctx_idx = None;
+                    return;
+                }
+            };
+
+            // The drag offset is measured from where the mouse was pressed, not where the drag started.
+            let mut press_pos = Mouse::default();
+            press_pos.post_position(press.client_pos, self.client_size, stage_size);
+            let sprite = &mut self.exec_contexts[ctx_idx].sprite;
+            press.drag_offset = Some((sprite.x - press_pos.x, sprite.y - press_pos.y));
+            sprite.dragging = true;
+            sprite.go_to_front();
+        }
+
+        if let (Some(ctx_idx), Some(offset)) = (press.ctx_idx, press.drag_offset) {
+            self.exec_contexts[ctx_idx]
+                .sprite
+                .drag_to(self.mouse.x + offset.0, self.mouse.y + offset.1);
+        }
+    }
+
+    /// Post a change in the (primary) mouse button's state. Click hats fire on press for non-draggable sprites and
+    /// the stage, and on release for draggable sprites, unless they were dragged.
+    pub fn mouse_button(&mut self, is_down: bool) {
+        if is_down == self.mouse.is_down {
+            return;
+        }
+        self.mouse.is_down = is_down;
+
+        if is_down {
+            let ctx_idx = self.pick_sprite();
+            self.mouse_press = Some(MousePress {
+                client_pos: self.client_mouse_pos,
+                ctx_idx,
+                drag_offset: None,
+            });
+            if let Some(ctx_idx) = ctx_idx {
+                if !self.exec_contexts[ctx_idx].sprite.draggable {
+                    self.start_click_hats(ctx_idx);
+                }
+            }
+            return;
+        }
+
+        let press = match self.mouse_press.take() {
+            Some(press) => press,
+            None => return,
+        };
+
+        match (press.ctx_idx, press.drag_offset) {
+            (Some(ctx_idx), Some(_)) => {
+                let sprite = &mut self.exec_contexts[ctx_idx].sprite;
+                sprite.dragging = false;
+                // Re-apply fencing now that scripts can move the sprite again.
+                sprite.move_to(sprite.x, sprite.y);
+            }
+            (Some(_), None) => {
+                if let Some(ctx_idx) = self.pick_sprite() {
+                    if self.exec_contexts[ctx_idx].sprite.draggable {
+                        self.start_click_hats(ctx_idx);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Find the execution context of the topmost sprite under the mouse, falling back to the stage.
+    fn pick_sprite(&self) -> Option<usize> {
+        let picked = self
+            .renderer
+            .borrow_mut()
+            .pick((self.mouse.x, self.mouse.y));
+        let ctx_idx = picked.and_then(|drawable| {
+            self.exec_contexts
+                .iter()
+                .position(|ctx| ctx.sprite.drawable == drawable)
+        });
+        ctx_idx.or_else(|| {
+            self.exec_contexts
+                .iter()
+                .position(|ctx| ctx.sprite.target.is_stage)
+        })
+    }
+
+    fn start_click_hats(&mut self, ctx_idx: usize) {
+        let trigger = if self.exec_contexts[ctx_idx].sprite.target.is_stage {
+            Trigger::WhenStageClicked
+        } else {
+            Trigger::WhenSpriteClicked
+        };
+        self.start_sprite_hats(ctx_idx, &trigger);
+    }
+
+    /// Handle a character typed by the user. While a question is being asked, this edits the answer; Enter submits
+    /// it.
+    pub fn post_character(&mut self, c: char) {
+        if self.questions.current().is_none() {
+            return;
+        }
+
+        match c {
+            '\r' | '\n' => self.submit_answer(),
+            '\u{8}' | '\u{7f}' => {
+                self.questions.text_entry.pop();
+                self.update_prompt();
+            }
+            c if c.is_control() => {}
+            c => {
+                self.questions.text_entry.push(c);
+                self.update_prompt();
+            }
+        }
+    }
+
+    /// Queue a question from an "ask and wait" block, returning its ID. The question is shown in the asking sprite's
+    /// speech bubble, or in the prompt if the sprite is hidden or is the stage.
+    pub(crate) fn ask(&mut self, sprite_idx: usize, text: String) -> usize {
+        let sprite = &self.exec_contexts[sprite_idx].sprite;
+        let in_bubble = sprite.visible && !sprite.target.is_stage;
+        let was_empty = self.questions.current().is_none();
+        let id = self.questions.push(text, sprite_idx, in_bubble);
+        if was_empty {
+            self.ask_next_question();
+        }
+        id
+    }
+
+    fn ask_next_question(&mut self) {
+        if let Some(question) = self.questions.current() {
+            if question.in_bubble {
+                let text = question.text.clone();
+                self.exec_contexts[question.sprite_idx]
+                    .sprite
+                    .set_bubble(BubbleType::Say, &text);
+            }
+        }
+        self.update_prompt();
+    }
+
+    fn submit_answer(&mut self) {
+        if let Some(question) = self.questions.submit() {
+            self.finish_question(question);
+        }
+    }
+
+    /// Drop questions whose threads were restarted, and so aren't waiting for the answers anymore.
+    fn cancel_questions(&mut self, ids: &[usize]) {
+        for &id in ids {
+            if let Some(question) = self.questions.cancel(id) {
+                self.finish_question(question);
+            }
+        }
+    }
+
+    /// Take down a question that was being asked (answered or not), and ask the next one if there is one.
+    fn finish_question(&mut self, question: Question) {
+        if question.in_bubble {
+            self.exec_contexts[question.sprite_idx]
+                .sprite
+                .set_bubble(BubbleType::Say, "");
+        }
+        self.ask_next_question();
+    }
+
+    /// Show, update, or remove the prompt at the bottom of the stage to match the current question.
+    fn update_prompt(&mut self) {
+        let mut renderer = self.renderer.borrow_mut();
+
+        let question = match self.questions.current() {
+            Some(question) => question,
+            None => {
+                if let Some(drawable) = self.prompt_drawable.take() {
+                    renderer.destroy_drawable(drawable);
+                }
+                return;
+            }
+        };
+
+        let question_text = if question.in_bubble {
+            ""
+        } else {
+            &question.text
+        };
+        let skin = renderer.create_prompt_skin(question_text, &self.questions.text_entry);
+        let prompt_height = skin.borrow().get_size().y as f64;
+        let drawable = match self.prompt_drawable {
+            Some(drawable) => {
+                renderer.update_drawable_skin(drawable, skin);
+                drawable
+            }
+            None => renderer.create_overlay_drawable(skin),
+        };
+        self.prompt_drawable = Some(drawable);
+
+        let stage_bottom = renderer.stage_size().1 as f64 * -0.5;
+        renderer.update_drawable_position(
+            drawable,
+            (0.0, stage_bottom + PROMPT_MARGIN + (prompt_height * 0.5)),
+        );
+    }
+
+    /// Set the username reported by the "username" sensing block.
+    pub fn set_username(&mut self, username: &str) {
+        self.username = username.to_string();
+    }
+
+    pub fn stage(&self) -> Option<&Sprite<'a, 'r>> {
+        self.exec_contexts
+            .iter()
+            .map(|ctx| &ctx.sprite)
+            .find(|sprite| sprite.target.is_stage)
+    }
+
+    /// Look up a (non-stage) sprite by its target's name.
+    pub fn sprite_by_name(&self, name: &str) -> Option<&Sprite<'a, 'r>> {
+        self.exec_contexts
+            .iter()
+            .map(|ctx| &ctx.sprite)
+            .find(|sprite| !sprite.target.is_stage && sprite.target.name == name)
+    }
 }
@@ -0,0 +1,277 @@
+//! Decoding Scratch 1.x costume images (Squeak Forms) and sounds (Squeak ADPCM or raw samples).
+
+use std::convert::TryInto;
+
+use tiny_skia::{Pixmap, PremultipliedColorU8};
+
+use super::obj_stream::{Obj, ObjTable};
+
+const FORM_WIDTH: usize = 0;
+const FORM_HEIGHT: usize = 1;
+const FORM_DEPTH: usize = 2;
+const FORM_BITS: usize = 4;
+const COLOR_FORM_COLORS: usize = 5;
+
+const SOUND_MEDIA_ORIGINAL_SOUND: usize = 1;
+const SOUND_MEDIA_COMPRESSED_SAMPLE_RATE: usize = 4;
+const SOUND_MEDIA_COMPRESSED_BITS_PER_SAMPLE: usize = 5;
+const SOUND_MEDIA_COMPRESSED_DATA: usize = 6;
+
+/// Read a variable-length integer from a compressed Bitmap.
+fn decode_int(data: &[u8], pos: &mut usize) -> Result<usize, &'static str> {
+    let byte = |idx: usize| data.get(idx).copied().ok_or("Truncated bitmap");
+    let first = byte(*pos)? as usize;
+    let (value, len) = match first {
+        0..=223 => (first, 1),
+        224..=254 => ((first - 224) * 256 + byte(*pos + 1)? as usize, 2),
+        _ => (
+            u32::from_be_bytes([
+                byte(*pos + 1)?,
+                byte(*pos + 2)?,
+                byte(*pos + 3)?,
+                byte(*pos + 4)?,
+            ]) as usize,
+            5,
+        ),
+    };
+    *pos += len;
+    Ok(value)
+}
+
+/// Undo Squeak's `Bitmap>>compressToByteArray`, a run-length encoding of 32-bit words.
+fn decompress_bitmap(data: &[u8]) -> Result<Vec<u32>, &'static str> {
+    let mut pos = 0;
+    let len = decode_int(data, &mut pos)?;
+    if len > data.len() * 256 {
+        return Err("Malformed compressed bitmap");
+    }
+    let mut words = vec![0u32; len];
+    let mut idx = 0;
+    let bytes = |pos: usize, len: usize| data.get(pos..pos + len).ok_or("Truncated bitmap");
+
+    while idx < len {
+        let run = decode_int(data, &mut pos)?;
+        let (code, count) = (run & 3, run >> 2);
+        let end = (idx + count).min(len);
+        match code {
+            // Words that are already zero
+            0 => {}
+            // One byte, repeated across the word
+            1 => {
+                let word = u32::from_ne_bytes([bytes(pos, 1)?[0]; 4]);
+                pos += 1;
+                words[idx..end].fill(word);
+            }
+            // One word, repeated
+            2 => {
+                let word = u32::from_be_bytes(bytes(pos, 4)?.try_into().unwrap());
+                pos += 4;
+                words[idx..end].fill(word);
+            }
+            // Literal words
+            _ => {
+                for word in &mut words[idx..end] {
+                    *word = u32::from_be_bytes(bytes(pos, 4)?.try_into().unwrap());
+                    pos += 4;
+                }
+            }
+        }
+        idx += count;
+    }
+
+    Ok(words)
+}
+
+/// Convert a Form or ColorForm into a PNG.
+pub fn form_to_png(table: &ObjTable, form: &Obj) -> Result<Vec<u8>, &'static str> {
+    let width = table
+        .field(form, FORM_WIDTH)
+        .as_f64()
+        .ok_or("Form has no width")? as usize;
+    let height = table
+        .field(form, FORM_HEIGHT)
+        .as_f64()
+        .ok_or("Form has no height")? as usize;
+    // Negative depths are little-endian forms, which Scratch never saves; the pixel layout is otherwise the same.
+    let depth = table
+        .field(form, FORM_DEPTH)
+        .as_f64()
+        .ok_or("Form has no depth")?
+        .abs() as usize;
+    let words = match table.field(form, FORM_BITS) {
+        Obj::Bytes(bytes) => decompress_bitmap(bytes)?,
+        Obj::Bitmap(words) => words.clone(),
+        _ => return Err("Form has no bits"),
+    };
+    let colors: Option<Vec<[u8; 4]>> = match table.field(form, COLOR_FORM_COLORS) {
+        Obj::Collection(colors) => Some(
+            colors
+                .iter()
+                .map(|color| match table.resolve(color) {
+                    Obj::Color { r, g, b, a } => [*r, *g, *b, *a],
+                    _ => [0, 0, 0, 0],
+                })
+                .collect(),
+        ),
+        _ => None,
+    };
+
+    if !matches!(depth, 1 | 2 | 4 | 8 | 16 | 32) {
+        return Err("Unsupported form depth");
+    }
+    let pixels_per_word = 32 / depth;
+    let words_per_row = width.div_ceil(pixels_per_word);
+    if words.len() < words_per_row * height {
+        return Err("Form has too few bits");
+    }
+
+    let mut pixmap = Pixmap::new(width as u32, height as u32).ok_or("Form has no pixels")?;
+    let pixels = pixmap.pixels_mut();
+    for y in 0..height {
+        for x in 0..width {
+            let word = words[y * words_per_row + x / pixels_per_word];
+            let shift = 32 - depth * (x % pixels_per_word + 1);
+            let value = (word >> shift) & (u32::MAX >> (32 - depth));
+
+            let [r, g, b, a] = match (depth, &colors) {
+                (32, _) => {
+                    let [a, r, g, b] = value.to_be_bytes();
+                    match (value, a) {
+                        (0, _) => [0, 0, 0, 0],
+                        // Older forms leave the alpha channel empty for opaque pixels.
+                        (_, 0) => [r, g, b, 255],
+                        _ => [r, g, b, a],
+                    }
+                }
+                (16, _) => {
+                    let component = |shift: u32| (((value >> shift) & 0x1f) * 255 / 31) as u8;
+                    if value == 0 {
+                        [0, 0, 0, 0]
+                    } else {
+                        [component(10), component(5), component(0), 255]
+                    }
+                }
+                (_, Some(colors)) => colors.get(value as usize).copied().unwrap_or([0; 4]),
+                // Scratch saves indexed images as ColorForms. Treat a plain indexed Form as grayscale with a transparent
+                // background rather than reproducing Squeak's default palettes.
+                (_, None) => {
+                    if value == 0 {
+                        [0, 0, 0, 0]
+                    } else {
+                        let max = (1u32 << depth) - 1;
+                        let gray = (255 - value * 255 / max) as u8;
+                        [gray, gray, gray, 255]
+                    }
+                }
+            };
+
+            let premultiply = |c: u8| ((c as u16 * a as u16 + 127) / 255) as u8;
+            pixels[y * width + x] =
+                PremultipliedColorU8::from_rgba(premultiply(r), premultiply(g), premultiply(b), a)
+                    .unwrap();
+        }
+    }
+
+    pixmap
+        .encode_png()
+        .map_err(|_| "Could not encode costume as PNG")
+}
+
+const ADPCM_STEP_SIZES: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+/// Decode Squeak's `ADPCMCodec` bitstream, which Scratch used (without frame headers) to compress sounds.
+fn decode_squeak_adpcm(data: &[u8], bits_per_sample: usize) -> Result<Vec<i16>, &'static str> {
+    let index_table: &[i32] = match bits_per_sample {
+        2 => &[-1, 2],
+        3 => &[-1, -1, 2, 4],
+        4 => &[-1, -1, -1, -1, 2, 4, 6, 8],
+        5 => &[-1, -1, -1, -1, -1, -1, -1, -1, 1, 2, 4, 6, 8, 10, 13, 16],
+        _ => return Err("Unsupported ADPCM bits per sample"),
+    };
+    let sign_mask = 1u32 << (bits_per_sample - 1);
+    let value_mask = sign_mask - 1;
+
+    let num_samples = data.len() * 8 / bits_per_sample;
+    let mut samples = Vec::with_capacity(num_samples);
+    let mut sample = 0i32;
+    let mut index = 0i32;
+    let mut bit_pos = 0;
+
+    for _ in 0..num_samples {
+        // Codes are packed most significant bit first.
+        let mut code = 0u32;
+        for _ in 0..bits_per_sample {
+            let bit = (data[bit_pos / 8] >> (7 - bit_pos % 8)) & 1;
+            code = (code << 1) | bit as u32;
+            bit_pos += 1;
+        }
+
+        let mut step = ADPCM_STEP_SIZES[index as usize];
+        let mut delta = 0;
+        let mut bit = sign_mask >> 1;
+        while bit > 0 {
+            if code & bit != 0 {
+                delta += step;
+            }
+            step >>= 1;
+            bit >>= 1;
+        }
+        delta += step;
+
+        sample = if code & sign_mask != 0 {
+            sample - delta
+        } else {
+            sample + delta
+        }
+        .clamp(i16::MIN as i32, i16::MAX as i32);
+        index = (index + index_table[(code & value_mask) as usize]).clamp(0, 88);
+        samples.push(sample as i16);
+    }
+
+    Ok(samples)
+}
+
+/// Get the samples and sample rate of a SoundMedia.
+pub fn decode_sound(table: &ObjTable, sound_media: &Obj) -> Result<(Vec<i16>, u32), &'static str> {
+    if let Obj::Bytes(compressed) = table.field(sound_media, SOUND_MEDIA_COMPRESSED_DATA) {
+        let rate = table
+            .field(sound_media, SOUND_MEDIA_COMPRESSED_SAMPLE_RATE)
+            .as_f64()
+            .ok_or("Compressed sound has no sample rate")?;
+        let bits_per_sample = table
+            .field(sound_media, SOUND_MEDIA_COMPRESSED_BITS_PER_SAMPLE)
+            .as_f64()
+            .ok_or("Compressed sound has no bits per sample")?;
+        return Ok((
+            decode_squeak_adpcm(compressed, bits_per_sample as usize)?,
+            rate as u32,
+        ));
+    }
+
+    // Uncompressed sounds are a SampledSound, whose fields end with "... samples originalSamplingRate". Find the
+    // sample buffer rather than relying on its position, which differs between versions.
+    let sampled_sound = table.field(sound_media, SOUND_MEDIA_ORIGINAL_SOUND);
+    let fields = match sampled_sound {
+        Obj::User { fields, .. } => fields,
+        _ => return Err("Sound has no samples"),
+    };
+    let samples_idx = fields
+        .iter()
+        .position(|field| matches!(table.resolve(field), Obj::SoundBuffer(_)))
+        .ok_or("Sound has no samples")?;
+    let samples = match table.resolve(&fields[samples_idx]) {
+        Obj::SoundBuffer(samples) => samples.clone(),
+        _ => unreachable!(),
+    };
+    let rate = table
+        .field(sampled_sound, samples_idx + 1)
+        .as_f64()
+        .ok_or("Sound has no sample rate")?;
+    Ok((samples, rate as u32))
+}
@@ -0,0 +1,442 @@
+//! Scratch 1.x (.sb) project support. The Squeak object table is decoded and rearranged into the sb2
+//! `project.json` layout, whose block format is derived from Scratch 1.x's, and from there it's translated the same
+//! way sb2 projects are.
+
+pub mod media;
+pub mod obj_stream;
+
+use std::collections::HashMap;
+
+use serde_json::{json, Map, Value};
+
+use crate::audio::wav::write_pcm16_wav;
+
+use obj_stream::{
+    Obj, ObjTable, CLASS_IMAGE_MEDIA, CLASS_SCRATCH_SPRITE, CLASS_SCRATCH_STAGE, CLASS_SOUND_MEDIA,
+};
+
+// Fields of ScratchStageMorph and ScratchSpriteMorph. The first six come from Morph, the next six from
+// ScriptableScratchMorph.
+const MORPH_BOUNDS: usize = 0;
+const MORPH_SUBMORPHS: usize = 2;
+const MORPH_FLAGS: usize = 4;
+const OBJ_NAME: usize = 6;
+const VARS: usize = 7;
+const BLOCKS_BIN: usize = 8;
+const MEDIA: usize = 10;
+const COSTUME: usize = 11;
+const SPRITE_SCALE_POINT: usize = 13;
+const SPRITE_ROTATION_DEGREES: usize = 14;
+const SPRITE_ROTATION_STYLE: usize = 15;
+const SPRITE_VOLUME: usize = 16;
+const SPRITE_DRAGGABLE: usize = 18;
+const STAGE_SPRITES: usize = 16;
+const STAGE_VOLUME: usize = 17;
+const STAGE_TEMPO_BPM: usize = 18;
+/// Lists are the last field of both sprites and the stage.
+const LISTS: usize = 20;
+
+const IMAGE_MEDIA_NAME: usize = 0;
+const IMAGE_MEDIA_FORM: usize = 1;
+const IMAGE_MEDIA_ROTATION_CENTER: usize = 2;
+const IMAGE_MEDIA_JPEG_BYTES: usize = 4;
+const IMAGE_MEDIA_COMPOSITE_FORM: usize = 5;
+const SOUND_MEDIA_NAME: usize = 0;
+
+/// Morph flag set on hidden morphs.
+const FLAG_HIDDEN: i64 = 1;
+
+const STAGE_WIDTH: f64 = 480.0;
+const STAGE_HEIGHT: f64 = 360.0;
+
+/// Does this file look like a Scratch 1.x project?
+pub fn is_sb1_project(data: &[u8]) -> bool {
+    data.starts_with(b"ScratchV0")
+}
+
+/// Convert a Scratch 1.x project into an sb2-layout `project.json` and the asset files it refers to, named the way
+/// an sb2 archive would name them (`0.png`, `0.wav`, ...).
+pub fn convert_project(data: &[u8]) -> Result<(Value, HashMap<String, Vec<u8>>), &'static str> {
+    let (_info, table) = obj_stream::read_project(data)?;
+    let stage = table.root()?;
+    if table.class_id(stage) != Some(CLASS_SCRATCH_STAGE) {
+        return Err("Scratch 1.x project does not contain a stage");
+    }
+
+    let mut converter = Converter {
+        table: &table,
+        assets: HashMap::new(),
+        next_image_id: 0,
+        next_sound_id: 0,
+    };
+    let project = converter.convert_stage(stage)?;
+    Ok((project, converter.assets))
+}
+
+/// Squeak stores points as a fixed-format object with x and y fields.
+fn point(table: &ObjTable, obj: &Obj) -> Option<(f64, f64)> {
+    Some((table.field(obj, 0).as_f64()?, table.field(obj, 1).as_f64()?))
+}
+
+/// Rectangles are (left, top, right, bottom).
+fn rectangle(table: &ObjTable, obj: &Obj) -> Option<[f64; 4]> {
+    Some([
+        table.field(obj, 0).as_f64()?,
+        table.field(obj, 1).as_f64()?,
+        table.field(obj, 2).as_f64()?,
+        table.field(obj, 3).as_f64()?,
+    ])
+}
+
+fn collection<'a>(table: &'a ObjTable, obj: &'a Obj) -> &'a [Obj] {
+    match table.resolve(obj) {
+        Obj::Collection(items) => items,
+        _ => &[],
+    }
+}
+
+/// Rewrite blocks whose Scratch 1.x form differs from the sb2 form, recursing into arguments and substacks.
+fn fix_blocks(value: Value) -> Value {
+    let items = match value {
+        Value::Array(items) => items,
+        other => return other,
+    };
+    let mut items: Vec<Value> = items.into_iter().map(fix_blocks).collect();
+    let selector = match items.first().and_then(Value::as_str) {
+        Some(selector) => selector.to_string(),
+        // A stack of blocks or a literal list
+        None => return Value::Array(items),
+    };
+    let arg = |idx: usize| items.get(idx).cloned().unwrap_or(Value::Null);
+
+    match selector.as_str() {
+        "EventHatMorph" => {
+            if arg(1) == "Scratch-StartClicked" {
+                json!(["whenGreenFlag"])
+            } else {
+                json!(["whenIReceive", arg(1)])
+            }
+        }
+        "KeyEventHatMorph" => json!(["whenKeyPressed", arg(1)]),
+        "MouseClickEventHatMorph" => json!(["whenClicked"]),
+        // [changeVariable, name, setVar:to: | changeVar:by:, value]
+        "changeVariable" => json!([arg(2), arg(1), arg(3)]),
+        "touching:" | "distanceTo:" | "gotoSpriteOrMouse:" | "pointTowards:" => {
+            if let Some(target) = items.get_mut(1) {
+                match target.as_str() {
+                    Some("mouse") => *target = json!("_mouse_"),
+                    Some("edge") => *target = json!("_edge_"),
+                    _ => {}
+                }
+            }
+            Value::Array(items)
+        }
+        _ => Value::Array(items),
+    }
+}
+
+struct Converter<'a> {
+    table: &'a ObjTable,
+    assets: HashMap<String, Vec<u8>>,
+    next_image_id: i64,
+    next_sound_id: i64,
+}
+
+impl<'a> Converter<'a> {
+    fn string_field(&self, obj: &Obj, idx: usize) -> String {
+        self.table
+            .field(obj, idx)
+            .as_str()
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// Convert a value from a script, variable, or list to JSON.
+    fn to_json(&self, obj: &Obj) -> Value {
+        match self.table.resolve(obj) {
+            Obj::Bool(b) => json!(b),
+            Obj::Int(i) => json!(i),
+            Obj::Float(f) if f.is_finite() => json!(f),
+            Obj::Float(f) => json!(f.to_string()),
+            Obj::String(s) | Obj::Symbol(s) => json!(s),
+            Obj::Collection(items) => {
+                Value::Array(items.iter().map(|item| self.to_json(item)).collect())
+            }
+            // sb2 stores colors as ARGB integers.
+            Obj::Color { r, g, b, a } => json!(u32::from_be_bytes([*a, *r, *g, *b])),
+            // Blocks that refer to sprites and the stage (e.g. "touching", "x position of") store the morph itself.
+            resolved @ Obj::User { class_id, .. } => match *class_id {
+                CLASS_SCRATCH_SPRITE => json!(self.string_field(resolved, OBJ_NAME)),
+                CLASS_SCRATCH_STAGE => json!("_stage_"),
+                _ => Value::Null,
+            },
+            _ => Value::Null,
+        }
+    }
+
+    fn add_asset(&mut self, id: i64, ext: &str, data: Vec<u8>) -> String {
+        let md5 = format!("{:x}", md5::compute(&data));
+        self.assets.insert(format!("{}.{}", id, ext), data);
+        format!("{}.{}", md5, ext)
+    }
+
+    fn convert_costume(&mut self, image_media: &Obj) -> Result<Value, &'static str> {
+        let table = self.table;
+        // The composite form has the costume's text layer drawn in. Otherwise, prefer the original JPEG data over
+        // re-encoding the decoded form.
+        let (data, ext) = match (
+            table.field(image_media, IMAGE_MEDIA_COMPOSITE_FORM),
+            table.field(image_media, IMAGE_MEDIA_JPEG_BYTES),
+        ) {
+            (Obj::Nil, Obj::Bytes(jpeg)) => (jpeg.clone(), "jpg"),
+            (Obj::Nil, _) => (
+                media::form_to_png(table, table.field(image_media, IMAGE_MEDIA_FORM))?,
+                "png",
+            ),
+            (composite, _) => (media::form_to_png(table, composite)?, "png"),
+        };
+
+        let id = self.next_image_id;
+        self.next_image_id += 1;
+        let md5 = self.add_asset(id, ext, data);
+        let (center_x, center_y) =
+            point(table, table.field(image_media, IMAGE_MEDIA_ROTATION_CENTER))
+                .unwrap_or((0.0, 0.0));
+
+        Ok(json!({
+            "costumeName": self.string_field(image_media, IMAGE_MEDIA_NAME),
+            "baseLayerID": id,
+            "baseLayerMD5": md5,
+            "bitmapResolution": 1,
+            "rotationCenterX": center_x,
+            "rotationCenterY": center_y,
+        }))
+    }
+
+    fn convert_sound(&mut self, sound_media: &Obj) -> Result<Value, &'static str> {
+        let (samples, rate) = media::decode_sound(self.table, sound_media)?;
+        let mut data = Vec::new();
+        write_pcm16_wav(&mut data, &samples, 1, rate).map_err(|_| "Could not encode sound")?;
+
+        let id = self.next_sound_id;
+        self.next_sound_id += 1;
+        let md5 = self.add_asset(id, "wav", data);
+
+        Ok(json!({
+            "soundName": self.string_field(sound_media, SOUND_MEDIA_NAME),
+            "soundID": id,
+            "md5": md5,
+            "sampleCount": samples.len(),
+            "rate": rate,
+            "format": "",
+        }))
+    }
+
+    /// Fields shared between sprites and the stage: name, variables, lists, scripts, costumes, and sounds.
+    fn convert_scriptable(&mut self, morph: &Obj) -> Result<Map<String, Value>, &'static str> {
+        let table = self.table;
+        let mut object = Map::new();
+        object.insert(
+            "objName".to_string(),
+            json!(self.string_field(morph, OBJ_NAME)),
+        );
+
+        let mut variables = vec![];
+        if let Obj::Dictionary(vars) = table.field(morph, VARS) {
+            for (name, value) in vars {
+                variables.push(json!({
+                    "name": self.to_json(name),
+                    "value": self.to_json(value),
+                }));
+            }
+        }
+        object.insert("variables".to_string(), Value::Array(variables));
+
+        let mut lists = vec![];
+        if let Obj::Dictionary(list_morphs) = table.field(morph, LISTS) {
+            for (name, list_morph) in list_morphs {
+                // A list's contents are stored on its watcher (a ScratchListMorph), as a collection of strings.
+                let contents = match table.resolve(list_morph) {
+                    Obj::User { fields, .. } => fields
+                        .iter()
+                        .map(|field| table.resolve(field))
+                        .find(|field| match field {
+                            Obj::Collection(items) => items
+                                .iter()
+                                .all(|item| matches!(table.resolve(item), Obj::String(_))),
+                            _ => false,
+                        })
+                        .map_or(json!([]), |contents| self.to_json(contents)),
+                    _ => json!([]),
+                };
+                lists.push(json!({
+                    "listName": self.to_json(name),
+                    "contents": contents,
+                }));
+            }
+        }
+        object.insert("lists".to_string(), Value::Array(lists));
+
+        let mut scripts = vec![];
+        for script in collection(table, table.field(morph, BLOCKS_BIN)) {
+            // Each script is (position, blocks).
+            let script = collection(table, script);
+            let (x, y) = match script
+                .first()
+                .and_then(|position| point(table, table.resolve(position)))
+            {
+                Some(position) => position,
+                None => continue,
+            };
+            let blocks = match script.get(1).map(|blocks| fix_blocks(self.to_json(blocks))) {
+                Some(Value::Array(blocks)) => blocks,
+                _ => continue,
+            };
+            // Comments are saved alongside scripts.
+            if blocks
+                .first()
+                .and_then(|block| block.get(0))
+                .and_then(Value::as_str)
+                == Some("scratchComment")
+            {
+                continue;
+            }
+            scripts.push(json!([x, y, blocks]));
+        }
+        object.insert("scripts".to_string(), Value::Array(scripts));
+
+        let costume = table.field(morph, COSTUME);
+        let mut costumes = vec![];
+        let mut sounds = vec![];
+        let mut current_costume_index = 0;
+        for item in collection(table, table.field(morph, MEDIA)) {
+            let media = table.resolve(item);
+            match table.class_id(media) {
+                Some(CLASS_IMAGE_MEDIA) => {
+                    if std::ptr::eq(media, costume) {
+                        current_costume_index = costumes.len();
+                    }
+                    costumes.push(self.convert_costume(media)?);
+                }
+                Some(CLASS_SOUND_MEDIA) => sounds.push(self.convert_sound(media)?),
+                _ => {}
+            }
+        }
+        object.insert("costumes".to_string(), Value::Array(costumes));
+        object.insert("sounds".to_string(), Value::Array(sounds));
+        object.insert(
+            "currentCostumeIndex".to_string(),
+            json!(current_costume_index),
+        );
+
+        Ok(object)
+    }
+
+    fn convert_sprite(
+        &mut self,
+        sprite: &Obj,
+        stage_origin: (f64, f64),
+    ) -> Result<Value, &'static str> {
+        let table = self.table;
+        let mut object = self.convert_scriptable(sprite)?;
+
+        let scale = point(table, table.field(sprite, SPRITE_SCALE_POINT)).map_or(1.0, |(x, _)| x);
+        let rotation = table
+            .field(sprite, SPRITE_ROTATION_DEGREES)
+            .as_f64()
+            .unwrap_or(0.0);
+        let bounds = rectangle(table, table.field(sprite, MORPH_BOUNDS)).unwrap_or_default();
+        let costume = table.field(sprite, COSTUME);
+        let rotation_center =
+            point(table, table.field(costume, IMAGE_MEDIA_ROTATION_CENTER)).unwrap_or_default();
+        // Morphs are positioned by their bounding box. Unrotated, the sprite's position is its costume's rotation
+        // center; rotated, the bounds are those of the rotated costume, whose center is the best estimate.
+        let (center_x, center_y) = if rotation.rem_euclid(360.0) == 0.0 {
+            (
+                bounds[0] + rotation_center.0 * scale,
+                bounds[1] + rotation_center.1 * scale,
+            )
+        } else {
+            ((bounds[0] + bounds[2]) / 2.0, (bounds[1] + bounds[3]) / 2.0)
+        };
+        // Scratch 1.x headings are rotations from "right", from -180 (exclusive) to 180.
+        let mut direction = (rotation + 90.0).rem_euclid(360.0);
+        if direction > 180.0 {
+            direction -= 360.0;
+        }
+        let flags = match table.field(sprite, MORPH_FLAGS) {
+            Obj::Int(flags) => *flags,
+            _ => 0,
+        };
+
+        object.insert(
+            "scratchX".to_string(),
+            json!(center_x - stage_origin.0 - STAGE_WIDTH / 2.0),
+        );
+        object.insert(
+            "scratchY".to_string(),
+            json!(STAGE_HEIGHT / 2.0 - (center_y - stage_origin.1)),
+        );
+        object.insert("scale".to_string(), json!(scale));
+        object.insert("direction".to_string(), json!(direction));
+        object.insert(
+            "rotationStyle".to_string(),
+            json!(self.string_field(sprite, SPRITE_ROTATION_STYLE)),
+        );
+        object.insert(
+            "isDraggable".to_string(),
+            json!(matches!(
+                table.field(sprite, SPRITE_DRAGGABLE),
+                Obj::Bool(true)
+            )),
+        );
+        object.insert("visible".to_string(), json!(flags & FLAG_HIDDEN == 0));
+        if let Some(volume) = table.field(sprite, SPRITE_VOLUME).as_f64() {
+            object.insert("volume".to_string(), json!(volume));
+        }
+
+        Ok(Value::Object(object))
+    }
+
+    fn convert_stage(&mut self, stage: &Obj) -> Result<Value, &'static str> {
+        let table = self.table;
+        let mut object = self.convert_scriptable(stage)?;
+
+        let bounds = rectangle(table, table.field(stage, MORPH_BOUNDS)).unwrap_or_default();
+        let stage_origin = (bounds[0], bounds[1]);
+
+        // Submorphs are in front-to-back order, and include watchers as well as sprites.
+        let mut sprites: Vec<&Obj> = collection(table, table.field(stage, MORPH_SUBMORPHS))
+            .iter()
+            .map(|submorph| table.resolve(submorph))
+            .filter(|submorph| table.class_id(submorph) == Some(CLASS_SCRATCH_SPRITE))
+            .collect();
+        sprites.reverse();
+        // Hidden sprites may not be in the stage's submorphs; find them in the stage's list of all sprites.
+        for sprite in collection(table, table.field(stage, STAGE_SPRITES)) {
+            let sprite = table.resolve(sprite);
+            if table.class_id(sprite) == Some(CLASS_SCRATCH_SPRITE)
+                && !sprites
+                    .iter()
+                    .any(|existing| std::ptr::eq(*existing, sprite))
+            {
+                sprites.push(sprite);
+            }
+        }
+
+        let children = sprites
+            .into_iter()
+            .map(|sprite| self.convert_sprite(sprite, stage_origin))
+            .collect::<Result<Vec<Value>, &'static str>>()?;
+        object.insert("children".to_string(), Value::Array(children));
+        object.insert(
+            "tempoBPM".to_string(),
+            json!(table.field(stage, STAGE_TEMPO_BPM).as_f64().unwrap_or(60.0)),
+        );
+        if let Some(volume) = table.field(stage, STAGE_VOLUME).as_f64() {
+            object.insert("volume".to_string(), json!(volume));
+        }
+
+        Ok(Value::Object(object))
+    }
+}
@@ -0,0 +1,324 @@
+//! Reader for Squeak's object table serialization (`ObjStream`), which Scratch 1.x used to save projects.
+//!
+//! An object table is a header, an object count, and then that many objects. Each object starts with a class ID.
+//! Simple values (nil, booleans, numbers) are stored inline wherever they appear; everything else is stored once in
+//! the table and referenced by its 1-based index.
+
+use std::convert::TryInto;
+
+pub const CLASS_SCRATCH_SPRITE: u8 = 124;
+pub const CLASS_SCRATCH_STAGE: u8 = 125;
+pub const CLASS_IMAGE_MEDIA: u8 = 162;
+pub const CLASS_SOUND_MEDIA: u8 = 164;
+
+/// A decoded object. References to other objects are left as `Ref`s and looked up through the `ObjTable`.
+#[derive(Debug, Clone)]
+pub enum Obj {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Symbol(String),
+    Bytes(Vec<u8>),
+    /// 16-bit audio samples.
+    SoundBuffer(Vec<i16>),
+    Bitmap(Vec<u32>),
+    /// Array, OrderedCollection, Set, and IdentitySet.
+    Collection(Vec<Obj>),
+    /// Dictionary and IdentityDictionary, as (key, value) pairs.
+    Dictionary(Vec<(Obj, Obj)>),
+    /// Components from 0 to 255.
+    Color {
+        r: u8,
+        g: u8,
+        b: u8,
+        a: u8,
+    },
+    /// Point, Rectangle, Form, and ColorForm, which are stored as fixed lists of fields.
+    Fixed {
+        class_id: u8,
+        fields: Vec<Obj>,
+    },
+    /// An instance of one of Scratch's own classes.
+    User {
+        class_id: u8,
+        version: u8,
+        fields: Vec<Obj>,
+    },
+    /// Index into the object table.
+    Ref(usize),
+}
+
+/// Scratch 1.x strings are Mac OS Roman. The first 128 characters are the same as ASCII.
+const MAC_ROMAN_HIGH: &str = "ÄÅÇÉÑÖÜáàâäãåçéèêëíìîïñóòôöõúùûü†°¢£§•¶ß®©™´¨≠ÆØ∞±≤≥¥µ∂∑∏π∫ªºΩæø¿¡¬√ƒ≈∆«»… ÀÃÕŒœ–—“”‘’÷◊ÿŸ⁄€‹›ﬁﬂ‡·‚„‰ÂÊÁËÈÍÎÏÌÓÔ\u{f8ff}ÒÚÛÙıˆ˜¯˘˙˚¸˝˛ˇ";
+
+fn decode_mac_roman(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| {
+            if b < 0x80 {
+                b as char
+            } else {
+                MAC_ROMAN_HIGH
+                    .chars()
+                    .nth((b - 0x80) as usize)
+                    .unwrap_or('?')
+            }
+        })
+        .collect()
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        let end = self.pos.checked_add(len).ok_or("Truncated object table")?;
+        let bytes = self
+            .data
+            .get(self.pos..end)
+            .ok_or("Truncated object table")?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, &'static str> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, &'static str> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u24(&mut self) -> Result<u32, &'static str> {
+        let bytes = self.bytes(3)?;
+        Ok(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, &'static str> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn len(&mut self) -> Result<usize, &'static str> {
+        let len = self.u32()? as usize;
+        // Every element takes at least a byte, so a longer length can only come from a corrupt file.
+        if len > self.data.len() - self.pos {
+            return Err("Truncated object table");
+        }
+        Ok(len)
+    }
+
+    fn fields(&mut self, count: usize) -> Result<Vec<Obj>, &'static str> {
+        (0..count).map(|_| self.field()).collect()
+    }
+
+    /// Read a field: either an inline value or a reference into the object table.
+    fn field(&mut self) -> Result<Obj, &'static str> {
+        let class_id = self.u8()?;
+        match class_id {
+            99 => Ok(Obj::Ref(self.u24()? as usize)),
+            1..=8 => self.inline_value(class_id),
+            _ => Err("Unexpected object in field"),
+        }
+    }
+
+    fn inline_value(&mut self, class_id: u8) -> Result<Obj, &'static str> {
+        Ok(match class_id {
+            1 => Obj::Nil,
+            2 => Obj::Bool(true),
+            3 => Obj::Bool(false),
+            4 => Obj::Int(self.u32()? as i32 as i64),
+            5 => Obj::Int(self.u16()? as i16 as i64),
+            6 | 7 => {
+                // Large integers are a little-endian magnitude.
+                let len = self.u16()? as usize;
+                let magnitude = self
+                    .bytes(len)?
+                    .iter()
+                    .rev()
+                    .fold(0f64, |acc, &b| acc * 256.0 + b as f64);
+                let value = if class_id == 7 { -magnitude } else { magnitude };
+                if value.abs() < i64::MAX as f64 {
+                    Obj::Int(value as i64)
+                } else {
+                    Obj::Float(value)
+                }
+            }
+            8 => Obj::Float(f64::from_be_bytes(self.bytes(8)?.try_into().unwrap())),
+            _ => unreachable!(),
+        })
+    }
+
+    /// Read a top-level object from the table.
+    fn object(&mut self) -> Result<Obj, &'static str> {
+        let class_id = self.u8()?;
+        Ok(match class_id {
+            1..=8 => self.inline_value(class_id)?,
+            9 | 10 => {
+                let len = self.len()?;
+                let string = decode_mac_roman(self.bytes(len)?);
+                if class_id == 9 {
+                    Obj::String(string)
+                } else {
+                    Obj::Symbol(string)
+                }
+            }
+            11 => {
+                let len = self.len()?;
+                Obj::Bytes(self.bytes(len)?.to_vec())
+            }
+            12 => {
+                let len = self.len()?;
+                Obj::SoundBuffer(
+                    self.bytes(len * 2)?
+                        .chunks_exact(2)
+                        .map(|sample| i16::from_be_bytes([sample[0], sample[1]]))
+                        .collect(),
+                )
+            }
+            13 => {
+                let len = self.len()?;
+                Obj::Bitmap(
+                    self.bytes(len * 4)?
+                        .chunks_exact(4)
+                        .map(|word| u32::from_be_bytes(word.try_into().unwrap()))
+                        .collect(),
+                )
+            }
+            14 => {
+                let len = self.len()?;
+                Obj::String(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+            }
+            20..=23 => {
+                let len = self.len()?;
+                Obj::Collection(self.fields(len)?)
+            }
+            24 | 25 => {
+                let len = self.len()?;
+                let mut entries = Vec::with_capacity(len);
+                for _ in 0..len {
+                    entries.push((self.field()?, self.field()?));
+                }
+                Obj::Dictionary(entries)
+            }
+            30 | 31 => {
+                // 10 bits per component.
+                let rgb = self.u32()?;
+                let component = |shift: u32| (((rgb >> shift) & 0x3ff) * 255 / 1023) as u8;
+                Obj::Color {
+                    r: component(20),
+                    g: component(10),
+                    b: component(0),
+                    a: if class_id == 31 { self.u8()? } else { 255 },
+                }
+            }
+            32..=35 => {
+                let num_fields = match class_id {
+                    32 => 2,
+                    33 => 4,
+                    34 => 5,
+                    _ => 6,
+                };
+                Obj::Fixed {
+                    class_id,
+                    fields: self.fields(num_fields)?,
+                }
+            }
+            100..=255 => {
+                let version = self.u8()?;
+                let num_fields = self.u8()? as usize;
+                Obj::User {
+                    class_id,
+                    version,
+                    fields: self.fields(num_fields)?,
+                }
+            }
+            _ => return Err("Unknown object class"),
+        })
+    }
+
+    fn table(&mut self) -> Result<ObjTable, &'static str> {
+        if self.bytes(10)? != b"ObjS\x01Stch\x01" {
+            return Err("Missing object table header");
+        }
+        let num_objects = self.len()?;
+        let objects = (0..num_objects)
+            .map(|_| self.object())
+            .collect::<Result<Vec<Obj>, &'static str>>()?;
+        Ok(ObjTable { objects })
+    }
+}
+
+pub struct ObjTable {
+    objects: Vec<Obj>,
+}
+
+impl ObjTable {
+    /// The object the table was written from.
+    pub fn root(&self) -> Result<&Obj, &'static str> {
+        self.objects.first().ok_or("Empty object table")
+    }
+
+    /// Follow a reference, or return the object itself if it's an inline value.
+    pub fn resolve<'a>(&'a self, obj: &'a Obj) -> &'a Obj {
+        match obj {
+            Obj::Ref(idx) => self.objects.get(idx.wrapping_sub(1)).unwrap_or(&Obj::Nil),
+            _ => obj,
+        }
+    }
+
+    /// A resolved field of a Scratch class instance, or nil if it's missing (older versions of a class have fewer
+    /// fields).
+    pub fn field<'a>(&'a self, obj: &'a Obj, idx: usize) -> &'a Obj {
+        match obj {
+            Obj::User { fields, .. } | Obj::Fixed { fields, .. } => fields
+                .get(idx)
+                .map_or(&Obj::Nil, |field| self.resolve(field)),
+            _ => &Obj::Nil,
+        }
+    }
+
+    pub fn class_id(&self, obj: &Obj) -> Option<u8> {
+        match self.resolve(obj) {
+            Obj::User { class_id, .. } | Obj::Fixed { class_id, .. } => Some(*class_id),
+            _ => None,
+        }
+    }
+}
+
+impl Obj {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Obj::Int(i) => Some(*i as f64),
+            Obj::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Obj::String(s) | Obj::Symbol(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Read the object tables from a Scratch 1.x project file. The first table holds project info (author, notes,
+/// thumbnail); the second holds the stage and everything in it.
+pub fn read_project(data: &[u8]) -> Result<(ObjTable, ObjTable), &'static str> {
+    let mut reader = Reader { data, pos: 0 };
+    // "ScratchV01" or "ScratchV02"
+    let header = reader.bytes(10)?;
+    if !header.starts_with(b"ScratchV0") {
+        return Err("Not a Scratch 1.x project");
+    }
+    let info_size = reader.u32()? as usize;
+    let info_start = reader.pos;
+    let info = reader.table()?;
+    reader.pos = info_start + info_size;
+    let contents = reader.table()?;
+    Ok((info, contents))
+}
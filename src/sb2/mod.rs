@@ -0,0 +1,582 @@
+//! Scratch 2.0 project support. Rather than building targets directly, sb2 projects are translated into the sb3
+//! `project.json` layout, so they go through exactly the same deserialization path as sb3 projects.
+
+pub mod specmap;
+
+use std::collections::{HashMap, HashSet};
+
+use serde_json::{json, Map, Value};
+
+use specmap::ArgMap;
+
+/// Does this `project.json` use the sb2 layout? sb2 projects are a stage object at the top level, where sb3 projects
+/// have a list of targets.
+pub fn is_sb2_project(project: &Value) -> bool {
+    project.get("objName").is_some() && project.get("targets").is_none()
+}
+
+/// Translate an sb2 `project.json` into the equivalent sb3 one. Asset references point at the sb2 archive's
+/// numbered files (e.g. `0.svg`), so the result must be loaded from the same archive.
+pub fn convert_project(project: &Value) -> Result<Value, &'static str> {
+    let stage = project.as_object().ok_or("sb2 stage is not an object")?;
+
+    let mut converter = Converter::default();
+    let mut stage_target = converter.convert_target(stage, true, 0)?;
+
+    let mut targets = vec![];
+    let children = stage
+        .get("children")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    // Children also include variable watchers and list monitors; only sprites have an objName. They're listed from
+    // back to front.
+    for child in children
+        .iter()
+        .filter_map(Value::as_object)
+        .filter(|child| child.contains_key("objName"))
+    {
+        let layer_order = targets.len() as u64 + 1;
+        targets.push(converter.convert_target(child, false, layer_order)?);
+    }
+
+    // Sprites can create global variables (by referencing ones that don't exist) and broadcasts, so the stage's are
+    // filled in last.
+    stage_target["variables"] = Value::Object(converter.global_variables);
+    stage_target["lists"] = Value::Object(converter.global_lists);
+    stage_target["broadcasts"] = Value::Object(
+        converter
+            .broadcasts
+            .into_iter()
+            .map(|(name, id)| (id, Value::String(name)))
+            .collect(),
+    );
+    targets.insert(0, stage_target);
+
+    let mut extensions: Vec<&str> = converter.extensions.into_iter().collect();
+    extensions.sort_unstable();
+
+    Ok(json!({
+        "targets": targets,
+        "monitors": [],
+        "extensions": extensions,
+        "meta": { "semver": "3.0.0" },
+    }))
+}
+
+/// Variables and lists declared by the target currently being converted, by name.
+#[derive(Default)]
+struct TargetScope {
+    variables: HashMap<String, String>,
+    lists: HashMap<String, String>,
+    blocks: Map<String, Value>,
+    is_stage: bool,
+}
+
+#[derive(Default)]
+struct Converter {
+    next_id: usize,
+    global_variable_ids: HashMap<String, String>,
+    global_list_ids: HashMap<String, String>,
+    global_variables: Map<String, Value>,
+    global_lists: Map<String, Value>,
+    /// Broadcast names to IDs.
+    broadcasts: HashMap<String, String>,
+    extensions: HashSet<&'static str>,
+}
+
+/// The sb3 primitive type code for a shadow block that sb3 stores inline, or None if it's stored as a shadow block.
+fn primitive_code(input_op: &str) -> Option<u64> {
+    match input_op {
+        "math_number" => Some(4),
+        "math_positive_number" => Some(5),
+        "math_whole_number" => Some(6),
+        "math_integer" => Some(7),
+        "math_angle" => Some(8),
+        "colour_picker" => Some(9),
+        "text" => Some(10),
+        "event_broadcast_menu" => Some(11),
+        _ => None,
+    }
+}
+
+/// sb2 stores colors as signed ARGB integers; sb3 stores them as hex strings.
+fn convert_color(value: &Value) -> Value {
+    match value.as_i64() {
+        Some(argb) => Value::String(format!("#{:06x}", argb & 0xff_ffff)),
+        None => value.clone(),
+    }
+}
+
+/// An argument is a nested block if it's an array starting with the block's selector.
+fn is_block(value: &Value) -> bool {
+    value
+        .as_array()
+        .and_then(|arr| arr.first())
+        .is_some_and(Value::is_string)
+}
+
+fn convert_rotation_style(rotation_style: &str) -> &str {
+    match rotation_style {
+        "leftRight" => "left-right",
+        "none" => "don't rotate",
+        _ => "all around",
+    }
+}
+
+/// Split an sb2 asset MD5 such as `"83a9787d4cb6f3b7632b4ddfebf74367.wav"` into its hash and extension.
+fn split_md5(md5: &str) -> Result<(&str, &str), &'static str> {
+    let (hash, ext) = md5
+        .split_once('.')
+        .ok_or("sb2 asset md5 has no extension")?;
+    Ok((hash, ext))
+}
+
+fn convert_costume(costume: &Map<String, Value>) -> Result<Value, &'static str> {
+    let name = costume
+        .get("costumeName")
+        .and_then(Value::as_str)
+        .ok_or("sb2 costume has no name")?;
+    let id = costume
+        .get("baseLayerID")
+        .and_then(Value::as_i64)
+        .ok_or("sb2 costume has no baseLayerID")?;
+    let (hash, ext) = split_md5(
+        costume
+            .get("baseLayerMD5")
+            .and_then(Value::as_str)
+            .ok_or("sb2 costume has no baseLayerMD5")?,
+    )?;
+
+    Ok(json!({
+        "name": name,
+        "assetId": hash,
+        "md5ext": format!("{}.{}", id, ext),
+        "dataFormat": ext,
+        "bitmapResolution": costume.get("bitmapResolution").and_then(Value::as_f64).unwrap_or(1.0),
+        "rotationCenterX": costume.get("rotationCenterX").and_then(Value::as_f64).unwrap_or(0.0),
+        "rotationCenterY": costume.get("rotationCenterY").and_then(Value::as_f64).unwrap_or(0.0),
+    }))
+}
+
+fn convert_sound(sound: &Map<String, Value>) -> Result<Value, &'static str> {
+    let name = sound
+        .get("soundName")
+        .and_then(Value::as_str)
+        .ok_or("sb2 sound has no name")?;
+    let id = sound
+        .get("soundID")
+        .and_then(Value::as_i64)
+        .ok_or("sb2 sound has no soundID")?;
+    let (hash, ext) = split_md5(
+        sound
+            .get("md5")
+            .and_then(Value::as_str)
+            .ok_or("sb2 sound has no md5")?,
+    )?;
+
+    Ok(json!({
+        "name": name,
+        "assetId": hash,
+        "md5ext": format!("{}.{}", id, ext),
+        "dataFormat": ext,
+        "format": sound.get("format").and_then(Value::as_str).unwrap_or(""),
+        "rate": sound.get("rate").and_then(Value::as_u64).unwrap_or(0),
+        "sampleCount": sound.get("sampleCount").and_then(Value::as_u64).unwrap_or(0),
+    }))
+}
+
+impl Converter {
+    fn new_id(&mut self) -> String {
+        self.next_id += 1;
+        format!("sb2-{}", self.next_id)
+    }
+
+    fn convert_target(
+        &mut self,
+        object: &Map<String, Value>,
+        is_stage: bool,
+        layer_order: u64,
+    ) -> Result<Value, &'static str> {
+        let name = object
+            .get("objName")
+            .and_then(Value::as_str)
+            .ok_or("sb2 object has no objName")?;
+
+        let mut scope = TargetScope {
+            is_stage,
+            ..TargetScope::default()
+        };
+        let mut variables = Map::new();
+        let mut lists = Map::new();
+
+        // Variables and lists must be declared before the scripts that reference them are converted.
+        for variable in object
+            .get("variables")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+        {
+            let var_name = variable
+                .get("name")
+                .and_then(Value::as_str)
+                .ok_or("sb2 variable has no name")?;
+            let value = variable.get("value").cloned().unwrap_or_else(|| json!(0));
+            let id = self.new_id();
+            if is_stage {
+                self.global_variable_ids
+                    .insert(var_name.to_string(), id.clone());
+                self.global_variables.insert(id, json!([var_name, value]));
+            } else {
+                scope.variables.insert(var_name.to_string(), id.clone());
+                variables.insert(id, json!([var_name, value]));
+            }
+        }
+        for list in object
+            .get("lists")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+        {
+            let list_name = list
+                .get("listName")
+                .and_then(Value::as_str)
+                .ok_or("sb2 list has no listName")?;
+            let contents = list.get("contents").cloned().unwrap_or_else(|| json!([]));
+            let id = self.new_id();
+            if is_stage {
+                self.global_list_ids
+                    .insert(list_name.to_string(), id.clone());
+                self.global_lists.insert(id, json!([list_name, contents]));
+            } else {
+                scope.lists.insert(list_name.to_string(), id.clone());
+                lists.insert(id, json!([list_name, contents]));
+            }
+        }
+
+        for script in object
+            .get("scripts")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+        {
+            // Scripts are [x, y, [block, block, ...]].
+            let script = script.as_array().ok_or("sb2 script is not an array")?;
+            let stack = script
+                .get(2)
+                .and_then(Value::as_array)
+                .ok_or("sb2 script has no blocks")?;
+            if let Some(top_id) = self.convert_stack(&mut scope, stack, None)? {
+                let top_block = &mut scope.blocks[&top_id];
+                top_block["x"] = script.first().cloned().unwrap_or_else(|| json!(0));
+                top_block["y"] = script.get(1).cloned().unwrap_or_else(|| json!(0));
+            }
+        }
+
+        let costumes = object
+            .get("costumes")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .map(|costume| {
+                convert_costume(costume.as_object().ok_or("sb2 costume is not an object")?)
+            })
+            .collect::<Result<Vec<Value>, &'static str>>()?;
+        let sounds = object
+            .get("sounds")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .map(|sound| convert_sound(sound.as_object().ok_or("sb2 sound is not an object")?))
+            .collect::<Result<Vec<Value>, &'static str>>()?;
+        let current_costume = object
+            .get("currentCostumeIndex")
+            .and_then(Value::as_f64)
+            .unwrap_or(0.0)
+            .floor() as u64;
+
+        let mut target = json!({
+            "isStage": is_stage,
+            "name": name,
+            "variables": variables,
+            "lists": lists,
+            "broadcasts": {},
+            "blocks": scope.blocks,
+            "comments": {},
+            "currentCostume": current_costume,
+            "costumes": costumes,
+            "sounds": sounds,
+            "volume": object.get("volume").and_then(Value::as_f64).unwrap_or(100.0),
+            "layerOrder": layer_order,
+        });
+
+        if is_stage {
+            target["tempo"] = json!(object
+                .get("tempoBPM")
+                .and_then(Value::as_f64)
+                .unwrap_or(60.0));
+        } else {
+            target["visible"] = json!(object
+                .get("visible")
+                .and_then(Value::as_bool)
+                .unwrap_or(true));
+            target["x"] = json!(object
+                .get("scratchX")
+                .and_then(Value::as_f64)
+                .unwrap_or(0.0));
+            target["y"] = json!(object
+                .get("scratchY")
+                .and_then(Value::as_f64)
+                .unwrap_or(0.0));
+            target["size"] =
+                json!(object.get("scale").and_then(Value::as_f64).unwrap_or(1.0) * 100.0);
+            target["direction"] = json!(object
+                .get("direction")
+                .and_then(Value::as_f64)
+                .unwrap_or(90.0));
+            target["draggable"] = json!(object
+                .get("isDraggable")
+                .and_then(Value::as_bool)
+                .unwrap_or(false));
+            target["rotationStyle"] = json!(convert_rotation_style(
+                object
+                    .get("rotationStyle")
+                    .and_then(Value::as_str)
+                    .unwrap_or("normal")
+            ));
+        }
+
+        Ok(target)
+    }
+
+    /// Convert a list of sb2 blocks into a linked sb3 stack, returning the ID of the first block.
+    fn convert_stack(
+        &mut self,
+        scope: &mut TargetScope,
+        stack: &[Value],
+        parent: Option<&str>,
+    ) -> Result<Option<String>, &'static str> {
+        let mut first_id = None;
+        let mut prev_id: Option<String> = None;
+        for block in stack {
+            let parent = prev_id.as_deref().or(parent);
+            let id = self.convert_block(scope, block, parent)?;
+            if let Some(prev_id) = &prev_id {
+                scope.blocks[prev_id]["next"] = Value::String(id.clone());
+            }
+            if first_id.is_none() {
+                first_id = Some(id.clone());
+            }
+            prev_id = Some(id);
+        }
+        Ok(first_id)
+    }
+
+    fn variable_id(&mut self, scope: &TargetScope, name: &str) -> String {
+        if let Some(id) = scope
+            .variables
+            .get(name)
+            .or_else(|| self.global_variable_ids.get(name))
+        {
+            return id.clone();
+        }
+        // Like Scratch, create variables that scripts reference but that don't exist, as globals.
+        let id = self.new_id();
+        self.global_variable_ids
+            .insert(name.to_string(), id.clone());
+        self.global_variables.insert(id.clone(), json!([name, 0]));
+        id
+    }
+
+    fn list_id(&mut self, scope: &TargetScope, name: &str) -> String {
+        if let Some(id) = scope
+            .lists
+            .get(name)
+            .or_else(|| self.global_list_ids.get(name))
+        {
+            return id.clone();
+        }
+        let id = self.new_id();
+        self.global_list_ids.insert(name.to_string(), id.clone());
+        self.global_lists.insert(id.clone(), json!([name, []]));
+        id
+    }
+
+    fn broadcast_id(&mut self, name: &str) -> String {
+        self.broadcasts
+            .entry(name.to_string())
+            .or_insert_with(|| format!("broadcastMsgId-{}", name))
+            .clone()
+    }
+
+    /// Convert an sb2 field value to an sb3 `[value, id]` pair.
+    fn convert_field(
+        &mut self,
+        scope: &TargetScope,
+        opcode: &str,
+        field_name: &str,
+        value: &Value,
+    ) -> Value {
+        let value_str = match value {
+            Value::String(s) => s.clone(),
+            Value::Null => String::new(),
+            other => other.to_string(),
+        };
+        match field_name {
+            "VARIABLE" => {
+                let id = self.variable_id(scope, &value_str);
+                json!([value_str, id])
+            }
+            "LIST" => {
+                let id = self.list_id(scope, &value_str);
+                json!([value_str, id])
+            }
+            "BROADCAST_OPTION" => {
+                let id = self.broadcast_id(&value_str);
+                json!([value_str, id])
+            }
+            // sb3 spells these menu options in uppercase.
+            "WHENGREATERTHANMENU" if opcode == "event_whengreaterthan" => {
+                json!([value_str.to_uppercase(), null])
+            }
+            "CURRENTMENU" if opcode == "sensing_current" => {
+                json!([value_str.to_uppercase().replace(' ', ""), null])
+            }
+            _ => json!([value, null]),
+        }
+    }
+
+    /// Build the shadow for an input, either as an inline primitive or as the ID of a new shadow block.
+    fn convert_shadow(
+        &mut self,
+        scope: &mut TargetScope,
+        input_op: &str,
+        input_name: &str,
+        value: &Value,
+        parent: &str,
+    ) -> Value {
+        let value = match value {
+            Value::Null => json!(""),
+            _ => value.clone(),
+        };
+        match (input_op, primitive_code(input_op)) {
+            ("colour_picker", Some(code)) => json!([code, convert_color(&value)]),
+            ("event_broadcast_menu", Some(code)) => {
+                let name = value
+                    .as_str()
+                    .map_or_else(|| value.to_string(), String::from);
+                let id = self.broadcast_id(&name);
+                json!([code, name, id])
+            }
+            (_, Some(code)) => json!([code, value]),
+            (_, None) => {
+                let id = self.new_id();
+                scope.blocks.insert(
+                    id.clone(),
+                    json!({
+                        "opcode": input_op,
+                        "next": null,
+                        "parent": parent,
+                        "inputs": {},
+                        "fields": { input_name: [value, null] },
+                        "shadow": true,
+                        "topLevel": false,
+                    }),
+                );
+                Value::String(id)
+            }
+        }
+    }
+
+    fn convert_block(
+        &mut self,
+        scope: &mut TargetScope,
+        block: &Value,
+        parent: Option<&str>,
+    ) -> Result<String, &'static str> {
+        let block = block.as_array().ok_or("sb2 block is not an array")?;
+        let selector = block
+            .first()
+            .and_then(Value::as_str)
+            .ok_or("sb2 block has no selector")?;
+        let args = &block[1..];
+        let id = self.new_id();
+
+        // Unknown selectors are passed through unchanged, so they're reported the same way unknown sb3 opcodes are.
+        let block_map = specmap::lookup(selector).unwrap_or(specmap::BlockMap {
+            opcode: "",
+            args: &[],
+            fixed_fields: &[],
+        });
+        let opcode = match (block_map.opcode, scope.is_stage) {
+            ("", _) => selector,
+            // sb2 uses the same "when clicked" hat for sprites and the stage.
+            ("event_whenthisspriteclicked", true) => "event_whenstageclicked",
+            (opcode, _) => opcode,
+        };
+        match opcode.split_once('_') {
+            Some(("music", _)) => {
+                self.extensions.insert("music");
+            }
+            Some(("pen", _)) => {
+                self.extensions.insert("pen");
+            }
+            _ => {}
+        }
+
+        let mut inputs = Map::new();
+        let mut fields = Map::new();
+        for (arg_map, arg) in block_map.args.iter().zip(args) {
+            match *arg_map {
+                ArgMap::Field(field_name) => {
+                    let field = self.convert_field(scope, opcode, field_name, arg);
+                    fields.insert(field_name.to_string(), field);
+                }
+                ArgMap::Input("substack", input_name) => {
+                    let stack = arg.as_array().map(Vec::as_slice).unwrap_or_default();
+                    if let Some(first_id) = self.convert_stack(scope, stack, Some(&id))? {
+                        inputs.insert(input_name.to_string(), json!([2, first_id]));
+                    }
+                }
+                ArgMap::Input("boolean", input_name) => {
+                    if is_block(arg) {
+                        let reporter_id = self.convert_block(scope, arg, Some(&id))?;
+                        inputs.insert(input_name.to_string(), json!([2, reporter_id]));
+                    }
+                }
+                ArgMap::Input(input_op, input_name) => {
+                    let input = if is_block(arg) {
+                        let reporter_id = self.convert_block(scope, arg, Some(&id))?;
+                        let shadow =
+                            self.convert_shadow(scope, input_op, input_name, &Value::Null, &id);
+                        json!([3, reporter_id, shadow])
+                    } else {
+                        let shadow = self.convert_shadow(scope, input_op, input_name, arg, &id);
+                        json!([1, shadow])
+                    };
+                    inputs.insert(input_name.to_string(), input);
+                }
+            }
+        }
+        for (field_name, value) in block_map.fixed_fields {
+            fields.insert(field_name.to_string(), json!([value, null]));
+        }
+
+        scope.blocks.insert(
+            id.clone(),
+            json!({
+                "opcode": opcode,
+                "next": null,
+                "parent": parent,
+                "inputs": inputs,
+                "fields": fields,
+                "shadow": false,
+                "topLevel": parent.is_none(),
+            }),
+        );
+
+        Ok(id)
+    }
+}
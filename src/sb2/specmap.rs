@@ -0,0 +1,427 @@
+//! Translation table from Scratch 2.0 block selectors to Scratch 3.0 opcodes, following scratch-vm's `sb2_specmap`.
+
+/// Where one of an sb2 block's positional arguments ends up in the sb3 block.
+#[derive(Debug, Clone, Copy)]
+pub enum ArgMap {
+    /// An input. The first string is the shadow block's opcode (or "substack"/"boolean" for inputs without a shadow),
+    /// the second is the input name.
+    Input(&'static str, &'static str),
+    /// A field with the given name.
+    Field(&'static str),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BlockMap {
+    pub opcode: &'static str,
+    pub args: &'static [ArgMap],
+    /// Fields that sb2 encoded in the selector itself (e.g. "comeToFront" vs. "goBackByLayers:").
+    pub fixed_fields: &'static [(&'static str, &'static str)],
+}
+
+use ArgMap::{Field, Input};
+
+const fn map(opcode: &'static str, args: &'static [ArgMap]) -> BlockMap {
+    BlockMap {
+        opcode,
+        args,
+        fixed_fields: &[],
+    }
+}
+
+const fn map_with_fields(
+    opcode: &'static str,
+    args: &'static [ArgMap],
+    fixed_fields: &'static [(&'static str, &'static str)],
+) -> BlockMap {
+    BlockMap {
+        opcode,
+        args,
+        fixed_fields,
+    }
+}
+
+/// Look up the sb3 equivalent of an sb2 block selector.
+pub fn lookup(selector: &str) -> Option<BlockMap> {
+    Some(match selector {
+        // Motion
+        "forward:" => map("motion_movesteps", &[Input("math_number", "STEPS")]),
+        "turnRight:" => map("motion_turnright", &[Input("math_number", "DEGREES")]),
+        "turnLeft:" => map("motion_turnleft", &[Input("math_number", "DEGREES")]),
+        "heading:" => map(
+            "motion_pointindirection",
+            &[Input("math_angle", "DIRECTION")],
+        ),
+        "pointTowards:" => map(
+            "motion_pointtowards",
+            &[Input("motion_pointtowards_menu", "TOWARDS")],
+        ),
+        "gotoX:y:" => map(
+            "motion_gotoxy",
+            &[Input("math_number", "X"), Input("math_number", "Y")],
+        ),
+        "gotoSpriteOrMouse:" => map("motion_goto", &[Input("motion_goto_menu", "TO")]),
+        "glideSecs:toX:y:elapsed:from:" => map(
+            "motion_glidesecstoxy",
+            &[
+                Input("math_number", "SECS"),
+                Input("math_number", "X"),
+                Input("math_number", "Y"),
+            ],
+        ),
+        "changeXposBy:" => map("motion_changexby", &[Input("math_number", "DX")]),
+        "xpos:" => map("motion_setx", &[Input("math_number", "X")]),
+        "changeYposBy:" => map("motion_changeyby", &[Input("math_number", "DY")]),
+        "ypos:" => map("motion_sety", &[Input("math_number", "Y")]),
+        "bounceOffEdge" => map("motion_ifonedgebounce", &[]),
+        "setRotationStyle" => map("motion_setrotationstyle", &[Field("STYLE")]),
+        "xpos" => map("motion_xposition", &[]),
+        "ypos" => map("motion_yposition", &[]),
+        "heading" => map("motion_direction", &[]),
+        "scrollRight" => map("motion_scroll_right", &[Input("math_number", "DISTANCE")]),
+        "scrollUp" => map("motion_scroll_up", &[Input("math_number", "DISTANCE")]),
+        "scrollAlign" => map("motion_align_scene", &[Field("ALIGNMENT")]),
+        "xScroll" => map("motion_xscroll", &[]),
+        "yScroll" => map("motion_yscroll", &[]),
+
+        // Looks
+        "say:duration:elapsed:from:" => map(
+            "looks_sayforsecs",
+            &[Input("text", "MESSAGE"), Input("math_number", "SECS")],
+        ),
+        "say:" => map("looks_say", &[Input("text", "MESSAGE")]),
+        "think:duration:elapsed:from:" => map(
+            "looks_thinkforsecs",
+            &[Input("text", "MESSAGE"), Input("math_number", "SECS")],
+        ),
+        "think:" => map("looks_think", &[Input("text", "MESSAGE")]),
+        "show" => map("looks_show", &[]),
+        "hide" => map("looks_hide", &[]),
+        "hideAll" => map("looks_hideallsprites", &[]),
+        "lookLike:" => map(
+            "looks_switchcostumeto",
+            &[Input("looks_costume", "COSTUME")],
+        ),
+        "nextCostume" => map("looks_nextcostume", &[]),
+        "startScene" => map(
+            "looks_switchbackdropto",
+            &[Input("looks_backdrops", "BACKDROP")],
+        ),
+        "changeGraphicEffect:by:" => map(
+            "looks_changeeffectby",
+            &[Field("EFFECT"), Input("math_number", "CHANGE")],
+        ),
+        "setGraphicEffect:to:" => map(
+            "looks_seteffectto",
+            &[Field("EFFECT"), Input("math_number", "VALUE")],
+        ),
+        "filterReset" => map("looks_cleargraphiceffects", &[]),
+        "changeSizeBy:" => map("looks_changesizeby", &[Input("math_number", "CHANGE")]),
+        "setSizeTo:" => map("looks_setsizeto", &[Input("math_number", "SIZE")]),
+        "changeStretchBy:" => map("looks_changestretchby", &[Input("math_number", "CHANGE")]),
+        "setStretchTo:" => map("looks_setstretchto", &[Input("math_number", "STRETCH")]),
+        "comeToFront" => map_with_fields("looks_gotofrontback", &[], &[("FRONT_BACK", "front")]),
+        "goBackByLayers:" => map_with_fields(
+            "looks_goforwardbackwardlayers",
+            &[Input("math_integer", "NUM")],
+            &[("FORWARD_BACKWARD", "backward")],
+        ),
+        "costumeIndex" => {
+            map_with_fields("looks_costumenumbername", &[], &[("NUMBER_NAME", "number")])
+        }
+        "costumeName" => {
+            map_with_fields("looks_costumenumbername", &[], &[("NUMBER_NAME", "name")])
+        }
+        "sceneName" => map_with_fields("looks_backdropnumbername", &[], &[("NUMBER_NAME", "name")]),
+        "backgroundIndex" => map_with_fields(
+            "looks_backdropnumbername",
+            &[],
+            &[("NUMBER_NAME", "number")],
+        ),
+        "nextScene" => map("looks_nextbackdrop", &[]),
+        "startSceneAndWait" => map(
+            "looks_switchbackdroptoandwait",
+            &[Input("looks_backdrops", "BACKDROP")],
+        ),
+        "scale" => map("looks_size", &[]),
+
+        // Sound
+        "playSound:" => map("sound_play", &[Input("sound_sounds_menu", "SOUND_MENU")]),
+        "doPlaySoundAndWait" => map(
+            "sound_playuntildone",
+            &[Input("sound_sounds_menu", "SOUND_MENU")],
+        ),
+        "stopAllSounds" => map("sound_stopallsounds", &[]),
+        "changeVolumeBy:" => map("sound_changevolumeby", &[Input("math_number", "VOLUME")]),
+        "setVolumeTo:" => map("sound_setvolumeto", &[Input("math_number", "VOLUME")]),
+        "volume" => map("sound_volume", &[]),
+
+        // Music. Scratch 2 put these in the sound category; Scratch 3 moved them to the music extension.
+        "playDrum" => map(
+            "music_playDrumForBeats",
+            &[
+                Input("music_menu_DRUM", "DRUM"),
+                Input("math_number", "BEATS"),
+            ],
+        ),
+        "drum:duration:elapsed:from:" => map(
+            "music_midiPlayDrumForBeats",
+            &[Input("math_number", "DRUM"), Input("math_number", "BEATS")],
+        ),
+        "rest:elapsed:from:" => map("music_restForBeats", &[Input("math_number", "BEATS")]),
+        "noteOn:duration:elapsed:from:" => map(
+            "music_playNoteForBeats",
+            &[Input("note", "NOTE"), Input("math_number", "BEATS")],
+        ),
+        "instrument:" => map(
+            "music_setInstrument",
+            &[Input("music_menu_INSTRUMENT", "INSTRUMENT")],
+        ),
+        "midiInstrument:" => map(
+            "music_midiSetInstrument",
+            &[Input("math_number", "INSTRUMENT")],
+        ),
+        "changeTempoBy:" => map("music_changeTempo", &[Input("math_number", "TEMPO")]),
+        "setTempoTo:" => map("music_setTempo", &[Input("math_number", "TEMPO")]),
+        "tempo" => map("music_getTempo", &[]),
+
+        // Pen
+        "clearPenTrails" => map("pen_clear", &[]),
+        "stampCostume" => map("pen_stamp", &[]),
+        "putPenDown" => map("pen_penDown", &[]),
+        "putPenUp" => map("pen_penUp", &[]),
+        "penColor:" => map("pen_setPenColorToColor", &[Input("colour_picker", "COLOR")]),
+        "changePenHueBy:" => map("pen_changePenHueBy", &[Input("math_number", "HUE")]),
+        "setPenHueTo:" => map("pen_setPenHueToNumber", &[Input("math_number", "HUE")]),
+        "changePenShadeBy:" => map("pen_changePenShadeBy", &[Input("math_number", "SHADE")]),
+        "setPenShadeTo:" => map("pen_setPenShadeToNumber", &[Input("math_number", "SHADE")]),
+        "changePenSizeBy:" => map("pen_changePenSizeBy", &[Input("math_number", "SIZE")]),
+        "penSize:" => map("pen_setPenSizeTo", &[Input("math_number", "SIZE")]),
+
+        // Events
+        "whenGreenFlag" => map("event_whenflagclicked", &[]),
+        "whenKeyPressed" => map("event_whenkeypressed", &[Field("KEY_OPTION")]),
+        "whenClicked" => map("event_whenthisspriteclicked", &[]),
+        "whenSceneStarts" => map("event_whenbackdropswitchesto", &[Field("BACKDROP")]),
+        "whenSensorGreaterThan" => map(
+            "event_whengreaterthan",
+            &[Field("WHENGREATERTHANMENU"), Input("math_number", "VALUE")],
+        ),
+        "whenIReceive" => map("event_whenbroadcastreceived", &[Field("BROADCAST_OPTION")]),
+        "broadcast:" => map(
+            "event_broadcast",
+            &[Input("event_broadcast_menu", "BROADCAST_INPUT")],
+        ),
+        "doBroadcastAndWait" => map(
+            "event_broadcastandwait",
+            &[Input("event_broadcast_menu", "BROADCAST_INPUT")],
+        ),
+
+        // Control
+        "wait:elapsed:from:" => map("control_wait", &[Input("math_positive_number", "DURATION")]),
+        "doRepeat" => map(
+            "control_repeat",
+            &[
+                Input("math_whole_number", "TIMES"),
+                Input("substack", "SUBSTACK"),
+            ],
+        ),
+        "doForever" => map("control_forever", &[Input("substack", "SUBSTACK")]),
+        "doIf" => map(
+            "control_if",
+            &[Input("boolean", "CONDITION"), Input("substack", "SUBSTACK")],
+        ),
+        "doIfElse" => map(
+            "control_if_else",
+            &[
+                Input("boolean", "CONDITION"),
+                Input("substack", "SUBSTACK"),
+                Input("substack", "SUBSTACK2"),
+            ],
+        ),
+        "doWaitUntil" => map("control_wait_until", &[Input("boolean", "CONDITION")]),
+        "doUntil" => map(
+            "control_repeat_until",
+            &[Input("boolean", "CONDITION"), Input("substack", "SUBSTACK")],
+        ),
+        "doWhile" => map(
+            "control_while",
+            &[Input("boolean", "CONDITION"), Input("substack", "SUBSTACK")],
+        ),
+        "doForLoop" => map(
+            "control_for_each",
+            &[
+                Field("VARIABLE"),
+                Input("math_whole_number", "VALUE"),
+                Input("substack", "SUBSTACK"),
+            ],
+        ),
+        "stopScripts" => map("control_stop", &[Field("STOP_OPTION")]),
+        "stopAll" => map_with_fields("control_stop", &[], &[("STOP_OPTION", "all")]),
+        "doReturn" => map_with_fields("control_stop", &[], &[("STOP_OPTION", "this script")]),
+        "whenCloned" => map("control_start_as_clone", &[]),
+        "createCloneOf" => map(
+            "control_create_clone_of",
+            &[Input("control_create_clone_of_menu", "CLONE_OPTION")],
+        ),
+        "deleteClone" => map("control_delete_this_clone", &[]),
+        "COUNT" => map("control_get_counter", &[]),
+        "INCR_COUNT" => map("control_incr_counter", &[]),
+        "CLR_COUNT" => map("control_clear_counter", &[]),
+        "warpSpeed" => map("control_all_at_once", &[Input("substack", "SUBSTACK")]),
+
+        // Sensing
+        "touching:" => map(
+            "sensing_touchingobject",
+            &[Input("sensing_touchingobjectmenu", "TOUCHINGOBJECTMENU")],
+        ),
+        "touchingColor:" => map("sensing_touchingcolor", &[Input("colour_picker", "COLOR")]),
+        "color:sees:" => map(
+            "sensing_coloristouchingcolor",
+            &[
+                Input("colour_picker", "COLOR"),
+                Input("colour_picker", "COLOR2"),
+            ],
+        ),
+        "distanceTo:" => map(
+            "sensing_distanceto",
+            &[Input("sensing_distancetomenu", "DISTANCETOMENU")],
+        ),
+        "doAsk" => map("sensing_askandwait", &[Input("text", "QUESTION")]),
+        "answer" => map("sensing_answer", &[]),
+        "keyPressed:" => map(
+            "sensing_keypressed",
+            &[Input("sensing_keyoptions", "KEY_OPTION")],
+        ),
+        "mousePressed" => map("sensing_mousedown", &[]),
+        "mouseX" => map("sensing_mousex", &[]),
+        "mouseY" => map("sensing_mousey", &[]),
+        "soundLevel" => map("sensing_loudness", &[]),
+        "isLoud" => map("sensing_loud", &[]),
+        "timer" => map("sensing_timer", &[]),
+        "timerReset" => map("sensing_resettimer", &[]),
+        "getAttribute:of:" => map(
+            "sensing_of",
+            &[Field("PROPERTY"), Input("sensing_of_object_menu", "OBJECT")],
+        ),
+        "timeAndDate" => map("sensing_current", &[Field("CURRENTMENU")]),
+        "timestamp" => map("sensing_dayssince2000", &[]),
+        "getUserName" => map("sensing_username", &[]),
+
+        // Operators
+        "+" => map(
+            "operator_add",
+            &[Input("math_number", "NUM1"), Input("math_number", "NUM2")],
+        ),
+        "-" => map(
+            "operator_subtract",
+            &[Input("math_number", "NUM1"), Input("math_number", "NUM2")],
+        ),
+        "*" => map(
+            "operator_multiply",
+            &[Input("math_number", "NUM1"), Input("math_number", "NUM2")],
+        ),
+        "/" => map(
+            "operator_divide",
+            &[Input("math_number", "NUM1"), Input("math_number", "NUM2")],
+        ),
+        "randomFrom:to:" => map(
+            "operator_random",
+            &[Input("math_number", "FROM"), Input("math_number", "TO")],
+        ),
+        "<" => map(
+            "operator_lt",
+            &[Input("text", "OPERAND1"), Input("text", "OPERAND2")],
+        ),
+        "=" => map(
+            "operator_equals",
+            &[Input("text", "OPERAND1"), Input("text", "OPERAND2")],
+        ),
+        ">" => map(
+            "operator_gt",
+            &[Input("text", "OPERAND1"), Input("text", "OPERAND2")],
+        ),
+        "&" => map(
+            "operator_and",
+            &[Input("boolean", "OPERAND1"), Input("boolean", "OPERAND2")],
+        ),
+        "|" => map(
+            "operator_or",
+            &[Input("boolean", "OPERAND1"), Input("boolean", "OPERAND2")],
+        ),
+        "not" => map("operator_not", &[Input("boolean", "OPERAND")]),
+        "concatenate:with:" => map(
+            "operator_join",
+            &[Input("text", "STRING1"), Input("text", "STRING2")],
+        ),
+        "letter:of:" => map(
+            "operator_letter_of",
+            &[
+                Input("math_whole_number", "LETTER"),
+                Input("text", "STRING"),
+            ],
+        ),
+        "stringLength:" => map("operator_length", &[Input("text", "STRING")]),
+        "%" => map(
+            "operator_mod",
+            &[Input("math_number", "NUM1"), Input("math_number", "NUM2")],
+        ),
+        "rounded" => map("operator_round", &[Input("math_number", "NUM")]),
+        "computeFunction:of:" => map(
+            "operator_mathop",
+            &[Field("OPERATOR"), Input("math_number", "NUM")],
+        ),
+
+        // Variables and lists
+        "readVariable" => map("data_variable", &[Field("VARIABLE")]),
+        "setVar:to:" => map(
+            "data_setvariableto",
+            &[Field("VARIABLE"), Input("text", "VALUE")],
+        ),
+        "changeVar:by:" => map(
+            "data_changevariableby",
+            &[Field("VARIABLE"), Input("math_number", "VALUE")],
+        ),
+        "showVariable:" => map("data_showvariable", &[Field("VARIABLE")]),
+        "hideVariable:" => map("data_hidevariable", &[Field("VARIABLE")]),
+        "contentsOfList:" => map("data_listcontents", &[Field("LIST")]),
+        "append:toList:" => map("data_addtolist", &[Input("text", "ITEM"), Field("LIST")]),
+        "deleteLine:ofList:" => map(
+            "data_deleteoflist",
+            &[Input("math_integer", "INDEX"), Field("LIST")],
+        ),
+        "insert:at:ofList:" => map(
+            "data_insertatlist",
+            &[
+                Input("text", "ITEM"),
+                Input("math_integer", "INDEX"),
+                Field("LIST"),
+            ],
+        ),
+        "setLine:ofList:to:" => map(
+            "data_replaceitemoflist",
+            &[
+                Input("math_integer", "INDEX"),
+                Field("LIST"),
+                Input("text", "ITEM"),
+            ],
+        ),
+        "getLine:ofList:" => map(
+            "data_itemoflist",
+            &[Input("math_integer", "INDEX"), Field("LIST")],
+        ),
+        "lineCountOfList:" => map("data_lengthoflist", &[Field("LIST")]),
+        "list:contains:" => map(
+            "data_listcontainsitem",
+            &[Field("LIST"), Input("text", "ITEM")],
+        ),
+        "showList:" => map("data_showlist", &[Field("LIST")]),
+        "hideList:" => map("data_hidelist", &[Field("LIST")]),
+
+        // Custom blocks. Their arguments are described by the procedure's spec string rather than by this table.
+        "procDef" => map("procedures_definition", &[]),
+        "call" => map("procedures_call", &[]),
+        "getParam" => map("argument_reporter_string_number", &[Field("VALUE")]),
+
+        _ => return None,
+    })
+}
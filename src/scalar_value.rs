@@ -65,6 +65,22 @@ impl TryFrom<&Value> for ScalarValue {
     }
 }
 
+impl From<&ScalarValue> for Value {
+    fn from(value: &ScalarValue) -> Value {
+        match value {
+            ScalarValue::Bool(v) => Value::Bool(*v),
+            // Whole numbers are written without a decimal point, like Scratch does.
+            ScalarValue::Num(v) if v.fract() == 0.0 && v.abs() < 9007199254740992.0 => {
+                Value::from(*v as i64)
+            }
+            ScalarValue::Num(v) if v.is_finite() => Value::from(*v),
+            // JSON has no NaN or infinities; Scratch casts these strings back to the same numbers.
+            ScalarValue::Num(v) => Value::String(v.to_string().replace("inf", "Infinity")),
+            ScalarValue::Text(v) => Value::String(v.as_str().to_string()),
+        }
+    }
+}
+
 impl From<&ScalarValue> for bool {
     fn from(value: &ScalarValue) -> bool {
         match value {
@@ -74,7 +90,7 @@ impl From<&ScalarValue> for bool {
                 let s = v.as_str();
                 // TODO: Scratch's code doesn't check for the string "-0", but
                 // tests seem to show that it is cast to false.
-                !(s == "" || s == "0" || s == "-0" || s == "false")
+                !(s.is_empty() || s == "0" || s == "-0" || s == "false")
             }
         }
     }
@@ -0,0 +1,613 @@
+//! Saving projects as sb3 files: a zip containing `project.json` and every costume and sound, named by MD5.
+
+use crate::{
+    data::asset::Asset,
+    engine::{
+        costume::Costume, monitor::Monitor, project::Project, sound::Sound, sprite::Sprite,
+        target::Target,
+    },
+    runtime::Runtime,
+    scalar_value::ScalarValue,
+};
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    io::{Seek, Write},
+};
+
+use serde_json::{json, Map, Value};
+use zip::{write::FileOptions, ZipWriter};
+
+/// Opcode prefixes of the blocks built into Scratch. Any other prefix is an extension, which has to be listed in the
+/// project's `extensions` for Scratch to load it.
+const CORE_CATEGORIES: &[&str] = &[
+    "argument",
+    "colour",
+    "control",
+    "data",
+    "event",
+    "looks",
+    "math",
+    "motion",
+    "operator",
+    "procedures",
+    "sensing",
+    "sound",
+];
+
+/// Blocks that project.json stores as compact `[type, value]` arrays rather than as block objects, along with the
+/// field that holds their value.
+fn primitive_info(opcode: &str) -> Option<(u8, &'static str)> {
+    Some(match opcode {
+        "math_number" => (4, "NUM"),
+        "math_positive_number" => (5, "NUM"),
+        "math_whole_number" => (6, "NUM"),
+        "math_integer" => (7, "NUM"),
+        "math_angle" => (8, "NUM"),
+        "colour_picker" => (9, "COLOUR"),
+        "text" => (10, "TEXT"),
+        "event_broadcast_menu" => (11, "BROADCAST_OPTION"),
+        "data_variable" => (12, "VARIABLE"),
+        "data_listcontents" => (13, "LIST"),
+        _ => return None,
+    })
+}
+
+/// The compact form of a primitive block, or None if it isn't one. Broadcasts, variables, and lists also carry their
+/// ID.
+fn compress_primitive(block: &Value) -> Option<Vec<Value>> {
+    let (code, field_name) = primitive_info(block.get("opcode")?.as_str()?)?;
+    let field = block.get("fields")?.get(field_name)?.as_array()?;
+    let mut primitive = vec![Value::from(code), field.first()?.clone()];
+    if code >= 11 {
+        primitive.push(field.get(1)?.clone());
+    }
+    Some(primitive)
+}
+
+/// Replace primitive blocks with the compact form that Scratch saves: inline in the inputs that use them, or as bare
+/// `[type, value, id, x, y]` arrays if they're top-level. Blocks that are already compact are left alone.
+fn compress_blocks(blocks: &Map<String, Value>) -> Map<String, Value> {
+    let mut compressed = Map::new();
+    let mut inlined = HashSet::new();
+
+    for (id, block) in blocks {
+        let mut block = block.clone();
+        if compress_primitive(&block).is_some() {
+            continue;
+        }
+        if let Some(Value::Object(inputs)) = block.get_mut("inputs") {
+            for input in inputs.values_mut() {
+                if let Value::Array(input) = input {
+                    for descriptor in input.iter_mut().skip(1) {
+                        let primitive = descriptor
+                            .as_str()
+                            .and_then(|input_id| blocks.get(input_id))
+                            .and_then(compress_primitive);
+                        if let Some(primitive) = primitive {
+                            inlined.insert(descriptor.as_str().unwrap().to_string());
+                            *descriptor = Value::Array(primitive);
+                        }
+                    }
+                }
+            }
+        }
+        compressed.insert(id.clone(), block);
+    }
+
+    for (id, block) in blocks {
+        let primitive = match compress_primitive(block) {
+            Some(primitive) => primitive,
+            None => continue,
+        };
+        if inlined.contains(id) {
+            continue;
+        }
+        if block.get("topLevel").and_then(Value::as_bool) == Some(true) {
+            let mut primitive = primitive;
+            for coord in ["x", "y"] {
+                primitive.push(Value::from(
+                    block
+                        .get(coord)
+                        .and_then(Value::as_f64)
+                        .unwrap_or(0.0)
+                        .round() as i64,
+                ));
+            }
+            compressed.insert(id.clone(), Value::Array(primitive));
+        } else {
+            // Not used by any block; keep it as-is rather than losing it.
+            compressed.insert(id.clone(), block.clone());
+        }
+    }
+
+    compressed
+}
+
+/// A number as Scratch writes it: without a decimal point if it's whole.
+fn number(value: f64) -> Value {
+    Value::from(&ScalarValue::Num(value))
+}
+
+/// Add the entries whose values are present to a JSON object, leaving out the ones that the project didn't have when it
+/// was loaded.
+fn extend_present(
+    object: &mut Value,
+    entries: impl IntoIterator<Item = (&'static str, Option<Value>)>,
+) {
+    if let Value::Object(object) = object {
+        for (key, value) in entries {
+            if let Some(value) = value {
+                object.insert(key.to_string(), value);
+            }
+        }
+    }
+}
+
+fn asset_file_name(asset: &Asset) -> String {
+    format!("{:x}.{}", asset.md5_digest, asset.asset_type.data_format())
+}
+
+fn serialize_costume(costume: &Costume) -> Value {
+    let costume_asset = &costume.costume_asset;
+    let mut serialized = json!({
+        "name": costume_asset.name,
+        "dataFormat": costume_asset.asset.asset_type.data_format(),
+        "assetId": format!("{:x}", costume_asset.asset.md5_digest),
+        "md5ext": asset_file_name(&costume_asset.asset),
+        "rotationCenterX": number(costume_asset.rotation_center.0),
+        "rotationCenterY": number(costume_asset.rotation_center.1),
+    });
+    extend_present(
+        &mut serialized,
+        [(
+            "bitmapResolution",
+            costume_asset.bitmap_resolution.map(number),
+        )],
+    );
+    serialized
+}
+
+fn serialize_sound(sound: &Sound) -> Value {
+    let sound_asset = &sound.sound_asset;
+    let mut serialized = json!({
+        "name": sound_asset.name,
+        "assetId": format!("{:x}", sound_asset.asset.md5_digest),
+        "dataFormat": sound_asset.asset.asset_type.data_format(),
+        "md5ext": asset_file_name(&sound_asset.asset),
+    });
+    extend_present(
+        &mut serialized,
+        [
+            ("format", sound_asset.format.as_deref().map(Value::from)),
+            ("rate", sound_asset.rate.map(Value::from)),
+            ("sampleCount", sound_asset.sample_count.map(Value::from)),
+        ],
+    );
+    serialized
+}
+
+/// Serialize a target. If `live` is given, its current state (position, costume, variable values, etc.) is saved in
+/// place of the state the target was loaded with.
+fn serialize_target(target: &Target, live: Option<&Sprite>, layer_order: u32, tempo: f64) -> Value {
+    let variables: Map<String, Value> = target
+        .variables
+        .iter()
+        .enumerate()
+        .map(|(idx, variable)| {
+            let value = live.map_or(&variable.value, |sprite| &sprite.variables[idx]);
+            (
+                variable.id.clone(),
+                json!([variable.name, Value::from(value)]),
+            )
+        })
+        .collect();
+    let lists: Map<String, Value> = target
+        .lists
+        .iter()
+        .map(|list| {
+            let contents: Vec<Value> = list.contents.iter().map(Value::from).collect();
+            (list.id.clone(), json!([list.name, contents]))
+        })
+        .collect();
+    let broadcasts: Map<String, Value> = target
+        .broadcasts
+        .iter()
+        .map(|broadcast| (broadcast.id.clone(), Value::from(broadcast.name.as_str())))
+        .collect();
+
+    let mut serialized = json!({
+        "isStage": target.is_stage,
+        "name": target.name,
+        "variables": variables,
+        "lists": lists,
+        "broadcasts": broadcasts,
+        "blocks": compress_blocks(&target.blocks),
+        "comments": target.comments,
+        "currentCostume": live.map_or(target.current_costume, |sprite| sprite.costume),
+        "costumes": target.costumes.iter().map(serialize_costume).collect::<Vec<Value>>(),
+        "sounds": target.sounds.iter().map(serialize_sound).collect::<Vec<Value>>(),
+        "volume": number(live.map_or(target.volume, |sprite| sprite.volume)),
+        "layerOrder": layer_order,
+    });
+    let state = if target.is_stage {
+        let mut state = json!({ "tempo": number(tempo) });
+        extend_present(
+            &mut state,
+            [
+                ("videoTransparency", target.video_transparency.map(number)),
+                ("videoState", target.video_state.as_deref().map(Value::from)),
+                (
+                    "textToSpeechLanguage",
+                    target
+                        .text_to_speech_language
+                        .as_ref()
+                        .map(|language| Value::from(language.as_deref())),
+                ),
+            ],
+        );
+        state
+    } else {
+        json!({
+            "visible": live.map_or(target.visible, |sprite| sprite.visible),
+            "x": number(live.map_or(target.x, |sprite| sprite.x)),
+            "y": number(live.map_or(target.y, |sprite| sprite.y)),
+            "size": number(live.map_or(target.size, |sprite| sprite.size)),
+            "direction": number(live.map_or(target.direction, |sprite| sprite.direction)),
+            "draggable": live.map_or(target.draggable, |sprite| sprite.draggable),
+            "rotationStyle": target.rotation_style,
+        })
+    };
+    if let (Value::Object(serialized), Value::Object(state)) = (&mut serialized, state) {
+        serialized.extend(state);
+    }
+
+    serialized
+}
+
+/// The extensions the project was loaded with, plus any that blocks added since (e.g. by importing a sprite) use.
+fn serialize_extensions<'t>(
+    project: &Project,
+    targets: impl Iterator<Item = &'t Target>,
+) -> Vec<String> {
+    let extensions: BTreeSet<String> = targets
+        .flat_map(|target| target.blocks.values())
+        .filter_map(|block| block.get("opcode")?.as_str())
+        .filter_map(|opcode| opcode.split_once('_'))
+        .map(|(prefix, _)| prefix)
+        .filter(|prefix| !CORE_CATEGORIES.contains(prefix))
+        .map(String::from)
+        .chain(project.extensions.iter().cloned())
+        .collect();
+    extensions.into_iter().collect()
+}
+
+fn serialize_monitor(monitor: &Monitor) -> Value {
+    json!({
+        "id": monitor.id,
+        "mode": monitor.mode.name(),
+        "opcode": monitor.opcode,
+        "params": monitor.params,
+        "spriteName": monitor.sprite_name,
+        "value": monitor.value,
+        "width": number(monitor.width),
+        "height": number(monitor.height),
+        "x": number(monitor.x),
+        "y": number(monitor.y),
+        "visible": monitor.visible,
+        "sliderMin": number(monitor.slider_min),
+        "sliderMax": number(monitor.slider_max),
+        "isDiscrete": monitor.is_discrete,
+    })
+}
+
+/// Write project.json and every asset the targets use to an sb3 archive.
+fn write_sb3<'t, W: Write + Seek>(
+    project_json: &Value,
+    targets: impl Iterator<Item = &'t Target>,
+    writer: W,
+) -> Result<(), &'static str> {
+    let mut zip = ZipWriter::new(writer);
+    let options = FileOptions::default();
+
+    zip.start_file("project.json", options)
+        .map_err(|_| "Could not write project.json")?;
+    serde_json::to_writer(&mut zip, project_json).map_err(|_| "Could not write project.json")?;
+
+    // Targets often share assets; only store each one once.
+    let mut written = HashSet::new();
+    for target in targets {
+        let costume_assets = target
+            .costumes
+            .iter()
+            .map(|costume| &costume.costume_asset.asset);
+        let sound_assets = target.sounds.iter().map(|sound| &sound.sound_asset.asset);
+        for asset in costume_assets.chain(sound_assets) {
+            let file_name = asset_file_name(asset);
+            if !written.insert(file_name.clone()) {
+                continue;
+            }
+            zip.start_file(file_name, options)
+                .map_err(|_| "Could not write asset")?;
+            zip.write_all(&asset.data)
+                .map_err(|_| "Could not write asset")?;
+        }
+    }
+
+    zip.finish().map_err(|_| "Could not write sb3")?;
+    Ok(())
+}
+
+fn project_json(targets: Vec<Value>, project: &Project, extensions: Vec<String>) -> Value {
+    // Keep whatever saved the project originally, so that saving doesn't change it. Scratch needs the semver to load
+    // the project, so projects that don't say (e.g. ones converted from older formats) get the one Scratch 3 writes.
+    let mut meta = json!({ "semver": project.meta.semver.as_deref().unwrap_or("3.0.0") });
+    extend_present(
+        &mut meta,
+        [
+            ("vm", project.meta.vm.as_deref().map(Value::from)),
+            ("agent", project.meta.agent.as_deref().map(Value::from)),
+        ],
+    );
+    json!({
+        "targets": targets,
+        "monitors": project.monitors.iter().map(serialize_monitor).collect::<Vec<Value>>(),
+        "extensions": extensions,
+        "meta": meta,
+    })
+}
+
+/// Save a project as it was loaded.
+pub fn serialize_project<W: Write + Seek>(
+    project: &Project,
+    writer: W,
+) -> Result<(), &'static str> {
+    let stage_tempo = project
+        .targets
+        .iter()
+        .find(|target| target.is_stage)
+        .map_or(60.0, |stage| stage.tempo);
+    let targets = project
+        .targets
+        .iter()
+        .map(|target| serialize_target(target, None, target.layer_order, stage_tempo))
+        .collect();
+    let json = project_json(
+        targets,
+        project,
+        serialize_extensions(project, project.targets.iter()),
+    );
+
+    write_sb3(&json, project.targets.iter(), writer)
+}
+
+/// Each sprite's current layer order, by execution context. Sprites can be moved in front of each other (e.g. by
+/// dragging them), so this comes from the order the renderer draws them in rather than the order they were loaded in.
+fn layer_orders(runtime: &Runtime) -> Vec<u32> {
+    let draw_order = runtime.renderer.borrow().scene().draw_order();
+    let mut by_layer: Vec<usize> = (0..runtime.exec_contexts.len()).collect();
+    by_layer.sort_by_key(|&ctx_idx| {
+        let drawable = runtime.exec_contexts[ctx_idx].sprite.drawable;
+        draw_order.iter().position(|id| *id == drawable)
+    });
+
+    let mut layer_orders = vec![0; by_layer.len()];
+    for (layer_order, ctx_idx) in by_layer.into_iter().enumerate() {
+        layer_orders[ctx_idx] = layer_order as u32;
+    }
+    layer_orders
+}
+
+/// Save a running project exactly where it is: every sprite's current position, costume, variable values, and so on
+/// are saved as its starting state. Sprites added to the runtime after it started are included.
+pub fn serialize_runtime<W: Write + Seek>(
+    runtime: &Runtime,
+    writer: W,
+) -> Result<(), &'static str> {
+    let targets = runtime
+        .exec_contexts
+        .iter()
+        .zip(layer_orders(runtime))
+        .map(|(ctx, layer_order)| {
+            serialize_target(
+                ctx.sprite.target,
+                Some(&ctx.sprite),
+                layer_order,
+                runtime.tempo,
+            )
+        })
+        .collect();
+    let json = project_json(
+        targets,
+        runtime.project,
+        serialize_extensions(runtime.project, runtime.targets().into_iter()),
+    );
+
+    write_sb3(&json, runtime.targets().into_iter(), writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        audio::wav::write_wav,
+        deserialize::{deserialize_project, LoadOptions},
+        engine::engine_data::EngineData,
+        renderer::headless::HeadlessRenderer,
+    };
+
+    use std::io::{Cursor, Read};
+
+    use zip::ZipArchive;
+
+    const SVG: &[u8] = br#"<svg xmlns="http://www.w3.org/2000/svg" width="2" height="2"></svg>"#;
+
+    fn md5_hex(data: &[u8]) -> String {
+        format!("{:x}", md5::compute(data))
+    }
+
+    fn read_project_json(sb3: &[u8]) -> Value {
+        let mut archive = ZipArchive::new(Cursor::new(sb3)).unwrap();
+        let mut json = String::new();
+        archive
+            .by_name("project.json")
+            .unwrap()
+            .read_to_string(&mut json)
+            .unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    /// Load an sb3 and save it again.
+    fn resave(sb3: &[u8]) -> Vec<u8> {
+        let mut renderer = HeadlessRenderer::new((480, 360));
+        let project = deserialize_project(
+            &mut ZipArchive::new(Cursor::new(sb3)).unwrap(),
+            &LoadOptions::default(),
+            &EngineData::new(),
+            &mut renderer,
+        )
+        .unwrap();
+        let mut saved = Cursor::new(vec![]);
+        serialize_project(&project, &mut saved).unwrap();
+        saved.into_inner()
+    }
+
+    #[test]
+    fn round_trip_keeps_project_json() {
+        let mut wav = vec![];
+        write_wav(&mut wav, &[0.0, 0.0], 22050).unwrap();
+        let (svg_md5, wav_md5) = (md5_hex(SVG), md5_hex(&wav));
+
+        // Written the way Scratch writes it: the stage has every optional field, the sprite's costume and sound have
+        // none, and whole numbers have no decimal point.
+        let original = json!({
+            "targets": [
+                {
+                    "isStage": true,
+                    "name": "Stage",
+                    "variables": { "var": ["my variable", 0] },
+                    "lists": { "list": ["my list", ["a", 1.5]] },
+                    "broadcasts": { "msg": "message1" },
+                    "blocks": {},
+                    "comments": {},
+                    "currentCostume": 0,
+                    "costumes": [{
+                        "name": "backdrop1",
+                        "bitmapResolution": 1,
+                        "dataFormat": "svg",
+                        "assetId": svg_md5,
+                        "md5ext": format!("{}.svg", svg_md5),
+                        "rotationCenterX": 240,
+                        "rotationCenterY": 180,
+                    }],
+                    "sounds": [{
+                        "name": "pop",
+                        "assetId": wav_md5,
+                        "dataFormat": "wav",
+                        "format": "adpcm",
+                        "rate": 22050,
+                        "sampleCount": 1,
+                        "md5ext": format!("{}.wav", wav_md5),
+                    }],
+                    "volume": 100,
+                    "layerOrder": 0,
+                    "tempo": 60,
+                    "videoTransparency": 20,
+                    "videoState": "off",
+                    "textToSpeechLanguage": null,
+                },
+                {
+                    "isStage": false,
+                    "name": "Sprite1",
+                    "variables": {},
+                    "lists": {},
+                    "broadcasts": {},
+                    "blocks": {
+                        "flag": {
+                            "opcode": "event_whenflagclicked",
+                            "next": "move",
+                            "parent": null,
+                            "inputs": {},
+                            "fields": {},
+                            "shadow": false,
+                            "topLevel": true,
+                            "x": 0,
+                            "y": 0,
+                        },
+                        "move": {
+                            "opcode": "motion_movesteps",
+                            "next": null,
+                            "parent": "flag",
+                            "inputs": { "STEPS": [1, [4, "10"]] },
+                            "fields": {},
+                            "shadow": false,
+                            "topLevel": false,
+                        },
+                    },
+                    "comments": {},
+                    "currentCostume": 0,
+                    "costumes": [{
+                        "name": "costume1",
+                        "dataFormat": "svg",
+                        "assetId": svg_md5,
+                        "md5ext": format!("{}.svg", svg_md5),
+                        "rotationCenterX": 1,
+                        "rotationCenterY": 1.5,
+                    }],
+                    "sounds": [{
+                        "name": "pop",
+                        "assetId": wav_md5,
+                        "dataFormat": "wav",
+                        "md5ext": format!("{}.wav", wav_md5),
+                    }],
+                    "volume": 100,
+                    "layerOrder": 1,
+                    "visible": true,
+                    "x": 5,
+                    "y": -10.5,
+                    "size": 100,
+                    "direction": 90,
+                    "draggable": false,
+                    "rotationStyle": "all around",
+                },
+            ],
+            "monitors": [{
+                "id": "var",
+                "mode": "default",
+                "opcode": "data_variable",
+                "params": { "VARIABLE": "my variable" },
+                "spriteName": null,
+                "value": 0,
+                "width": 0,
+                "height": 0,
+                "x": 5,
+                "y": 5,
+                "visible": true,
+                "sliderMin": 0,
+                "sliderMax": 100,
+                "isDiscrete": true,
+            }],
+            "extensions": [],
+            "meta": { "semver": "3.0.0" },
+        });
+
+        let mut sb3 = Cursor::new(vec![]);
+        let mut zip = ZipWriter::new(&mut sb3);
+        let files = [
+            ("project.json", original.to_string().into_bytes()),
+            (&format!("{}.svg", svg_md5), SVG.to_vec()),
+            (&format!("{}.wav", wav_md5), wav),
+        ];
+        for (name, data) in files {
+            zip.start_file(name, FileOptions::default()).unwrap();
+            zip.write_all(&data).unwrap();
+        }
+        zip.finish().unwrap();
+        drop(zip);
+
+        let saved = resave(sb3.get_ref());
+        assert_eq!(read_project_json(&saved), original);
+        let saved_again = resave(&saved);
+        assert_eq!(read_project_json(&saved_again), original);
+    }
+}
@@ -0,0 +1,109 @@
+//! Where projects are loaded from. A project is a `project.json` plus asset files named by their `md5ext`, which can
+//! be packed into an sb3 zip (on disk or in memory), unpacked into a directory, or split between a JSON file and a
+//! separate asset folder.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Cursor, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+use zip::ZipArchive;
+
+/// Where a project's asset files are read from, by file name.
+pub trait AssetSource {
+    fn read_asset(&mut self, name: &str) -> Result<Vec<u8>, &'static str>;
+}
+
+/// A complete project: its `project.json` and its assets.
+pub trait ProjectSource: AssetSource {
+    fn read_project_json(&mut self) -> Result<Value, &'static str>;
+}
+
+fn parse_project_json(json: &[u8]) -> Result<Value, &'static str> {
+    serde_json::from_slice(json).map_err(|_| "Could not deserialize JSON")
+}
+
+impl<R: Read + Seek> AssetSource for ZipArchive<R> {
+    fn read_asset(&mut self, name: &str) -> Result<Vec<u8>, &'static str> {
+        let mut asset_file = self.by_name(name).map_err(|_| "asset not found in zip")?;
+        let mut asset_data: Vec<u8> = Vec::with_capacity(asset_file.size() as usize);
+        asset_file
+            .read_to_end(&mut asset_data)
+            .map_err(|_| "could not read asset file")?;
+        Ok(asset_data)
+    }
+}
+
+/// An sb3 (or sb2) archive. Works over anything seekable, so a project can be loaded from a file, or from bytes that are
+/// already in memory (see `zip_from_bytes`).
+impl<R: Read + Seek> ProjectSource for ZipArchive<R> {
+    fn read_project_json(&mut self) -> Result<Value, &'static str> {
+        let mut json = Vec::new();
+        self.by_name("project.json")
+            .map_err(|_| "project.json not found")?
+            .read_to_end(&mut json)
+            .map_err(|_| "Could not read project.json")?;
+        parse_project_json(&json)
+    }
+}
+
+/// Open an archive that's already in memory, e.g. one embedded with `include_bytes!`.
+pub fn zip_from_bytes(data: &[u8]) -> Result<ZipArchive<Cursor<&[u8]>>, &'static str> {
+    ZipArchive::new(Cursor::new(data)).map_err(|_| "Could not open zip archive")
+}
+
+/// Assets that have already been extracted, e.g. from a Scratch 1.x project.
+impl AssetSource for HashMap<String, Vec<u8>> {
+    fn read_asset(&mut self, name: &str) -> Result<Vec<u8>, &'static str> {
+        self.get(name).cloned().ok_or("asset not found in project")
+    }
+}
+
+/// Read an asset from a folder. Asset names come from the project, so don't let them reach outside it.
+fn read_asset_file(dir: &Path, name: &str) -> Result<Vec<u8>, &'static str> {
+    if name.is_empty() || name.contains(['/', '\\']) || name == ".." {
+        return Err("invalid asset file name");
+    }
+    fs::read(dir.join(name)).map_err(|_| "asset not found in asset folder")
+}
+
+/// An unpacked sb3: a directory containing `project.json` and the asset files.
+pub struct ProjectDirectory {
+    pub path: PathBuf,
+}
+
+impl AssetSource for ProjectDirectory {
+    fn read_asset(&mut self, name: &str) -> Result<Vec<u8>, &'static str> {
+        read_asset_file(&self.path, name)
+    }
+}
+
+impl ProjectSource for ProjectDirectory {
+    fn read_project_json(&mut self) -> Result<Value, &'static str> {
+        let json =
+            fs::read(self.path.join("project.json")).map_err(|_| "project.json not found")?;
+        parse_project_json(&json)
+    }
+}
+
+/// A bare project.json file, with its assets in a separate folder.
+pub struct ProjectFile {
+    pub json_path: PathBuf,
+    pub assets_path: PathBuf,
+}
+
+impl AssetSource for ProjectFile {
+    fn read_asset(&mut self, name: &str) -> Result<Vec<u8>, &'static str> {
+        read_asset_file(&self.assets_path, name)
+    }
+}
+
+impl ProjectSource for ProjectFile {
+    fn read_project_json(&mut self) -> Result<Value, &'static str> {
+        let json = fs::read(&self.json_path).map_err(|_| "Could not read project JSON file")?;
+        parse_project_json(&json)
+    }
+}
@@ -0,0 +1,347 @@
+//! Loading single sprites from `.sprite3` files (a `sprite.json` holding one target, plus its assets) into a project
+//! that's already loaded or running.
+//!
+//! The sprite is merged the same way Scratch does when a sprite is uploaded:
+//! - If its name is taken, it gets a number on the end.
+//! - Its local variables and lists keep their values, but are renamed if they'd shadow a global of the same name.
+//! - Any variable or list it uses but doesn't own binds to the stage's global with the same name, or becomes a new
+//!   local one if there's no such global.
+//! - Broadcasts bind to the stage's broadcast with the same (case-insensitive) name. New broadcasts are kept on the
+//!   sprite, since the stage can't change once a project is running.
+//! - IDs that collide with ones already in the project are replaced.
+
+use crate::{
+    data::asset_store::AssetStore,
+    deserialize::deserialize_target,
+    engine::{engine_data::EngineData, project::Project, target::Target},
+    load_error::LoadError,
+    renderer::renderer::Renderer,
+    source::AssetSource,
+};
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Read, Seek},
+};
+
+use serde_json::{Map, Value};
+use zip::ZipArchive;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum RefKind {
+    Variable,
+    List,
+    Broadcast,
+}
+
+impl RefKind {
+    fn from_primitive(code: &Value) -> Option<Self> {
+        match code.as_u64()? {
+            11 => Some(RefKind::Broadcast),
+            12 => Some(RefKind::Variable),
+            13 => Some(RefKind::List),
+            _ => None,
+        }
+    }
+
+    fn from_field(field_name: &str) -> Option<Self> {
+        match field_name {
+            "VARIABLE" => Some(RefKind::Variable),
+            "LIST" => Some(RefKind::List),
+            "BROADCAST_OPTION" => Some(RefKind::Broadcast),
+            _ => None,
+        }
+    }
+
+    /// Key in the target JSON that this kind of object is declared under.
+    fn key(self) -> &'static str {
+        match self {
+            RefKind::Variable => "variables",
+            RefKind::List => "lists",
+            RefKind::Broadcast => "broadcasts",
+        }
+    }
+}
+
+/// Scratch's `StringUtil.unusedName`: if `name` is taken, replace any trailing digits with the lowest number (from 2)
+/// that makes it unique.
+fn unused_name<'n>(name: &str, existing: impl Iterator<Item = &'n str> + Clone) -> String {
+    if !existing.clone().any(|other| other == name) {
+        return name.to_string();
+    }
+    let base = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    (2..)
+        .map(|i| format!("{}{}", base, i))
+        .find(|candidate| !existing.clone().any(|other| other == candidate))
+        .unwrap()
+}
+
+fn unused_id(id: &str, used_ids: &mut HashSet<String>) -> String {
+    let id = if used_ids.contains(id) {
+        (2..)
+            .map(|i| format!("{}-{}", id, i))
+            .find(|candidate| !used_ids.contains(candidate))
+            .unwrap()
+    } else {
+        id.to_string()
+    };
+    used_ids.insert(id.clone());
+    id
+}
+
+type RefVisitor<'v> = dyn FnMut(RefKind, &mut Value, &mut Value) + 'v;
+
+/// Visit an input's `[11/12/13, name, id]` primitive, if it is one.
+fn visit_primitive(primitive: &mut Value, visit: &mut RefVisitor) {
+    if let Value::Array(primitive) = primitive {
+        if primitive.len() < 3 {
+            return;
+        }
+        if let Some(kind) = RefKind::from_primitive(&primitive[0]) {
+            let (name, id) = primitive[1..].split_at_mut(1);
+            visit(kind, &mut name[0], &mut id[0]);
+        }
+    }
+}
+
+/// Call `visit` with the name and ID of every variable, list, and broadcast that a target's blocks refer to.
+fn visit_refs(blocks: &mut Map<String, Value>, visit: &mut RefVisitor) {
+    for block in blocks.values_mut() {
+        match block {
+            // Top-level variable or list reporters are stored as bare primitives.
+            Value::Array(_) => visit_primitive(block, visit),
+            Value::Object(block) => {
+                if let Some(Value::Object(inputs)) = block.get_mut("inputs") {
+                    for input in inputs.values_mut() {
+                        if let Value::Array(input) = input {
+                            for descriptor in input.iter_mut().skip(1) {
+                                visit_primitive(descriptor, visit);
+                            }
+                        }
+                    }
+                }
+                if let Some(Value::Object(fields)) = block.get_mut("fields") {
+                    for (field_name, field) in fields.iter_mut() {
+                        if let (Some(kind), Value::Array(field)) =
+                            (RefKind::from_field(field_name), field)
+                        {
+                            if field.len() >= 2 {
+                                let (name, id) = field.split_at_mut(1);
+                                visit(kind, &mut name[0], &mut id[0]);
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Apply the merge rules described at the top of this module to a serialized sprite, so that it can be deserialized
+/// alongside `existing`.
+fn merge_sprite(sprite: &mut Map<String, Value>, existing: &[&Target]) -> Result<(), &'static str> {
+    if sprite.get("isStage").and_then(Value::as_bool) != Some(false) {
+        return Err("sprite.json does not contain a sprite");
+    }
+    let stage = existing.iter().find(|target| target.is_stage);
+
+    let sprite_name = unused_name(
+        sprite
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or("target has no name")?,
+        existing
+            .iter()
+            .filter(|target| !target.is_stage)
+            .map(|target| target.name.as_str()),
+    );
+    sprite.insert("name".to_string(), Value::String(sprite_name.clone()));
+    let layer_order = existing
+        .iter()
+        .map(|target| target.layer_order + 1)
+        .max()
+        .unwrap_or(0);
+    sprite.insert("layerOrder".to_string(), Value::from(layer_order));
+
+    let mut used_ids: HashSet<String> = existing
+        .iter()
+        .flat_map(|target| {
+            let variable_ids = target.variables.iter().map(|variable| variable.id.clone());
+            let list_ids = target.lists.iter().map(|list| list.id.clone());
+            let broadcast_ids = target
+                .broadcasts
+                .iter()
+                .map(|broadcast| broadcast.id.clone());
+            variable_ids.chain(list_ids).chain(broadcast_ids)
+        })
+        .collect();
+    // The stage's ID and name for a global, if there is one.
+    let global = |kind: RefKind, name: &str| -> Option<(String, String)> {
+        let stage = stage?;
+        match kind {
+            RefKind::Variable => stage
+                .variables
+                .iter()
+                .find(|variable| variable.name == name)
+                .map(|variable| (variable.id.clone(), variable.name.clone())),
+            RefKind::List => stage
+                .lists
+                .iter()
+                .find(|list| list.name == name)
+                .map(|list| (list.id.clone(), list.name.clone())),
+            RefKind::Broadcast => stage
+                .broadcasts
+                .iter()
+                .find(|broadcast| broadcast.name.eq_ignore_ascii_case(name))
+                .map(|broadcast| (broadcast.id.clone(), broadcast.name.clone())),
+        }
+    };
+
+    // Maps each of the sprite's original IDs to its new ID and name.
+    let mut renames: HashMap<(RefKind, String), (String, String)> = HashMap::new();
+    let mut declared: HashMap<RefKind, Map<String, Value>> = HashMap::new();
+
+    for kind in [RefKind::Variable, RefKind::List] {
+        let locals = match sprite.get(kind.key()) {
+            Some(Value::Object(locals)) => locals.clone(),
+            Some(_) => return Err("target has malformed variables"),
+            None => Map::new(),
+        };
+        let mut names: Vec<String> = locals
+            .values()
+            .filter_map(|local| local.get(0).and_then(Value::as_str))
+            .map(String::from)
+            .collect();
+        let mut merged = Map::new();
+        for (id, mut local) in locals {
+            let name = local
+                .get(0)
+                .and_then(Value::as_str)
+                .ok_or("variable has no name")?
+                .to_string();
+            let new_name = if global(kind, &name).is_some() {
+                let new_name = unused_name(
+                    &format!("{}: {}", sprite_name, name),
+                    names.iter().map(String::as_str),
+                );
+                names.push(new_name.clone());
+                new_name
+            } else {
+                name
+            };
+            let new_id = unused_id(&id, &mut used_ids);
+            local[0] = Value::String(new_name.clone());
+            renames.insert((kind, id), (new_id.clone(), new_name));
+            merged.insert(new_id, local);
+        }
+        declared.insert(kind, merged);
+    }
+    declared.insert(RefKind::Broadcast, Map::new());
+
+    let mut blocks = match sprite.remove("blocks") {
+        Some(Value::Object(blocks)) => blocks,
+        _ => return Err("target has no blocks"),
+    };
+    visit_refs(&mut blocks, &mut |kind, name, id| {
+        let (old_name, old_id) = match (name.as_str(), id.as_str()) {
+            (Some(name), Some(id)) => (name.to_string(), id.to_string()),
+            _ => return,
+        };
+        let key = (kind, old_id.clone());
+        let (new_id, new_name) = if let Some(renamed) = renames.get(&key) {
+            renamed.clone()
+        } else {
+            let merged = match global(kind, &old_name) {
+                Some(global) => global,
+                None => {
+                    // Declare it on the sprite.
+                    let new_id = unused_id(&old_id, &mut used_ids);
+                    let declaration = match kind {
+                        RefKind::Variable => {
+                            Value::Array(vec![Value::from(old_name.as_str()), Value::from(0)])
+                        }
+                        RefKind::List => {
+                            Value::Array(vec![Value::from(old_name.as_str()), Value::Array(vec![])])
+                        }
+                        RefKind::Broadcast => Value::from(old_name.as_str()),
+                    };
+                    declared
+                        .get_mut(&kind)
+                        .unwrap()
+                        .insert(new_id.clone(), declaration);
+                    (new_id, old_name.clone())
+                }
+            };
+            renames.insert(key, merged.clone());
+            merged
+        };
+        *name = Value::String(new_name);
+        *id = Value::String(new_id);
+    });
+    sprite.insert("blocks".to_string(), Value::Object(blocks));
+
+    for (kind, declarations) in declared {
+        sprite.insert(kind.key().to_string(), Value::Object(declarations));
+    }
+
+    Ok(())
+}
+
+/// Load the sprite in a `.sprite3` archive so it can be added to a project whose targets are `existing`. The new target
+/// is layered above all of them.
+///
+/// Its assets are loaded through the project's `store`, so any it shares with the project are only decoded once.
+///
+/// To add it to a project that hasn't started running yet, use `add_sprite_to_project`. To add it to a running project,
+/// pass in `Runtime::targets` and the project's store, and then hand the result to `Runtime::add_sprite`.
+pub fn load_sprite<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    existing: &[&Target],
+    store: &mut AssetStore,
+    eng_data: &EngineData,
+    renderer: &mut dyn Renderer,
+) -> Result<Target, LoadError> {
+    let mut json = String::new();
+    {
+        let mut sprite_json_file = archive
+            .by_name("sprite.json")
+            .map_err(|_| "sprite.json not found")?;
+        sprite_json_file
+            .read_to_string(&mut json)
+            .map_err(|_| "Could not read sprite.json")?;
+    }
+
+    let mut sprite = match serde_json::from_str(&json) {
+        Ok(Value::Object(sprite)) => sprite,
+        _ => return Err("Could not deserialize JSON".into()),
+    };
+    merge_sprite(&mut sprite, existing)?;
+
+    deserialize_target(
+        &sprite,
+        archive as &mut dyn AssetSource,
+        store,
+        eng_data,
+        renderer,
+    )
+}
+
+/// Load the sprite in a `.sprite3` archive and add it to a project that isn't running yet.
+pub fn add_sprite_to_project<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    project: &mut Project,
+    eng_data: &EngineData,
+    renderer: &mut dyn Renderer,
+) -> Result<(), LoadError> {
+    let existing: Vec<&Target> = project.targets.iter().collect();
+    let sprite = load_sprite(
+        archive,
+        &existing,
+        project.asset_store.get_mut(),
+        eng_data,
+        renderer,
+    )?;
+    project.targets.push(sprite);
+    Ok(())
+}